@@ -0,0 +1,972 @@
+use crate::{
+    discriminators, BindRecipientClientAccounts, BindRecipientIx, CancelWithdrawClientAccounts,
+    CancelWithdrawIx, ClearMetadataClientAccounts, ClearMetadataIx, CloseClientAccounts, CloseIx,
+    ConfigureClientAccounts, ConfigureIx, DepositToOwnerClientAccounts, DepositToOwnerIx,
+    ExecuteWithdrawClientAccounts, ExecuteWithdrawIx, GlobalConfig, GlobalConfigSeeds,
+    InitializeClientAccounts, InitializeIx, LogLevel, ReadStateClientAccounts, ReadStateIx,
+    ReadStateResult, RequestWithdrawClientAccounts, RequestWithdrawIx, SafeCloseIx,
+    SetAdminClientAccounts, SetAdminIx, SetAutoForwardClientAccounts, SetAutoForwardIx,
+    SetBurnDustClientAccounts, SetBurnDustIx, SetCloseAuthorityClientAccounts, SetCloseAuthorityIx,
+    SetDepositLimitsClientAccounts, SetDepositLimitsIx, SetFirstWithdrawDelayClientAccounts,
+    SetFirstWithdrawDelayIx, SetGuardiansClientAccounts, SetGuardiansIx,
+    SetKeeperSweepClientAccounts, SetKeeperSweepIx, SetLabelClientAccounts, SetLabelIx,
+    SetLogLevelClientAccounts, SetLogLevelIx, SetMaxDepositPerTxClientAccounts,
+    SetMaxDepositPerTxIx, SetMaxSlotAgeClientAccounts, SetMaxSlotAgeIx, SetMetadataClientAccounts,
+    SetMetadataIx, SetRentBufferClientAccounts, SetRentBufferIx, SetRoundToClientAccounts,
+    SetRoundToIx, SetSandwichGuardClientAccounts, SetSandwichGuardIx,
+    SetWithdrawCooldownClientAccounts, SetWithdrawCooldownIx, SetWithdrawPolicyClientAccounts,
+    SetWithdrawPolicyIx, SetYieldProgramClientAccounts, SetYieldProgramIx, VaultIxSet,
+    VaultMetadata, VaultMetadataSeeds, VaultProgram, VaultSeeds, VaultState, VaultStateSeeds,
+    WithdrawClientAccounts, WithdrawIx, WithdrawPercentIx, WithdrawPolicy,
+    WithdrawToProgramClientAccounts, WithdrawToProgramIx, WithdrawTokenBatchClientAccounts,
+    WithdrawTokenBatchIx, WithdrawWithApprovalClientAccounts, WithdrawWithApprovalIx,
+    MAX_GUARDIANS, SOL_CURRENCY, TOKEN_PROGRAM_ID,
+};
+use star_frame::{prelude::*, SolanaInstruction};
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    pubkey!("ComputeBudget111111111111111111111111111111");
+
+/// The instructions sysvar, which [`WithdrawWithApprovalIx`] reads the approval signature from
+pub const INSTRUCTIONS_SYSVAR_ID: Pubkey = pubkey!("Sysvar1nstructions1111111111111111111111111");
+
+// `ComputeBudgetInstruction::SetComputeUnitLimit` tag
+const SET_COMPUTE_UNIT_LIMIT_TAG: u8 = 2;
+
+// Benched by `test_compute_unit_benchmarking`, which records its runs in
+// `benches/results/compute_units.md` and fails if one needs more than these. Each is the benched
+// usage plus ~20% headroom, rounded up; rerun the bench and resize them when a handler changes.
+pub const INITIALIZE_CU: u32 = 8_500;
+pub const DEPOSIT_CU: u32 = 4_500;
+pub const WITHDRAW_CU: u32 = 5_000;
+// The sandwich guard loading and scanning the instructions ahead of a withdrawal. Benched behind
+// a compute budget instruction, more or wider instructions ahead of it cost more.
+pub const SANDWICH_GUARD_CU: u32 = 2_000;
+pub const CLOSE_CU: u32 = 5_000;
+pub const TIP_CU: u32 = 6_000;
+// Re-derives the state PDA from the owner on top of a checked deposit
+pub const DEPOSIT_TO_OWNER_CU: u32 = 8_000;
+// A system transfer plus the token program's `SyncNative`
+pub const WRAP_DEPOSIT_CU: u32 = 12_000;
+// Plus `WITHDRAW_TOKEN_BATCH_RECIPIENT_CU` for each recipient, benched with 8
+pub const WITHDRAW_TOKEN_BATCH_CU: u32 = 10_000;
+// A token program transfer
+pub const WITHDRAW_TOKEN_BATCH_RECIPIENT_CU: u32 = 5_000;
+pub const REQUEST_WITHDRAW_CU: u32 = 5_000;
+pub const EXECUTE_WITHDRAW_CU: u32 = 6_000;
+pub const CANCEL_WITHDRAW_CU: u32 = 4_000;
+// Two transfers out of the vault, to the owner and the keeper
+pub const KEEPER_SWEEP_CU: u32 = 8_000;
+pub const SET_WITHDRAW_COOLDOWN_CU: u32 = 4_000;
+pub const SET_LABEL_CU: u32 = 5_000;
+// Benched with every field set, label validation dominates
+pub const CONFIGURE_CU: u32 = 6_000;
+pub const ASSERT_BALANCE_CU: u32 = 3_000;
+
+// Benched as well, but not yet in the recorded results, so these are still estimates from the
+// instructions they resemble. The bench fails if one falls short.
+pub const INITIALIZE_AND_DEPOSIT_CU: u32 = 9_000;
+pub const SET_YIELD_PROGRAM_CU: u32 = 4_000;
+pub const SET_BURN_DUST_CU: u32 = 4_000;
+pub const SET_ROUND_TO_CU: u32 = 4_000;
+pub const SET_MAX_DEPOSIT_PER_TX_CU: u32 = 4_000;
+pub const SET_DEPOSIT_LIMITS_CU: u32 = 4_000;
+pub const SET_RENT_BUFFER_CU: u32 = 4_000;
+pub const SET_FIRST_WITHDRAW_DELAY_CU: u32 = 4_000;
+// Creating the metadata account the first time dominates
+pub const SET_METADATA_CU: u32 = 12_000;
+pub const CLEAR_METADATA_CU: u32 = 5_000;
+pub const SET_KEEPER_SWEEP_CU: u32 = 4_000;
+pub const SET_SANDWICH_GUARD_CU: u32 = 4_000;
+pub const SET_MAX_SLOT_AGE_CU: u32 = 4_000;
+pub const SET_LOG_LEVEL_CU: u32 = 4_000;
+pub const SET_ADMIN_CU: u32 = 4_000;
+pub const SET_CLOSE_AUTHORITY_CU: u32 = 4_000;
+pub const SET_WITHDRAW_POLICY_CU: u32 = 4_000;
+pub const SET_GUARDIANS_CU: u32 = 5_000;
+pub const BIND_RECIPIENT_CU: u32 = 4_000;
+pub const SET_AUTO_FORWARD_CU: u32 = 4_000;
+// A second, signed transfer out of the vault when `auto_forward` is set
+pub const AUTO_FORWARD_CU: u32 = 3_000;
+// Compares every passed signer against each guardian
+pub const GUARDIAN_RECOVER_CU: u32 = 6_000;
+// Plus `CLOSE_MANY_VAULT_CU` for each vault, see `close_many_cu`
+pub const CLOSE_MANY_CU: u32 = 3_000;
+// Each vault derives its vault address and moves two balances
+pub const CLOSE_MANY_VAULT_CU: u32 = 7_000;
+pub const WITHDRAW_WITH_APPROVAL_CU: u32 = 10_000;
+pub const RESIZE_STATE_CU: u32 = 6_000;
+pub const RECOVER_STATE_LAMPORTS_CU: u32 = 4_000;
+// Derives the canonical bump with `find_program_address`, which can take many attempts
+pub const NORMALIZE_BUMP_CU: u32 = 30_000;
+pub const READ_STATE_CU: u32 = 3_000;
+// `find_program_address`, like `NORMALIZE_BUMP_CU`
+pub const DERIVE_VAULT_CU: u32 = 30_000;
+pub const INITIALIZE_GLOBAL_CONFIG_CU: u32 = 8_500;
+pub const SET_EMERGENCY_PAUSED_CU: u32 = 4_000;
+pub const SET_ALLOWLIST_ROOT_CU: u32 = 4_000;
+pub const INITIALIZE_SHARED_CU: u32 = 12_000;
+pub const WITHDRAW_SHARED_CU: u32 = 6_000;
+
+// Per allowlist proof node passed to `InitializeIx`, one sha256 syscall plus the data it copies
+pub const ALLOWLIST_PROOF_NODE_CU: u32 = 300;
+
+// Harvest runs an arbitrary yield program, so it isn't benched and only the default
+// per-instruction limit is safe
+pub const HARVEST_CU: u32 = 200_000;
+// A stake program CPI, plus `find_program_address` for the stake account
+pub const INITIALIZE_STAKE_VAULT_CU: u32 = 40_000;
+pub const DELEGATE_CU: u32 = 40_000;
+pub const DEACTIVATE_CU: u32 = 30_000;
+pub const WITHDRAW_STAKE_CU: u32 = 30_000;
+
+/// Compute unit limit for `ix`: benched usage plus headroom for the instructions
+/// `test_compute_unit_benchmarking` has recorded, an estimate for the others.
+///
+/// [`CloseManyIx`](crate::CloseManyIx) only lists its vaults in the accounts, so this covers a
+/// single one. Use [`close_many_cu`], or [`with_compute_unit_limit`] which counts them.
+pub fn recommended_cu(ix: &VaultIxSet) -> u32 {
+    match ix {
+        VaultIxSet::Initialize(ix) => INITIALIZE_CU + proof_cu(&ix.proof),
+        VaultIxSet::InitializeAndDeposit(ix) => INITIALIZE_AND_DEPOSIT_CU + proof_cu(&ix.proof),
+        // The raw deposit is benched cheaper than the checked one. Whether the vault forwards
+        // isn't known from the instruction, so leave room for it
+        VaultIxSet::Deposit(_) => DEPOSIT_CU + AUTO_FORWARD_CU,
+        VaultIxSet::DepositRaw(_) => DEPOSIT_CU,
+        VaultIxSet::Tip(_) => TIP_CU,
+        VaultIxSet::DepositToOwner(_) => DEPOSIT_TO_OWNER_CU,
+        VaultIxSet::WrapDeposit(_) => WRAP_DEPOSIT_CU,
+        VaultIxSet::WithdrawTokenBatch(ix) => {
+            let recipients = ix.amounts.len() as u32;
+            WITHDRAW_TOKEN_BATCH_CU
+                .saturating_add(recipients.saturating_mul(WITHDRAW_TOKEN_BATCH_RECIPIENT_CU))
+        }
+        // Whether the vault has the sandwich guard on isn't known from the instruction either
+        VaultIxSet::Withdraw(_)
+        | VaultIxSet::WithdrawPercent(_)
+        | VaultIxSet::WithdrawToProgram(_) => WITHDRAW_CU + SANDWICH_GUARD_CU,
+        VaultIxSet::WithdrawWithApproval(_) => WITHDRAW_WITH_APPROVAL_CU + SANDWICH_GUARD_CU,
+        VaultIxSet::RequestWithdraw(_) => REQUEST_WITHDRAW_CU,
+        VaultIxSet::ExecuteWithdraw(_) => EXECUTE_WITHDRAW_CU,
+        VaultIxSet::CancelWithdraw(_) => CANCEL_WITHDRAW_CU,
+        VaultIxSet::KeeperSweep(_) => KEEPER_SWEEP_CU,
+        VaultIxSet::Close(_) | VaultIxSet::SafeClose(_) => CLOSE_CU,
+        VaultIxSet::CloseMany(_) => close_many_cu(1),
+        VaultIxSet::SetYieldProgram(_) => SET_YIELD_PROGRAM_CU,
+        VaultIxSet::SetWithdrawCooldown(_) => SET_WITHDRAW_COOLDOWN_CU,
+        VaultIxSet::SetBurnDust(_) => SET_BURN_DUST_CU,
+        VaultIxSet::SetRoundTo(_) => SET_ROUND_TO_CU,
+        VaultIxSet::SetMaxDepositPerTx(_) => SET_MAX_DEPOSIT_PER_TX_CU,
+        VaultIxSet::SetDepositLimits(_) => SET_DEPOSIT_LIMITS_CU,
+        VaultIxSet::SetRentBuffer(_) => SET_RENT_BUFFER_CU,
+        VaultIxSet::SetFirstWithdrawDelay(_) => SET_FIRST_WITHDRAW_DELAY_CU,
+        VaultIxSet::SetLabel(_) => SET_LABEL_CU,
+        VaultIxSet::SetMetadata(_) => SET_METADATA_CU,
+        VaultIxSet::ClearMetadata(_) => CLEAR_METADATA_CU,
+        VaultIxSet::SetKeeperSweep(_) => SET_KEEPER_SWEEP_CU,
+        VaultIxSet::SetSandwichGuard(_) => SET_SANDWICH_GUARD_CU,
+        VaultIxSet::SetMaxSlotAge(_) => SET_MAX_SLOT_AGE_CU,
+        VaultIxSet::SetLogLevel(_) => SET_LOG_LEVEL_CU,
+        VaultIxSet::SetAdmin(_) => SET_ADMIN_CU,
+        VaultIxSet::SetCloseAuthority(_) => SET_CLOSE_AUTHORITY_CU,
+        VaultIxSet::SetWithdrawPolicy(_) => SET_WITHDRAW_POLICY_CU,
+        VaultIxSet::SetGuardians(_) => SET_GUARDIANS_CU,
+        VaultIxSet::BindRecipient(_) => BIND_RECIPIENT_CU,
+        VaultIxSet::SetAutoForward(_) => SET_AUTO_FORWARD_CU,
+        VaultIxSet::GuardianRecover(_) => GUARDIAN_RECOVER_CU,
+        VaultIxSet::Configure(_) => CONFIGURE_CU,
+        VaultIxSet::Harvest(_) => HARVEST_CU,
+        VaultIxSet::InitializeStakeVault(_) => INITIALIZE_STAKE_VAULT_CU,
+        VaultIxSet::Delegate(_) => DELEGATE_CU,
+        VaultIxSet::Deactivate(_) => DEACTIVATE_CU,
+        VaultIxSet::WithdrawStake(_) => WITHDRAW_STAKE_CU,
+        VaultIxSet::InitializeShared(_) => INITIALIZE_SHARED_CU,
+        VaultIxSet::WithdrawShared(_) => WITHDRAW_SHARED_CU,
+        VaultIxSet::ResizeState(_) => RESIZE_STATE_CU,
+        VaultIxSet::RecoverStateLamports(_) => RECOVER_STATE_LAMPORTS_CU,
+        VaultIxSet::NormalizeBump(_) => NORMALIZE_BUMP_CU,
+        VaultIxSet::AssertBalance(_) => ASSERT_BALANCE_CU,
+        VaultIxSet::ReadState(_) => READ_STATE_CU,
+        VaultIxSet::DeriveVault(_) => DERIVE_VAULT_CU,
+        VaultIxSet::InitializeGlobalConfig(_) => INITIALIZE_GLOBAL_CONFIG_CU,
+        VaultIxSet::SetEmergencyPaused(_) => SET_EMERGENCY_PAUSED_CU,
+        VaultIxSet::SetAllowlistRoot(_) => SET_ALLOWLIST_ROOT_CU,
+    }
+}
+
+fn proof_cu(proof: &[[u8; 32]]) -> u32 {
+    (proof.len() as u32).saturating_mul(ALLOWLIST_PROOF_NODE_CU)
+}
+
+/// Compute unit limit for a [`CloseManyIx`](crate::CloseManyIx) closing `vaults` vaults
+pub fn close_many_cu(vaults: usize) -> u32 {
+    CLOSE_MANY_CU.saturating_add((vaults as u32).saturating_mul(CLOSE_MANY_VAULT_CU))
+}
+
+/// Builds a `SetComputeUnitLimit` compute budget instruction
+pub fn set_compute_unit_limit(units: u32) -> SolanaInstruction {
+    let mut data = Vec::with_capacity(5);
+    data.push(SET_COMPUTE_UNIT_LIMIT_TAG);
+    data.extend_from_slice(&units.to_le_bytes());
+    SolanaInstruction {
+        program_id: COMPUTE_BUDGET_PROGRAM_ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Prepends a compute unit limit sized by [`recommended_cu`] to the vault `instruction`.
+///
+/// The limit is per transaction, so callers batching several vault instructions should
+/// sum the [`recommended_cu`] values and use [`set_compute_unit_limit`] directly.
+pub fn with_compute_unit_limit(
+    ix: &VaultIxSet,
+    instruction: SolanaInstruction,
+) -> [SolanaInstruction; 2] {
+    let units = match ix {
+        // The user and system program come first, then a vault and state for each vault
+        VaultIxSet::CloseMany(_) => close_many_cu(instruction.accounts.len().saturating_sub(2) / 2),
+        _ => recommended_cu(ix),
+    };
+    [set_compute_unit_limit(units), instruction]
+}
+
+/// Base fee for a single-signature transaction, a reasonable `fee_buffer` for [`check_deposit_funds`]
+pub const SIGNATURE_FEE_LAMPORTS: u64 = 5_000;
+
+/// Stricter pre-flight version of the on-chain `DepositIx` balance check. It also requires
+/// `fee_buffer` lamports to remain, e.g. to pay for later transactions; a buffer of 0 matches
+/// the program, which sees the balance after this transaction's fee was already charged.
+pub fn check_deposit_funds(balance: u64, amount: u64, fee_buffer: u64) -> Result<()> {
+    let required = amount
+        .checked_add(fee_buffer)
+        .ok_or_else(|| anyhow!("Deposit amount overflows"))?;
+    if balance < required {
+        bail!("Insufficient funds: {balance} lamports available, {required} required");
+    }
+    Ok(())
+}
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// Converts a SOL amount to lamports, rounding to the nearest lamport.
+/// Negative and NaN amounts become 0, amounts too large for a `u64` saturate.
+pub fn sol_to_lamports(sol: f64) -> u64 {
+    (sol * LAMPORTS_PER_SOL as f64).round() as u64
+}
+
+/// Converts lamports to SOL. Only exact up to 2^53 lamports, use [`Sol`] for display.
+pub fn lamports_to_sol(lamports: u64) -> f64 {
+    lamports as f64 / LAMPORTS_PER_SOL as f64
+}
+
+/// Displays a lamport amount in SOL without going through a float, e.g. `1.5 SOL`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Sol(pub u64);
+
+impl std::fmt::Display for Sol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let whole = self.0 / LAMPORTS_PER_SOL;
+        let fraction = self.0 % LAMPORTS_PER_SOL;
+        if fraction == 0 {
+            write!(f, "{whole} SOL")
+        } else {
+            let fraction = format!("{fraction:09}");
+            write!(f, "{whole}.{} SOL", fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+// `owner`'s vault state and vault addresses
+fn vault_addresses(owner: &Pubkey) -> (Pubkey, Pubkey) {
+    let (vault_state, _) = VaultState::find_program_address(&VaultStateSeeds { owner: *owner });
+    let (vault, _) = Pubkey::find_program_address(
+        &VaultSeeds { state: vault_state }.seeds(),
+        &VaultProgram::ID,
+    );
+    (vault_state, vault)
+}
+
+fn global_config_address() -> Pubkey {
+    GlobalConfig::find_program_address(&GlobalConfigSeeds).0
+}
+
+fn metadata_address(vault_state: Pubkey) -> Pubkey {
+    VaultMetadata::find_program_address(&VaultMetadataSeeds { state: vault_state }).0
+}
+
+/// Builds an [`InitializeIx`] opening `owner`'s SOL vault at its canonical addresses, for
+/// deployments without an allowlist
+pub fn initialize_instruction(owner: &Pubkey) -> Result<SolanaInstruction> {
+    initialize_instruction_with_proof(owner, vec![])
+}
+
+/// Like [`initialize_instruction`], with the allowlist `proof` for `owner`
+pub fn initialize_instruction_with_proof(
+    owner: &Pubkey,
+    proof: Vec<[u8; 32]>,
+) -> Result<SolanaInstruction> {
+    let (state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &InitializeIx {
+            proof,
+            currency: SOL_CURRENCY,
+        },
+        InitializeClientAccounts {
+            owner: *owner,
+            state,
+            vault,
+            system_program: None,
+            global_config: global_config_address(),
+        },
+    )
+}
+
+/// Builds a [`DepositToOwnerIx`] moving `amount` from `funder` into `owner`'s vault. A vault with
+/// `auto_forward` set needs [`DepositToOwnerAccounts::forward_to`](crate::DepositToOwnerAccounts)
+/// as well.
+pub fn deposit_to_owner_instruction(
+    funder: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &DepositToOwnerIx {
+            owner: *owner,
+            amount,
+        },
+        DepositToOwnerClientAccounts {
+            funder: *funder,
+            vault,
+            vault_state,
+            system_program: None,
+            global_config: global_config_address(),
+            forward_to: None,
+        },
+    )
+}
+
+/// Builds a [`WithdrawIx`] paying `amount` from `owner`'s vault to the owner. A vault with the
+/// sandwich guard on or a bound recipient needs the optional
+/// [`WithdrawAccounts`](crate::WithdrawAccounts) as well.
+pub fn withdraw_instruction(owner: &Pubkey, amount: u64) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &WithdrawIx { amount },
+        withdraw_accounts(owner, vault_state, vault),
+    )
+}
+
+/// Like [`withdraw_instruction`], for `bps` basis points of the withdrawable balance
+pub fn withdraw_percent_instruction(owner: &Pubkey, bps: u16) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &WithdrawPercentIx { bps },
+        withdraw_accounts(owner, vault_state, vault),
+    )
+}
+
+fn withdraw_accounts(owner: &Pubkey, vault_state: Pubkey, vault: Pubkey) -> WithdrawClientAccounts {
+    WithdrawClientAccounts {
+        user: *owner,
+        vault,
+        vault_state,
+        system_program: None,
+        global_config: global_config_address(),
+        instructions_sysvar: None,
+        recipient: None,
+    }
+}
+
+/// Builds a [`WithdrawToProgramIx`] paying `amount` from `owner`'s vault to `destination`
+pub fn withdraw_to_program_instruction(
+    owner: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &WithdrawToProgramIx { amount },
+        WithdrawToProgramClientAccounts {
+            user: *owner,
+            vault,
+            vault_state,
+            destination: *destination,
+            system_program: None,
+            global_config: global_config_address(),
+            instructions_sysvar: None,
+        },
+    )
+}
+
+/// Builds a [`WithdrawTokenBatchIx`] paying `amounts` of the tokens in `source`, a token
+/// account of `owner`'s vault, to the matching `recipients`
+pub fn withdraw_token_batch_instruction(
+    owner: &Pubkey,
+    source: &Pubkey,
+    recipients: &[Pubkey],
+    amounts: Vec<u64>,
+) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    let mut instruction = VaultProgram::instruction(
+        &WithdrawTokenBatchIx { amounts },
+        WithdrawTokenBatchClientAccounts {
+            user: *owner,
+            vault,
+            vault_state,
+            source: *source,
+            token_program: TOKEN_PROGRAM_ID,
+            global_config: global_config_address(),
+            recipients: recipients.to_vec(),
+        },
+    )?;
+    // The recipients are a `Rest` of plain accounts, which come out read-only
+    let recipients_start = instruction.accounts.len() - recipients.len();
+    for meta in &mut instruction.accounts[recipients_start..] {
+        meta.is_writable = true;
+    }
+    Ok(instruction)
+}
+
+/// Builds a [`WithdrawWithApprovalIx`] paying `owner`'s vault out to `recipient`. It must follow
+/// an Ed25519 program instruction verifying the owner's signature over the approval.
+pub fn withdraw_with_approval_instruction(
+    owner: &Pubkey,
+    recipient: &Pubkey,
+    approval: WithdrawWithApprovalIx,
+) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &approval,
+        WithdrawWithApprovalClientAccounts {
+            recipient: *recipient,
+            vault,
+            vault_state,
+            instructions_sysvar: INSTRUCTIONS_SYSVAR_ID,
+            system_program: None,
+            global_config: global_config_address(),
+        },
+    )
+}
+
+/// Builds a [`RequestWithdrawIx`] escrowing `amount` of `owner`'s vault for `recipient` until
+/// `release_slot`
+pub fn request_withdraw_instruction(
+    owner: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+    release_slot: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &RequestWithdrawIx {
+            recipient: *recipient,
+            amount,
+            release_slot,
+        },
+        RequestWithdrawClientAccounts {
+            user: *owner,
+            vault,
+            vault_state,
+            global_config: global_config_address(),
+        },
+    )
+}
+
+/// Builds an [`ExecuteWithdrawIx`] paying out the request of `owner`'s vault to `recipient`.
+/// Anyone can send it once the request is released.
+pub fn execute_withdraw_instruction(
+    owner: &Pubkey,
+    recipient: &Pubkey,
+) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &ExecuteWithdrawIx,
+        ExecuteWithdrawClientAccounts {
+            vault,
+            vault_state,
+            recipient: *recipient,
+            system_program: None,
+            global_config: global_config_address(),
+        },
+    )
+}
+
+/// Builds a [`CancelWithdrawIx`] dropping the pending request of `owner`'s vault
+pub fn cancel_withdraw_instruction(owner: &Pubkey) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &CancelWithdrawIx,
+        CancelWithdrawClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`CloseIx`] closing `owner`'s vault, signed by the owner. A vault that burns dust,
+/// has a bound recipient or metadata needs the optional [`CloseAccounts`](crate::CloseAccounts)
+/// as well.
+pub fn close_instruction(owner: &Pubkey) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(&CloseIx, close_accounts(owner, vault_state, vault))
+}
+
+/// Like [`close_instruction`], failing unless the vault only holds what withdrawals must leave
+pub fn safe_close_instruction(owner: &Pubkey) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(&SafeCloseIx, close_accounts(owner, vault_state, vault))
+}
+
+fn close_accounts(owner: &Pubkey, vault_state: Pubkey, vault: Pubkey) -> CloseClientAccounts {
+    CloseClientAccounts {
+        user: *owner,
+        vault,
+        vault_state,
+        system_program: None,
+        burn_address: None,
+        recipient: None,
+        owner: None,
+        metadata: None,
+    }
+}
+
+/// Builds a [`SetYieldProgramIx`] for `owner`'s vault
+pub fn set_yield_program_instruction(
+    owner: &Pubkey,
+    yield_program: &Pubkey,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetYieldProgramIx {
+            yield_program: *yield_program,
+        },
+        SetYieldProgramClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetWithdrawCooldownIx`] for `owner`'s vault
+pub fn set_withdraw_cooldown_instruction(
+    owner: &Pubkey,
+    cooldown_slots: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetWithdrawCooldownIx { cooldown_slots },
+        SetWithdrawCooldownClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetBurnDustIx`] for `owner`'s vault
+pub fn set_burn_dust_instruction(owner: &Pubkey, burn_dust: bool) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetBurnDustIx { burn_dust },
+        SetBurnDustClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetRoundToIx`] for `owner`'s vault
+pub fn set_round_to_instruction(owner: &Pubkey, round_to: u64) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetRoundToIx { round_to },
+        SetRoundToClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetMaxDepositPerTxIx`] for `owner`'s vault, signed by its `admin`
+pub fn set_max_deposit_per_tx_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    max_deposit_per_tx: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetMaxDepositPerTxIx { max_deposit_per_tx },
+        SetMaxDepositPerTxClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetDepositLimitsIx`] for `owner`'s vault, signed by its `admin`
+pub fn set_deposit_limits_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    min_deposit: u64,
+    balance_cap: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetDepositLimitsIx {
+            min_deposit,
+            balance_cap,
+        },
+        SetDepositLimitsClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetRentBufferIx`] for `owner`'s vault, signed by its `admin`
+pub fn set_rent_buffer_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    rent_buffer_bps: u16,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetRentBufferIx { rent_buffer_bps },
+        SetRentBufferClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetFirstWithdrawDelayIx`] for `owner`'s vault
+pub fn set_first_withdraw_delay_instruction(
+    owner: &Pubkey,
+    first_withdraw_delay: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetFirstWithdrawDelayIx {
+            first_withdraw_delay,
+        },
+        SetFirstWithdrawDelayClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetLabelIx`] for `owner`'s vault
+pub fn set_label_instruction(owner: &Pubkey, label: Vec<u8>) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetLabelIx { label },
+        SetLabelClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetMetadataIx`] for `owner`'s vault, creating its metadata account if needed
+pub fn set_metadata_instruction(owner: &Pubkey, data: Vec<u8>) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetMetadataIx { data },
+        SetMetadataClientAccounts {
+            user: *owner,
+            vault_state,
+            metadata: metadata_address(vault_state),
+            system_program: None,
+        },
+    )
+}
+
+/// Builds a [`ClearMetadataIx`] closing the metadata account of `owner`'s vault
+pub fn clear_metadata_instruction(owner: &Pubkey) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &ClearMetadataIx,
+        ClearMetadataClientAccounts {
+            user: *owner,
+            vault_state,
+            metadata: metadata_address(vault_state),
+        },
+    )
+}
+
+/// Builds a [`SetKeeperSweepIx`] for `owner`'s vault
+pub fn set_keeper_sweep_instruction(
+    owner: &Pubkey,
+    sweep_threshold: u64,
+    keeper_tip: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetKeeperSweepIx {
+            sweep_threshold,
+            keeper_tip,
+        },
+        SetKeeperSweepClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetSandwichGuardIx`] for `owner`'s vault, signed by its `admin`
+pub fn set_sandwich_guard_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    sandwich_guard: bool,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetSandwichGuardIx { sandwich_guard },
+        SetSandwichGuardClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetMaxSlotAgeIx`] for `owner`'s vault, signed by its `admin`
+pub fn set_max_slot_age_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    max_slot_age: u64,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetMaxSlotAgeIx { max_slot_age },
+        SetMaxSlotAgeClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetWithdrawPolicyIx`] for `owner`'s vault
+pub fn set_withdraw_policy_instruction(
+    owner: &Pubkey,
+    policy: WithdrawPolicy,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetWithdrawPolicyIx {
+            policy: policy as u8,
+        },
+        SetWithdrawPolicyClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetLogLevelIx`] for `owner`'s vault, signed by its `admin`
+pub fn set_log_level_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    log_level: LogLevel,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetLogLevelIx {
+            log_level: log_level as u8,
+        },
+        SetLogLevelClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetAdminIx`] handing the admin role of `owner`'s vault to `new_admin`, signed by
+/// the current admin or the owner
+pub fn set_admin_instruction(
+    signer: &Pubkey,
+    owner: &Pubkey,
+    new_admin: &Pubkey,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetAdminIx { admin: *new_admin },
+        SetAdminClientAccounts {
+            user: *signer,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetCloseAuthorityIx`] for `owner`'s vault
+pub fn set_close_authority_instruction(
+    owner: &Pubkey,
+    close_authority: &Pubkey,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetCloseAuthorityIx {
+            close_authority: *close_authority,
+        },
+        SetCloseAuthorityClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`BindRecipientIx`] for `owner`'s vault
+pub fn bind_recipient_instruction(owner: &Pubkey, recipient: &Pubkey) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &BindRecipientIx {
+            recipient: *recipient,
+        },
+        BindRecipientClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetAutoForwardIx`] for `owner`'s vault
+pub fn set_auto_forward_instruction(
+    owner: &Pubkey,
+    auto_forward: &Pubkey,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetAutoForwardIx {
+            auto_forward: *auto_forward,
+        },
+        SetAutoForwardClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`SetGuardiansIx`] for `owner`'s vault
+pub fn set_guardians_instruction(
+    owner: &Pubkey,
+    guardians: [Pubkey; MAX_GUARDIANS],
+    threshold: u8,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        &SetGuardiansIx {
+            guardians,
+            threshold,
+        },
+        SetGuardiansClientAccounts {
+            user: *owner,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`ConfigureIx`] for `owner`'s vault, signed by its `admin`. Fields only the owner
+/// may set need the owner as `admin`.
+pub fn configure_instruction(
+    admin: &Pubkey,
+    owner: &Pubkey,
+    configure: &ConfigureIx,
+) -> Result<SolanaInstruction> {
+    let (vault_state, _) = vault_addresses(owner);
+    VaultProgram::instruction(
+        configure,
+        ConfigureClientAccounts {
+            user: *admin,
+            vault_state,
+        },
+    )
+}
+
+/// Builds a [`ReadStateIx`] for `owner`'s vault, to simulate and decode with
+/// [`decode_read_state_return_data`]
+pub fn read_state_instruction(owner: &Pubkey) -> Result<SolanaInstruction> {
+    let (vault_state, vault) = vault_addresses(owner);
+    VaultProgram::instruction(&ReadStateIx, ReadStateClientAccounts { vault, vault_state })
+}
+
+/// Decodes the return data of a simulated [`ReadStateIx`].
+///
+/// The runtime strips trailing zero bytes from return data, so a shorter buffer is padded back
+/// out with zeros. Anything longer than a [`ReadStateResult`] was not returned by this program.
+pub fn decode_read_state_return_data(data: &[u8]) -> Result<ReadStateResult> {
+    if data.len() > size_of::<ReadStateResult>() {
+        bail!("Return data is larger than a read state result");
+    }
+    let mut result = ReadStateResult::default();
+    bytemuck::bytes_of_mut(&mut result)[..data.len()].copy_from_slice(data);
+    Ok(result)
+}
+
+/// The parts of an on chain account that [`ensure_vault`] looks at
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchedAccount {
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// Looks up accounts on chain, implemented for the nonblocking `RpcClient` with the `rpc` feature
+pub trait AccountFetcher {
+    /// Returns `None` if nothing is stored at `address`
+    fn get_account(
+        &self,
+        address: &Pubkey,
+    ) -> impl std::future::Future<Output = Result<Option<FetchedAccount>>>;
+}
+
+#[cfg(feature = "rpc")]
+impl AccountFetcher for solana_rpc_client::nonblocking::rpc_client::RpcClient {
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<FetchedAccount>> {
+        let response = self
+            .get_account_with_commitment(address, self.commitment())
+            .await?;
+        Ok(response.value.map(|account| FetchedAccount {
+            owner: account.owner,
+            data: account.data,
+        }))
+    }
+}
+
+/// Outcome of [`ensure_vault`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnsuredVault {
+    /// The vault state already exists at this address
+    Existing(Pubkey),
+    /// No vault yet, `initialize` creates one at `state` and must be signed by the owner
+    Missing {
+        state: Pubkey,
+        initialize: SolanaInstruction,
+    },
+}
+
+impl EnsuredVault {
+    /// The vault state address, whether or not it exists yet
+    pub fn state(&self) -> Pubkey {
+        match self {
+            Self::Existing(state) | Self::Missing { state, .. } => *state,
+        }
+    }
+}
+
+/// Returns `owner`'s vault state if it is initialized, or the instruction to create it
+///
+/// A prefunded state address still gets the instruction, [`InitializeIx`] tops up what is there.
+pub async fn ensure_vault(rpc: &impl AccountFetcher, owner: &Pubkey) -> Result<EnsuredVault> {
+    let (state, _) = VaultState::find_program_address(&VaultStateSeeds { owner: *owner });
+    // Anyone can send lamports to the state address, so only an initialized vault state counts
+    let initialized = rpc.get_account(&state).await?.is_some_and(|account| {
+        account.owner == VaultProgram::ID && account.data.starts_with(&discriminators::VAULT_STATE)
+    });
+    if initialized {
+        return Ok(EnsuredVault::Existing(state));
+    }
+    Ok(EnsuredVault::Missing {
+        state,
+        initialize: initialize_instruction(owner)?,
+    })
+}
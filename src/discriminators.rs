@@ -0,0 +1,115 @@
+//! Instruction and account discriminators, for clients and tests that build instructions or
+//! parse accounts by hand. They come from the same definitions the dispatcher matches on.
+
+use crate::*;
+use star_frame::instruction::InstructionDiscriminant;
+
+// Instructions
+pub const INITIALIZE: [u8; 8] = <InitializeIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const INITIALIZE_AND_DEPOSIT: [u8; 8] =
+    <InitializeAndDepositIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const DEPOSIT: [u8; 8] = <DepositIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const DEPOSIT_RAW: [u8; 8] =
+    <DepositRawIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const TIP: [u8; 8] = <TipIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const DEPOSIT_TO_OWNER: [u8; 8] =
+    <DepositToOwnerIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WRAP_DEPOSIT: [u8; 8] =
+    <WrapDepositIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW_TOKEN_BATCH: [u8; 8] =
+    <WithdrawTokenBatchIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW: [u8; 8] = <WithdrawIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW_PERCENT: [u8; 8] =
+    <WithdrawPercentIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW_TO_PROGRAM: [u8; 8] =
+    <WithdrawToProgramIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW_WITH_APPROVAL: [u8; 8] =
+    <WithdrawWithApprovalIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const REQUEST_WITHDRAW: [u8; 8] =
+    <RequestWithdrawIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const EXECUTE_WITHDRAW: [u8; 8] =
+    <ExecuteWithdrawIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const CANCEL_WITHDRAW: [u8; 8] =
+    <CancelWithdrawIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const KEEPER_SWEEP: [u8; 8] =
+    <KeeperSweepIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const CLOSE: [u8; 8] = <CloseIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SAFE_CLOSE: [u8; 8] = <SafeCloseIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const CLOSE_MANY: [u8; 8] = <CloseManyIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_YIELD_PROGRAM: [u8; 8] =
+    <SetYieldProgramIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_WITHDRAW_COOLDOWN: [u8; 8] =
+    <SetWithdrawCooldownIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_BURN_DUST: [u8; 8] =
+    <SetBurnDustIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_ROUND_TO: [u8; 8] =
+    <SetRoundToIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_MAX_DEPOSIT_PER_TX: [u8; 8] =
+    <SetMaxDepositPerTxIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_DEPOSIT_LIMITS: [u8; 8] =
+    <SetDepositLimitsIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_RENT_BUFFER: [u8; 8] =
+    <SetRentBufferIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_FIRST_WITHDRAW_DELAY: [u8; 8] =
+    <SetFirstWithdrawDelayIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_LABEL: [u8; 8] = <SetLabelIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_METADATA: [u8; 8] =
+    <SetMetadataIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const CLEAR_METADATA: [u8; 8] =
+    <ClearMetadataIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_KEEPER_SWEEP: [u8; 8] =
+    <SetKeeperSweepIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_SANDWICH_GUARD: [u8; 8] =
+    <SetSandwichGuardIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_MAX_SLOT_AGE: [u8; 8] =
+    <SetMaxSlotAgeIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_LOG_LEVEL: [u8; 8] =
+    <SetLogLevelIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_ADMIN: [u8; 8] = <SetAdminIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_CLOSE_AUTHORITY: [u8; 8] =
+    <SetCloseAuthorityIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_WITHDRAW_POLICY: [u8; 8] =
+    <SetWithdrawPolicyIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_GUARDIANS: [u8; 8] =
+    <SetGuardiansIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const BIND_RECIPIENT: [u8; 8] =
+    <BindRecipientIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_AUTO_FORWARD: [u8; 8] =
+    <SetAutoForwardIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const GUARDIAN_RECOVER: [u8; 8] =
+    <GuardianRecoverIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const CONFIGURE: [u8; 8] = <ConfigureIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const HARVEST: [u8; 8] = <HarvestIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const INITIALIZE_STAKE_VAULT: [u8; 8] =
+    <InitializeStakeVaultIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const DELEGATE: [u8; 8] = <DelegateIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const DEACTIVATE: [u8; 8] = <DeactivateIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW_STAKE: [u8; 8] =
+    <WithdrawStakeIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const INITIALIZE_SHARED: [u8; 8] =
+    <InitializeSharedIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const WITHDRAW_SHARED: [u8; 8] =
+    <WithdrawSharedIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const RESIZE_STATE: [u8; 8] =
+    <ResizeStateIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const RECOVER_STATE_LAMPORTS: [u8; 8] =
+    <RecoverStateLamportsIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const NORMALIZE_BUMP: [u8; 8] =
+    <NormalizeBumpIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const ASSERT_BALANCE: [u8; 8] =
+    <AssertBalanceIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const READ_STATE: [u8; 8] = <ReadStateIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const DERIVE_VAULT: [u8; 8] =
+    <DeriveVaultIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const INITIALIZE_GLOBAL_CONFIG: [u8; 8] =
+    <InitializeGlobalConfigIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_EMERGENCY_PAUSED: [u8; 8] =
+    <SetEmergencyPausedIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+pub const SET_ALLOWLIST_ROOT: [u8; 8] =
+    <SetAllowlistRootIx as InstructionDiscriminant<VaultIxSet>>::DISCRIMINANT;
+
+// Accounts
+pub const VAULT_STATE: [u8; 8] = <VaultState as ProgramAccount>::DISCRIMINANT;
+pub const GLOBAL_CONFIG: [u8; 8] = <GlobalConfig as ProgramAccount>::DISCRIMINANT;
+pub const SHARED_VAULT_STATE: [u8; 8] = <SharedVaultState as ProgramAccount>::DISCRIMINANT;
+pub const VAULT_METADATA: [u8; 8] = <VaultMetadata as ProgramAccount>::DISCRIMINANT;
@@ -1,22 +1,118 @@
+use solana_sha256_hasher::hashv;
 use star_frame::{
-    anyhow::ensure,
+    account_set::CanAddLamports,
+    data_types::PodBool,
+    pinocchio::{
+        cpi::{slice_invoke, slice_invoke_signed},
+        instruction::{Instruction as PinocchioInstruction, Seed, Signer as PinocchioSigner},
+        sysvars::instructions::Instructions,
+    },
     prelude::*,
-    program::system::{Transfer, TransferCpiAccounts},
+    program::system::{
+        Allocate, AllocateCpiAccounts, Assign, AssignCpiAccounts, CreateAccount,
+        CreateAccountCpiAccounts, Transfer, TransferCpiAccounts,
+    },
 };
 
+/// Like `ensure!`, but fails with `error`'s code so clients can match on the condition. The
+/// message only goes to the logs.
+macro_rules! require {
+    ($condition:expr, $error:expr, $($message:tt)+) => {
+        if !$condition {
+            return Err($error.context(format!($($message)+)));
+        }
+    };
+}
+
+pub mod client;
+pub mod discriminators;
+
+/// The vault program's address, for callers that don't go through [`StarFrameProgram`].
+pub const PROGRAM_ID: Pubkey = pubkey!("GxpAtbXpkbDu5b86TidcmuF5RF9UJm821rqJ5W3S4T12");
+
 #[derive(StarFrameProgram)]
 #[program(
     instruction_set = VaultIxSet,
-    id = "GxpAtbXpkbDu5b86TidcmuF5RF9UJm821rqJ5W3S4T12"
+    id = PROGRAM_ID,
+    skip_idl
 )]
 pub struct VaultProgram;
 
+// Written out instead of derived so the events, which no instruction references, reach the IDL
+#[cfg(all(feature = "idl", not(target_os = "solana")))]
+impl ProgramToIdl for VaultProgram {
+    fn crate_metadata() -> star_frame::star_frame_idl::CrateMetadata {
+        star_frame::star_frame_idl::CrateMetadata {
+            docs: vec![],
+            ..star_frame::crate_metadata!()
+        }
+    }
+
+    fn modify_idl(idl_definition: &mut star_frame::star_frame_idl::IdlDefinition) -> Result<()> {
+        events_to_idl(idl_definition)
+    }
+}
+
 #[derive(InstructionSet)]
 pub enum VaultIxSet {
     Initialize(InitializeIx),
+    InitializeAndDeposit(InitializeAndDepositIx),
     Deposit(DepositIx),
+    DepositRaw(DepositRawIx),
+    Tip(TipIx),
+    DepositToOwner(DepositToOwnerIx),
+    WrapDeposit(WrapDepositIx),
+    WithdrawTokenBatch(WithdrawTokenBatchIx),
     Withdraw(WithdrawIx),
+    WithdrawPercent(WithdrawPercentIx),
+    WithdrawToProgram(WithdrawToProgramIx),
+    WithdrawWithApproval(WithdrawWithApprovalIx),
+    RequestWithdraw(RequestWithdrawIx),
+    ExecuteWithdraw(ExecuteWithdrawIx),
+    CancelWithdraw(CancelWithdrawIx),
+    KeeperSweep(KeeperSweepIx),
     Close(CloseIx),
+    SafeClose(SafeCloseIx),
+    CloseMany(CloseManyIx),
+    SetYieldProgram(SetYieldProgramIx),
+    SetWithdrawCooldown(SetWithdrawCooldownIx),
+    SetBurnDust(SetBurnDustIx),
+    SetRoundTo(SetRoundToIx),
+    SetMaxDepositPerTx(SetMaxDepositPerTxIx),
+    SetDepositLimits(SetDepositLimitsIx),
+    SetRentBuffer(SetRentBufferIx),
+    SetFirstWithdrawDelay(SetFirstWithdrawDelayIx),
+    SetLabel(SetLabelIx),
+    SetMetadata(SetMetadataIx),
+    ClearMetadata(ClearMetadataIx),
+    SetKeeperSweep(SetKeeperSweepIx),
+    SetSandwichGuard(SetSandwichGuardIx),
+    SetMaxSlotAge(SetMaxSlotAgeIx),
+    SetLogLevel(SetLogLevelIx),
+    SetAdmin(SetAdminIx),
+    SetCloseAuthority(SetCloseAuthorityIx),
+    SetWithdrawPolicy(SetWithdrawPolicyIx),
+    SetGuardians(SetGuardiansIx),
+    BindRecipient(BindRecipientIx),
+    SetAutoForward(SetAutoForwardIx),
+    GuardianRecover(GuardianRecoverIx),
+    Configure(ConfigureIx),
+    Harvest(HarvestIx),
+    InitializeStakeVault(InitializeStakeVaultIx),
+    Delegate(DelegateIx),
+    Deactivate(DeactivateIx),
+    WithdrawStake(WithdrawStakeIx),
+    InitializeShared(InitializeSharedIx),
+    WithdrawShared(WithdrawSharedIx),
+    ResizeState(ResizeStateIx),
+    RecoverStateLamports(RecoverStateLamportsIx),
+    NormalizeBump(NormalizeBumpIx),
+    AssertBalance(AssertBalanceIx),
+    ReadState(ReadStateIx),
+    DeriveVault(DeriveVaultIx),
+    InitializeGlobalConfig(InitializeGlobalConfigIx),
+    SetEmergencyPaused(SetEmergencyPausedIx),
+    SetAllowlistRoot(SetAllowlistRootIx),
 }
 
 /* -------------------- PDA Seeds -------------------- */
@@ -33,29 +129,816 @@ pub struct VaultSeeds {
     pub state: Pubkey,
 }
 
+#[derive(Debug, GetSeeds, Clone)]
+#[get_seeds(seed_const = b"CONFIG")]
+pub struct GlobalConfigSeeds;
+
+#[derive(Debug, GetSeeds, Clone)]
+#[get_seeds(seed_const = b"STAKE")]
+pub struct StakeVaultSeeds {
+    pub vault: Pubkey,
+}
+
 /* -------------------- Program Account -------------------- */
 
 #[zero_copy(pod)]
-#[derive(Default, Debug, Eq, PartialEq, ProgramAccount)]
+#[derive(Debug, Eq, PartialEq, ProgramAccount)]
 #[program_account(seeds = VaultStateSeeds)]
 pub struct VaultState {
     pub owner: Pubkey,
     pub state_bump: u8,
     pub vault_bump: u8,
+    // External program that sweeps yield into the vault, `Pubkey::default()` when unset
+    pub yield_program: Pubkey,
+    // Lamports received through `TipIx` from anyone, kept apart from owner deposits
+    pub total_tips: u64,
+    // Next nonce accepted by `WithdrawWithApprovalIx`, bumped on every use
+    pub withdraw_nonce: u64,
+    // Minimum slots between withdrawals, 0 disables the cooldown
+    pub cooldown_slots: u64,
+    pub last_withdraw_slot: u64,
+    // Send a dust balance to `BURN_ADDRESS` on close instead of back to the owner
+    pub burn_dust: PodBool,
+    // Largest amount a single `DepositIx` may add, 0 for no limit
+    pub max_deposit_per_tx: u64,
+    // Largest withdrawable balance deposits and tips may fill the vault to, 0 for no limit
+    pub balance_cap: u64,
+    // Smallest deposit or tip accepted, except one that fills the vault exactly to `balance_cap`
+    pub min_deposit: u64,
+    // Extra lamports kept on top of the vault's rent-exempt minimum, in basis points of it,
+    // so a future rent increase can't leave the vault below the minimum
+    pub rent_buffer_bps: u16,
+    // Slot the vault was opened at
+    pub init_slot: u64,
+    // Slots after `init_slot` before the first withdrawal or close, 0 for none
+    pub first_withdraw_delay: u64,
+    // Display name set with `SetLabelIx`, UTF-8 padded with trailing nulls
+    pub label: [u8; MAX_LABEL_LEN],
+    // Withdrawable balance `KeeperSweepIx` leaves in the vault, 0 disables keeper sweeps
+    pub sweep_threshold: u64,
+    // Lamports of each keeper sweep paid to the keeper
+    pub keeper_tip: u64,
+    // Withdrawal requested with `RequestWithdrawIx`, held in the vault until
+    // `pending_release_slot`. A zero `pending_amount` means none is pending.
+    pub pending_recipient: Pubkey,
+    pub pending_amount: u64,
+    pub pending_release_slot: u64,
+    // Reject withdrawals placed after an instruction that could pay into the vault in the same
+    // transaction, see `ensure_no_prior_vault_write`
+    pub sandwich_guard: PodBool,
+    // Asset the vault is denominated in for display, e.g. "SOL", ASCII padded with trailing
+    // nulls. Set by `InitializeIx` and never changed. All zeros on vaults opened before it existed.
+    pub currency: [u8; CURRENCY_LEN],
+    // Keys that can hand the vault to a new owner with `GuardianRecoverIx`, unused slots are
+    // `Pubkey::default()`
+    pub guardians: [Pubkey; MAX_GUARDIANS],
+    // Guardian signatures `GuardianRecoverIx` needs, 0 disables recovery
+    pub guardian_threshold: u8,
+    // Only address withdrawals may pay once set with `BindRecipientIx`, which can't be undone.
+    // `Pubkey::default()` while unbound.
+    pub bound_recipient: Pubkey,
+    // Key that sets the vault's limits and policy, see `VaultAdmin`. `Pubkey::default()` leaves
+    // it to the owner.
+    pub admin: Pubkey,
+    // `WithdrawPolicy` selecting which withdrawal guards run
+    pub withdraw_policy: u8,
+    // Address every `DepositIx` is passed straight on to, `Pubkey::default()` when unset
+    pub auto_forward: Pubkey,
+    // Withdrawals are rounded down to a multiple of this, the remainder stays in the vault.
+    // 0 disables rounding.
+    pub round_to: u64,
+    // Most slots ahead a relayed approval's `valid_until_slot` may be, 0 for no limit
+    pub max_slot_age: u64,
+    // Key that can close the vault besides the owner, see `VaultCloser`. `Pubkey::default()`
+    // leaves it to the owner alone.
+    pub close_authority: Pubkey,
+    // `LogLevel` selecting which of the vault's events are logged
+    pub log_level: u8,
+    // Set while the vault has a `VaultMetadata` account, which closing then has to close too
+    pub has_metadata: PodBool,
+    // Token accounts of the vault last seen with a balance, see `track_token_account`. Closing is
+    // refused while any is set. Unused slots are `Pubkey::default()`.
+    pub token_accounts: [Pubkey; MAX_TOKEN_ACCOUNTS],
+    // Set while the stake account opened with `InitializeStakeVaultIx` exists, closing is refused
+    // until `WithdrawStakeIx` empties it
+    pub has_stake_account: PodBool,
+}
+
+/// Which of the vault's withdrawal guards run, so a vault only pays for the checks it uses.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum WithdrawPolicy {
+    /// Every guard, as before the policy existed
+    All = 0,
+    /// No guard
+    None = 1,
+    /// Only `first_withdraw_delay`
+    Timelock = 2,
+    /// Only the recipient bound with [`BindRecipientIx`]
+    Whitelist = 3,
+    /// Only `cooldown_slots`
+    RateLimit = 4,
+}
+
+/// Which of a vault's events are logged, on builds with the `verbose-logs` feature. Lower levels
+/// log more, and a vault logs the events at its level and above.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    /// Every event, as before the level existed
+    Detailed = 0,
+    /// Only the vault being opened and closed
+    Summary = 1,
+    /// No event
+    Silent = 2,
+}
+
+impl TryFrom<u8> for LogLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value {
+            0 => Self::Detailed,
+            1 => Self::Summary,
+            2 => Self::Silent,
+            _ => {
+                return Err(VaultError::InvalidArgument.context("Unknown log level"));
+            }
+        })
+    }
+}
+
+impl TryFrom<u8> for WithdrawPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value {
+            0 => Self::All,
+            1 => Self::None,
+            2 => Self::Timelock,
+            3 => Self::Whitelist,
+            4 => Self::RateLimit,
+            _ => {
+                return Err(VaultError::InvalidArgument.context("Unknown withdraw policy"));
+            }
+        })
+    }
+}
+
+// `PodBool` has no `Default`, and an all-zero state is the empty one
+impl Default for VaultState {
+    fn default() -> Self {
+        Self::zeroed()
+    }
 }
 
 /* Let the account validate itself */
 impl AccountValidate<&Pubkey> for VaultState {
     fn validate_account(self_ref: &Self::Ref<'_>, owner: &Pubkey) -> Result<()> {
-        ensure!(self_ref.owner == *owner, "Incorrect owner");
+        require!(
+            self_ref.owner == *owner,
+            VaultError::Unauthorized,
+            "Incorrect owner"
+        );
+        Ok(())
+    }
+}
+
+/// Validates against [`VaultState::admin`] instead of the owner, for instructions that set the
+/// vault's limits and policy. Only the owner can move funds, whoever the admin is, so settings
+/// that could pay out or hold back the owner's funds stay with the owner.
+#[derive(Debug, Clone, Copy)]
+pub struct VaultAdmin<'a>(pub &'a Pubkey);
+
+impl AccountValidate<VaultAdmin<'_>> for VaultState {
+    fn validate_account(self_ref: &Self::Ref<'_>, admin: VaultAdmin<'_>) -> Result<()> {
+        require!(
+            self_ref.admin() == *admin.0,
+            VaultError::Unauthorized,
+            "Incorrect admin"
+        );
+        Ok(())
+    }
+}
+
+/// Validates against the admin or the owner, for [`SetAdminIx`], so the owner can always take
+/// back the role they gave away.
+#[derive(Debug, Clone, Copy)]
+pub struct VaultAdminOrOwner<'a>(pub &'a Pubkey);
+
+impl AccountValidate<VaultAdminOrOwner<'_>> for VaultState {
+    fn validate_account(self_ref: &Self::Ref<'_>, signer: VaultAdminOrOwner<'_>) -> Result<()> {
+        require!(
+            self_ref.admin() == *signer.0 || self_ref.owner == *signer.0,
+            VaultError::Unauthorized,
+            "Incorrect admin or owner"
+        );
+        Ok(())
+    }
+}
+
+/// Validates against the owner or [`VaultState::close_authority`], for instructions that close
+/// the vault. The vault and state balances still go to the owner.
+#[derive(Debug, Clone, Copy)]
+pub struct VaultCloser<'a>(pub &'a Pubkey);
+
+impl AccountValidate<VaultCloser<'_>> for VaultState {
+    fn validate_account(self_ref: &Self::Ref<'_>, closer: VaultCloser<'_>) -> Result<()> {
+        require!(
+            self_ref.owner == *closer.0 || self_ref.close_authority() == *closer.0,
+            VaultError::Unauthorized,
+            "Incorrect owner or close authority"
+        );
+        Ok(())
+    }
+}
+
+impl VaultState {
+    /// Copies the state out of `info`, defaulting trailing fields that an older, shorter layout
+    /// doesn't have yet. Use `ResizeStateIx` before writing to those fields.
+    pub fn read_tolerant(info: &AccountInfo) -> Result<Self> {
+        // Checks the program owner and discriminant, but not the data length
+        Self::validate_account_info(*info)?;
+
+        let data = info.try_borrow_data()?;
+        let fields = &data[size_of_val(&Self::DISCRIMINANT)..];
+        // The original layout ends after the bumps, anything shorter isn't a vault state
+        require!(
+            fields.len() >= std::mem::offset_of!(Self, yield_program),
+            VaultError::InvalidAccount,
+            "State account is too small"
+        );
+
+        let mut state = Self::default();
+        let len = fields.len().min(size_of::<Self>());
+        star_frame::bytemuck::bytes_of_mut(&mut state)[..len].copy_from_slice(&fields[..len]);
+        Ok(state)
+    }
+
+    /// Whether the vault's [`WithdrawPolicy`] runs `guard`.
+    pub fn guards(&self, guard: WithdrawPolicy) -> bool {
+        // Only `SetWithdrawPolicyIx` writes the policy, so it is always known
+        let policy = WithdrawPolicy::try_from(self.withdraw_policy).unwrap_or(WithdrawPolicy::All);
+        policy == WithdrawPolicy::All || policy == guard
+    }
+
+    /// Whether the vault's [`LogLevel`] logs events at `level`.
+    pub fn logs(&self, level: LogLevel) -> bool {
+        // Only `SetLogLevelIx` writes the level, so it is always known
+        let log_level = LogLevel::try_from(self.log_level).unwrap_or(LogLevel::Detailed);
+        level >= log_level
+    }
+
+    /// Fails until `first_withdraw_delay` slots have passed since the vault was opened, if the
+    /// policy runs the timelock.
+    pub fn ensure_first_withdraw_elapsed(&self, slot: u64) -> Result<()> {
+        if !self.guards(WithdrawPolicy::Timelock) {
+            return Ok(());
+        }
+        require!(
+            slot >= self.init_slot.saturating_add(self.first_withdraw_delay),
+            VaultError::WithdrawalLocked,
+            "First withdraw delay has not elapsed"
+        );
+        Ok(())
+    }
+
+    /// The label with its null padding trimmed, empty when unset.
+    pub fn label(&self) -> Result<&str> {
+        let len = self
+            .label
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |last| last + 1);
+        Ok(std::str::from_utf8(&self.label[..len])?)
+    }
+
+    /// The currency tag without its padding, empty if the vault predates it.
+    pub fn currency(&self) -> Result<&str> {
+        let len = self
+            .currency
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |last| last + 1);
+        Ok(std::str::from_utf8(&self.currency[..len])?)
+    }
+
+    /// The key allowed to set the vault's limits and policy, the owner unless [`SetAdminIx`]
+    /// handed it to someone else.
+    pub fn admin(&self) -> Pubkey {
+        if self.admin == Pubkey::default() {
+            self.owner
+        } else {
+            self.admin
+        }
+    }
+
+    /// The key allowed to close the vault for its owner, the owner unless
+    /// [`SetCloseAuthorityIx`] named someone else.
+    pub fn close_authority(&self) -> Pubkey {
+        if self.close_authority == Pubkey::default() {
+            self.owner
+        } else {
+            self.close_authority
+        }
+    }
+
+    /// The address every withdrawal must pay, if [`BindRecipientIx`] bound one.
+    pub fn bound_recipient(&self) -> Option<Pubkey> {
+        (self.bound_recipient != Pubkey::default()).then_some(self.bound_recipient)
+    }
+
+    /// `amount` rounded down to a multiple of `round_to`. Fails if that leaves nothing of a
+    /// non-zero amount, rather than paying out zero.
+    pub fn round_withdrawal(&self, amount: u64) -> Result<u64> {
+        if self.round_to == 0 {
+            return Ok(amount);
+        }
+        let rounded = amount - amount % self.round_to;
+        require!(
+            rounded != 0 || amount == 0,
+            VaultError::InvalidArgument,
+            "Amount is below the withdrawal rounding unit"
+        );
+        Ok(rounded)
+    }
+
+    /// Fails if a submission valid until `valid_until_slot` is stale at `slot`, or stays valid for
+    /// more than `max_slot_age` slots.
+    pub fn ensure_fresh(&self, slot: u64, valid_until_slot: u64) -> Result<()> {
+        require!(
+            slot <= valid_until_slot,
+            VaultError::InvalidApproval,
+            "Submission is past its valid_until_slot"
+        );
+        require!(
+            self.max_slot_age == 0 || valid_until_slot - slot <= self.max_slot_age,
+            VaultError::InvalidApproval,
+            "valid_until_slot is further ahead than max_slot_age"
+        );
+        Ok(())
+    }
+
+    /// The address deposits are forwarded to, if [`SetAutoForwardIx`] set one.
+    pub fn auto_forward(&self) -> Option<Pubkey> {
+        (self.auto_forward != Pubkey::default()).then_some(self.auto_forward)
+    }
+
+    /// Fails if the vault is bound to a recipient other than `recipient`.
+    pub fn ensure_recipient_allowed(&self, recipient: &Pubkey) -> Result<()> {
+        if let Some(bound_recipient) = self.bound_recipient() {
+            require!(
+                *recipient == bound_recipient,
+                VaultError::RecipientNotAllowed,
+                "Vault only pays its bound recipient"
+            );
+        }
+        Ok(())
+    }
+
+    /// Raises `first_withdraw_delay`. It's a commitment, so lowering it fails.
+    pub fn set_first_withdraw_delay(&mut self, first_withdraw_delay: u64) -> Result<()> {
+        require!(
+            first_withdraw_delay >= self.first_withdraw_delay,
+            VaultError::InvalidArgument,
+            "First withdraw delay can only be raised"
+        );
+        self.first_withdraw_delay = first_withdraw_delay;
+        Ok(())
+    }
+
+    /// Stores `label` null padded, rejecting anything over [`MAX_LABEL_LEN`] bytes.
+    pub fn set_label(&mut self, label: &[u8]) -> Result<()> {
+        require!(
+            label.len() <= MAX_LABEL_LEN,
+            VaultError::InvalidArgument,
+            "Label is longer than {MAX_LABEL_LEN} bytes"
+        );
+        // Wallets show the label as is, so only printable UTF-8 is accepted. This also rules out
+        // nulls, which would be trimmed as padding on read.
+        let text = std::str::from_utf8(label)
+            .map_err(|_| VaultError::InvalidArgument.context("Label is not valid UTF-8"))?;
+        require!(
+            !text.chars().any(char::is_control),
+            VaultError::InvalidArgument,
+            "Label contains control characters"
+        );
+
+        self.label = [0; MAX_LABEL_LEN];
+        self.label[..label.len()].copy_from_slice(label);
+        Ok(())
+    }
+
+    /// Enforces the first withdraw delay and cooldown the policy runs, and records `slot` as the
+    /// latest withdrawal.
+    pub fn record_withdraw(&mut self, slot: u64) -> Result<()> {
+        self.ensure_first_withdraw_elapsed(slot)?;
+        if self.guards(WithdrawPolicy::RateLimit) {
+            require!(
+                slot >= self.last_withdraw_slot.saturating_add(self.cooldown_slots),
+                VaultError::WithdrawalLocked,
+                "Withdraw cooldown has not elapsed"
+            );
+        }
+        self.last_withdraw_slot = slot;
+        Ok(())
+    }
+
+    /// Records what the vault's `token_account` holds after a transfer, so closing can't strand
+    /// a balance. Fails once [`MAX_TOKEN_ACCOUNTS`] other accounts hold a balance.
+    pub fn track_token_account(&mut self, token_account: &Pubkey, amount: u64) -> Result<()> {
+        if amount == 0 {
+            for held in self.token_accounts.iter_mut() {
+                if *held == *token_account {
+                    *held = Pubkey::default();
+                }
+            }
+            return Ok(());
+        }
+        if self.token_accounts.contains(token_account) {
+            return Ok(());
+        }
+        let slot = self
+            .token_accounts
+            .iter_mut()
+            .find(|held| **held == Pubkey::default())
+            .ok_or_else(|| {
+                VaultError::InvalidState.context("Vault already holds the most token accounts")
+            })?;
+        *slot = *token_account;
+        Ok(())
+    }
+
+    /// Fails while the vault holds tokens or stake, which closing the state would strand under
+    /// the vault address.
+    pub fn ensure_no_holdings(&self) -> Result<()> {
+        require!(
+            self.token_accounts
+                .iter()
+                .all(|held| *held == Pubkey::default()),
+            VaultError::VaultNotEmpty,
+            "Withdraw the vault's tokens before closing"
+        );
+        require!(
+            !self.has_stake_account.get(),
+            VaultError::VaultNotEmpty,
+            "Withdraw the vault's stake account before closing"
+        );
+        Ok(())
+    }
+
+    /// Lamports withdrawals must leave in the vault: its rent-exempt minimum plus `rent_buffer_bps`
+    /// of it, and any pending withdrawal held for [`ExecuteWithdrawIx`].
+    pub fn retained_lamports(&self, rent_exempt_lamports: u64) -> u64 {
+        let buffer = rent_exempt_lamports as u128 * self.rent_buffer_bps as u128 / 10_000;
+        rent_exempt_lamports
+            .saturating_add(buffer.try_into().unwrap_or(u64::MAX))
+            .saturating_add(self.pending_amount)
+    }
+
+    /// Enforces `min_deposit` and `balance_cap` for adding `amount` to a vault currently holding
+    /// `balance` withdrawable lamports.
+    ///
+    /// Without a minimum, anyone could keep a capped vault full with 1 lamport tips. Topping the
+    /// vault up to exactly the cap is always allowed, so the last gap below the minimum can be filled.
+    pub fn check_deposit_limits(&self, balance: u64, amount: u64) -> Result<()> {
+        let new_balance = balance
+            .checked_add(amount)
+            .ok_or_else(|| VaultError::BalanceOverflow.context("Vault balance overflow"))?;
+        if self.balance_cap != 0 && new_balance > self.balance_cap {
+            return Err(VaultError::BalanceCapExceeded.into());
+        }
+        let fills_cap = self.balance_cap != 0 && new_balance == self.balance_cap;
+        if amount < self.min_deposit && !fills_cap {
+            return Err(VaultError::DepositBelowMinimum.into());
+        }
+        Ok(())
+    }
+}
+
+/// Program-wide settings, a single PDA shared by every vault.
+#[zero_copy(pod)]
+#[derive(Debug, Eq, PartialEq, ProgramAccount)]
+#[program_account(seeds = GlobalConfigSeeds)]
+pub struct GlobalConfig {
+    pub admin: Pubkey,
+    pub bump: u8,
+    // Kill switch for protocol incidents. Blocks everything that moves funds on every vault,
+    // closing and `WithdrawStakeIx` are still allowed so owners can always get their funds back
+    pub emergency_paused: PodBool,
+    // Merkle root of the owners allowed to open vaults, all zeros lets anyone open one
+    pub allowlist_root: [u8; 32],
+}
+
+impl Default for GlobalConfig {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl AccountValidate<&Pubkey> for GlobalConfig {
+    fn validate_account(self_ref: &Self::Ref<'_>, admin: &Pubkey) -> Result<()> {
+        require!(
+            self_ref.admin == *admin,
+            VaultError::Unauthorized,
+            "Incorrect admin"
+        );
+        Ok(())
+    }
+}
+
+impl GlobalConfig {
+    pub fn ensure_not_paused(&self) -> Result<()> {
+        if self.emergency_paused.get() {
+            return Err(VaultError::EmergencyPaused.into());
+        }
+        Ok(())
+    }
+
+    /// Checks `proof` admits `owner` when an allowlist is set, otherwise anyone is allowed.
+    pub fn ensure_allowlisted(&self, owner: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+        if self.allowlist_root != [0; 32]
+            && !verify_allowlist_proof(&self.allowlist_root, owner, proof)
+        {
+            return Err(VaultError::NotAllowlisted.into());
+        }
         Ok(())
     }
 }
 
+// Leaves and inner nodes are hashed with different prefixes, so an inner node can't be passed
+// off as a leaf
+const ALLOWLIST_LEAF_PREFIX: &[u8] = &[0];
+const ALLOWLIST_NODE_PREFIX: &[u8] = &[1];
+
+/// Leaf committing to `owner` in the allowlist merkle tree.
+pub fn allowlist_leaf(owner: &Pubkey) -> [u8; 32] {
+    hashv(&[ALLOWLIST_LEAF_PREFIX, owner.as_ref()]).to_bytes()
+}
+
+/// Parent of two allowlist tree nodes. The pair is sorted first, so proofs don't need to say
+/// which side each sibling is on.
+pub fn allowlist_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[ALLOWLIST_NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Whether the sibling hashes in `proof`, from the leaf up, lead from `owner`'s leaf to `root`.
+pub fn verify_allowlist_proof(root: &[u8; 32], owner: &Pubkey, proof: &[[u8; 32]]) -> bool {
+    let computed = proof.iter().fold(allowlist_leaf(owner), |node, sibling| {
+        allowlist_node(&node, sibling)
+    });
+    computed == *root
+}
+
+/* -------------------- Errors -------------------- */
+
+/// Errors returned as `ProgramError::Custom(code)`. Every check in the program fails with one of
+/// these, so a condition always maps to the same code. Errors from star_frame's own account
+/// validation keep its codes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum VaultError {
+    /// The funder can't cover the transfer
+    InsufficientFunds = 6000,
+    /// A lamport transfer was rejected before reaching the system program
+    TransferFailed = 6001,
+    /// The deposit is larger than the vault's `max_deposit_per_tx`
+    DepositCapExceeded = 6002,
+    /// `AssertBalanceIx` found fewer lamports in the vault than expected
+    BalanceBelowExpected = 6003,
+    /// `SafeCloseIx` found withdrawable lamports left in the vault, or closing would strand the
+    /// vault's tokens or stake
+    VaultNotEmpty = 6004,
+    /// The global config has `emergency_paused` set
+    EmergencyPaused = 6005,
+    /// The deposit would take the vault above its `balance_cap`
+    BalanceCapExceeded = 6006,
+    /// The deposit is smaller than the vault's `min_deposit`
+    DepositBelowMinimum = 6007,
+    /// The owner isn't on the global config's allowlist
+    NotAllowlisted = 6008,
+    /// Closing moved a different amount to the owner than the vault and state held
+    CloseAccountingMismatch = 6009,
+    /// A deposit or running total would go past `u64::MAX`
+    BalanceOverflow = 6010,
+    /// The signer isn't the owner, admin or other key the instruction needs
+    Unauthorized = 6011,
+    /// An account isn't the one expected, or is missing
+    InvalidAccount = 6012,
+    /// An instruction argument is out of range or malformed
+    InvalidArgument = 6013,
+    /// The first withdraw delay, cooldown or hold on a requested withdrawal hasn't passed
+    WithdrawalLocked = 6014,
+    /// The vault can't pay this recipient
+    RecipientNotAllowed = 6015,
+    /// A requested withdrawal is pending and has to be executed or cancelled first
+    WithdrawalPending = 6016,
+    /// There's no requested withdrawal to execute or cancel
+    NoWithdrawalPending = 6017,
+    /// The signed approval for `WithdrawWithApprovalIx` is missing, stale or doesn't match
+    InvalidApproval = 6018,
+    /// The sandwich guard found a deposit into the vault earlier in the transaction
+    WithdrawalAfterDeposit = 6019,
+    /// The vault's settings don't allow this instruction
+    InvalidState = 6020,
+}
+
+impl VaultError {
+    /// The error with `message` attached for the logs, keeping its code.
+    pub(crate) fn context(
+        self,
+        message: impl std::fmt::Display + Send + Sync + 'static,
+    ) -> anyhow::Error {
+        anyhow::Error::from(self).context(message)
+    }
+}
+
+impl std::fmt::Display for VaultError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InsufficientFunds => write!(f, "Insufficient funds"),
+            Self::TransferFailed => write!(f, "Lamport transfer failed"),
+            Self::DepositCapExceeded => write!(f, "Deposit exceeds the per-transaction cap"),
+            Self::BalanceBelowExpected => write!(f, "Vault balance is below the expected minimum"),
+            Self::VaultNotEmpty => write!(f, "Vault still holds funds"),
+            Self::EmergencyPaused => write!(f, "Vaults are paused by the global config"),
+            Self::BalanceCapExceeded => write!(f, "Deposit exceeds the vault balance cap"),
+            Self::DepositBelowMinimum => write!(f, "Deposit is below the vault minimum"),
+            Self::NotAllowlisted => write!(f, "Owner is not allowlisted to open a vault"),
+            Self::CloseAccountingMismatch => {
+                write!(
+                    f,
+                    "Close refund does not match the vault and state balances"
+                )
+            }
+            Self::BalanceOverflow => write!(f, "Lamport amount overflows"),
+            Self::Unauthorized => write!(f, "Signer is not authorized"),
+            Self::InvalidAccount => write!(f, "Invalid account"),
+            Self::InvalidArgument => write!(f, "Invalid instruction argument"),
+            Self::WithdrawalLocked => write!(f, "Withdrawals are locked for now"),
+            Self::RecipientNotAllowed => write!(f, "Recipient is not allowed"),
+            Self::WithdrawalPending => write!(f, "A requested withdrawal is pending"),
+            Self::NoWithdrawalPending => write!(f, "No requested withdrawal is pending"),
+            Self::InvalidApproval => write!(f, "Invalid withdrawal approval"),
+            Self::WithdrawalAfterDeposit => {
+                write!(f, "Withdrawal follows a deposit into the vault")
+            }
+            Self::InvalidState => write!(f, "Not allowed by the vault settings"),
+        }
+    }
+}
+
+impl From<VaultError> for ProgramError {
+    fn from(error: VaultError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
+
+// star_frame returns the `ProgramError` found in the chain, the message only goes to the logs
+impl From<VaultError> for anyhow::Error {
+    fn from(error: VaultError) -> Self {
+        anyhow::Error::from(ProgramError::from(error)).context(error)
+    }
+}
+
+/// System transfer that surfaces failures as [`VaultError`] codes. A failing system program
+/// aborts the whole transaction without returning here, so the balance is checked up front.
+fn transfer_lamports(
+    funder: AccountInfo,
+    recipient: AccountInfo,
+    lamports: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if funder.lamports() < lamports {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    System::cpi(
+        Transfer { lamports },
+        TransferCpiAccounts { funder, recipient },
+        None,
+    )
+    .invoke_signed(signer_seeds)
+    .map_err(|error| error.context(ProgramError::from(VaultError::TransferFailed)))
+}
+
+/// Rent-exempt minimum of a vault PDA, which holds no data. The rent sysvar is read through a
+/// syscall that can fail, which comes back as a regular program error naming the sysvar.
+fn vault_rent_exempt_lamports(ctx: &Context) -> Result<u64> {
+    let rent = ctx
+        .get_rent()
+        .map_err(|error| error.context("Failed to read the rent sysvar"))?;
+    Ok(rent.minimum_balance(0))
+}
+
+/* -------------------- Events -------------------- */
+
+/// Events are logged as `Program data:` entries holding an `event:<Name>` sighash followed by the pod payload.
+pub trait VaultEvent: NoUninit {
+    const DISCRIMINATOR: [u8; 8];
+}
+
+/// Logs `event`, or does nothing unless the program is built with the `verbose-logs` feature.
+pub fn emit_event<E: VaultEvent>(event: &E) {
+    if cfg!(feature = "verbose-logs") {
+        star_frame::pinocchio::log::sol_log_data(&[
+            &E::DISCRIMINATOR,
+            star_frame::bytemuck::bytes_of(event),
+        ]);
+    }
+}
+
+// Every event type has to be listed here to show up in the IDL
+#[cfg(all(feature = "idl", not(target_os = "solana")))]
+fn events_to_idl(idl_definition: &mut star_frame::star_frame_idl::IdlDefinition) -> Result<()> {
+    VaultOpenedEvent::type_to_idl(idl_definition)?;
+    VaultClosedEvent::type_to_idl(idl_definition)?;
+    TipEvent::type_to_idl(idl_definition)?;
+    Ok(())
+}
+
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct VaultOpenedEvent {
+    pub owner: Pubkey,
+    pub state: Pubkey,
+    pub vault: Pubkey,
+}
+
+impl VaultEvent for VaultOpenedEvent {
+    const DISCRIMINATOR: [u8; 8] = star_frame::sighash!("event", "VaultOpenedEvent");
+}
+
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct VaultClosedEvent {
+    pub owner: Pubkey,
+    // Vault balance plus the state and metadata account rent returned to the owner
+    pub refunded: u64,
+}
+
+impl VaultEvent for VaultClosedEvent {
+    const DISCRIMINATOR: [u8; 8] = star_frame::sighash!("event", "VaultClosedEvent");
+}
+
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct TipEvent {
+    pub from: Pubkey,
+    pub amount: u64,
+}
+
+impl VaultEvent for TipEvent {
+    const DISCRIMINATOR: [u8; 8] = star_frame::sighash!("event", "TipEvent");
+}
+
 /* -------------------- Initialize -------------------- */
 
+/// Derived addresses returned from [`InitializeIx`] so clients don't need to recompute them.
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct VaultAddresses {
+    pub state: Pubkey,
+    pub vault: Pubkey,
+    pub state_bump: u8,
+    pub vault_bump: u8,
+}
+
+pub const CURRENCY_LEN: usize = 8;
+/// Currency tag of a native SOL vault
+pub const SOL_CURRENCY: [u8; CURRENCY_LEN] = *b"SOL\0\0\0\0\0";
+
+/// Checks `currency` is a non-empty run of printable ASCII, without spaces, padded with nulls.
+pub fn validate_currency(currency: &[u8; CURRENCY_LEN]) -> Result<()> {
+    let len = currency
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(CURRENCY_LEN);
+    require!(len > 0, VaultError::InvalidArgument, "Currency is empty");
+    require!(
+        currency[..len].iter().all(u8::is_ascii_graphic),
+        VaultError::InvalidArgument,
+        "Currency must be printable ASCII"
+    );
+    require!(
+        currency[len..].iter().all(|&byte| byte == 0),
+        VaultError::InvalidArgument,
+        "Currency padding must be nulls"
+    );
+    Ok(())
+}
+
+/// Opens a vault for the signer. When the global config has an allowlist, `proof` must show
+/// the owner is on it, see [`verify_allowlist_proof`]. Otherwise it can be left empty.
+///
+/// `currency` tags the vault for display, [`SOL_CURRENCY`] for a plain SOL vault. It can't be
+/// changed later.
 #[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
-pub struct InitializeIx;
+#[ix_args(&run)]
+pub struct InitializeIx {
+    pub proof: Vec<[u8; 32]>,
+    pub currency: [u8; CURRENCY_LEN],
+}
 
 #[derive(AccountSet)]
 pub struct InitializeAccounts {
@@ -74,34 +957,107 @@ pub struct InitializeAccounts {
     pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
 
     pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn InitializeIx(
+    a: &mut InitializeAccounts,
+    ix: &InitializeIx,
+    ctx: &mut Context,
+) -> Result<VaultAddresses> {
+    open_vault(a, ctx, &ix.proof, &ix.currency, 0)
+}
+
+/// Opens a vault from another on-chain program, see [`InitializeIx`].
+///
+/// `accounts.owner` becomes the vault owner and may be a PDA of the calling program, in which
+/// case `owner_signer_seeds` (including the bump) lets the caller sign for it. It funds the
+/// state and vault rent, so like a [`cpi_deposit`] user it must be system-owned and hold no data.
+///
+/// Depend on this crate with the `no-entrypoint` feature to call it.
+pub fn cpi_initialize(
+    accounts: InitializeCpiAccounts,
+    proof: Vec<[u8; 32]>,
+    currency: [u8; CURRENCY_LEN],
+    owner_signer_seeds: Option<&[&[u8]]>,
+) -> Result<()> {
+    let cpi = VaultProgram::cpi(InitializeIx { proof, currency }, accounts, None);
+    match owner_signer_seeds {
+        Some(seeds) => cpi.invoke_signed(&[seeds]),
+        None => cpi.invoke(),
+    }
+}
+
+/// Opens the vault like [`InitializeIx`] and deposits `amount` in the same instruction.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct InitializeAndDepositIx {
+    pub amount: u64,
+    pub proof: Vec<[u8; 32]>,
+    pub currency: [u8; CURRENCY_LEN],
 }
 
 #[star_frame_instruction]
-fn InitializeIx(a: &mut InitializeAccounts, _run: (), ctx: &mut Context) -> Result<()> {
-    // Get rent exemption amount for the vault (0 data bytes for SystemAccount)
-    let rent = ctx.get_rent()?;
-    let rent_exempt_lamports = rent.minimum_balance(0);
+fn InitializeAndDepositIx(
+    a: &mut InitializeAccounts,
+    ix: &InitializeAndDepositIx,
+    ctx: &mut Context,
+) -> Result<VaultAddresses> {
+    open_vault(a, ctx, &ix.proof, &ix.currency, ix.amount)
+}
+
+// Writes the state and funds the vault with its rent exemption plus `deposit` in one transfer
+fn open_vault(
+    a: &mut InitializeAccounts,
+    ctx: &mut Context,
+    proof: &[[u8; 32]],
+    currency: &[u8; CURRENCY_LEN],
+    deposit: u64,
+) -> Result<VaultAddresses> {
+    let global_config = a.global_config.data()?;
+    // Opening a vault only moves its rent, so only an opening deposit is held up by the pause
+    if deposit != 0 {
+        global_config.ensure_not_paused()?;
+    }
+    global_config.ensure_allowlisted(a.owner.pubkey(), proof)?;
+    validate_currency(currency)?;
+
+    let rent_exempt_lamports = vault_rent_exempt_lamports(ctx)?;
+    let lamports = rent_exempt_lamports
+        .checked_add(deposit)
+        .ok_or_else(|| VaultError::BalanceOverflow.context("Deposit amount overflows"))?;
 
     // What is the purpose of this transfer? Why does this account need to be funded?
-    System::cpi(
-        Transfer {
-            lamports: rent_exempt_lamports,
-        },
-        TransferCpiAccounts {
-            funder: *a.owner.account_info(),
-            recipient: *a.vault.account_info(),
-        },
-        None,
-    )
-    .invoke()?;
+    transfer_lamports(
+        *a.owner.account_info(),
+        *a.vault.account_info(),
+        lamports,
+        &[],
+    )?;
 
     **a.state.data_mut()? = VaultState {
         owner: *a.owner.pubkey(),
         state_bump: a.state.access_seeds().bump,
         vault_bump: a.vault.access_seeds().bump,
+        init_slot: ctx.get_clock()?.slot,
+        currency: *currency,
+        ..Default::default()
     };
 
-    Ok(())
+    // A new vault starts at `LogLevel::Detailed`, so its opening is always logged
+    emit_event(&VaultOpenedEvent {
+        owner: *a.owner.pubkey(),
+        state: *a.state.pubkey(),
+        vault: *a.vault.pubkey(),
+    });
+
+    Ok(VaultAddresses {
+        state: *a.state.pubkey(),
+        vault: *a.vault.pubkey(),
+        state_bump: a.state.access_seeds().bump,
+        vault_bump: a.vault.access_seeds().bump,
+    })
 }
 
 /* -------------------- Deposit -------------------- */
@@ -117,114 +1073,3132 @@ pub struct DepositAccounts {
     pub user: Signer<Mut<SystemAccount>>,
     #[validate(arg = SeedsWithBump {
         seeds: VaultSeeds { state: *self.vault_state.pubkey() },
-        bump: self.vault_state.data_mut()?.vault_bump,
+        bump: VaultState::read_tolerant(&self.vault_state)?.vault_bump,
     })]
     pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
-    // Validate that the user is the owner of the vault state account
-    #[validate(arg = self.user.pubkey())]
-    pub vault_state: ValidatedAccount<VaultState>,
+    // Deposits only need the owner and bumps, so states on an older layout are read with
+    // `read_tolerant` instead of failing to load as the current `VaultState`
+    pub vault_state: AccountInfo,
 
     pub system_program: Program<System>,
+    // Only `InitializeGlobalConfigIx` creates a `GlobalConfig`, always at its PDA, so the
+    // program owner and discriminant checks are enough to know this is the config
+    pub global_config: Account<GlobalConfig>,
+    // Only needed when the vault has `auto_forward` set, which is then paid the deposit
+    pub forward_to: Option<Mut<SystemAccount>>,
 }
 
-// Why does this instruction need to exist? Can't the user just do a manual system transfer to the vault PDA?
+/// Owner-only deposit. Unlike a plain system transfer to the vault PDA it enforces the
+/// vault's `max_deposit_per_tx`. Anyone else funds the vault with [`DepositToOwnerIx`],
+/// [`TipIx`] or [`DepositRawIx`].
+///
+/// With `auto_forward` set the vault passes the deposit straight on, so its balance doesn't
+/// grow.
 #[star_frame_instruction]
-fn DepositIx(a: &mut DepositAccounts, amount: u64) -> Result<()> {
-    ensure!(a.user.lamports() >= amount, "Insufficient funds");
+fn DepositIx(a: &mut DepositAccounts, amount: u64, ctx: &mut Context) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    // Deposits never write the state, so a writable one means the client expects a mutation
+    #[cfg(feature = "strict")]
+    require!(
+        !a.vault_state.is_writable(),
+        VaultError::InvalidAccount,
+        "Vault state must not be writable"
+    );
+    let state = VaultState::read_tolerant(&a.vault_state)?;
+    require!(
+        state.owner == *a.user.pubkey(),
+        VaultError::Unauthorized,
+        "Incorrect owner"
+    );
+    if state.max_deposit_per_tx != 0 && amount > state.max_deposit_per_tx {
+        return Err(VaultError::DepositCapExceeded.into());
+    }
+    let minimum_lamports = vault_rent_exempt_lamports(ctx)?;
+    state.check_deposit_limits(a.vault.lamports().saturating_sub(minimum_lamports), amount)?;
+    a.vault
+        .lamports()
+        .checked_add(amount)
+        .ok_or(VaultError::BalanceOverflow)?;
 
-    System::cpi(
-        Transfer { lamports: amount },
-        TransferCpiAccounts {
-            funder: *a.user.account_info(),
-            recipient: *a.vault.account_info(),
-        },
-        None,
+    // Transaction fees are charged before the program runs, so the user's balance is already net
+    // of fees and the whole of it can be deposited. Clients wanting headroom for later
+    // transactions can pre-flight with `client::check_deposit_funds`.
+    transfer_lamports(*a.user.account_info(), *a.vault.account_info(), amount, &[])?;
+
+    forward_deposit(
+        &a.vault,
+        a.forward_to.as_ref(),
+        state.auto_forward(),
+        amount,
     )
-    .invoke()?;
+}
 
-    Ok(())
+/// Passes a deposit that just landed in `vault` on to its `auto_forward` address, if it has one,
+/// so the vault's balance doesn't grow
+fn forward_deposit(
+    vault: &Seeded<Mut<SystemAccount>, VaultSeeds>,
+    forward_to: Option<&Mut<SystemAccount>>,
+    auto_forward: Option<Pubkey>,
+    amount: u64,
+) -> Result<()> {
+    let Some(auto_forward) = auto_forward else {
+        return Ok(());
+    };
+    let forward_to = forward_to
+        .ok_or_else(|| VaultError::InvalidAccount.context("Auto-forward account required"))?;
+    require!(
+        *forward_to.pubkey() == auto_forward,
+        VaultError::RecipientNotAllowed,
+        "Vault only forwards to its auto-forward address"
+    );
+    let signer_seeds = vault.access_seeds().seeds_with_bump();
+    transfer_lamports(
+        *vault.account_info(),
+        *forward_to.account_info(),
+        amount,
+        &[&signer_seeds],
+    )
 }
-/* -------------------- Withdraw -------------------- */
+
+/// Deposits `amount` into a vault from another on-chain program.
+///
+/// `accounts.user` must still be the vault owner. It may be a PDA of the calling program, in
+/// which case `user_signer_seeds` (including the bump) lets the caller sign for it. The PDA must
+/// be system-owned and hold no data, since the lamports are moved with a System transfer.
+///
+/// `vault_program` is this program's account, passed in place of `forward_to` when it is `None`.
+///
+/// Depend on this crate with the `no-entrypoint` feature to call it.
+pub fn cpi_deposit(
+    accounts: DepositCpiAccounts,
+    vault_program: &AccountInfo,
+    amount: u64,
+    user_signer_seeds: Option<&[&[u8]]>,
+) -> Result<()> {
+    let cpi = VaultProgram::cpi(DepositIx { amount }, accounts, vault_program);
+    match user_signer_seeds {
+        Some(seeds) => cpi.invoke_signed(&[seeds]),
+        None => cpi.invoke(),
+    }
+}
+
+/* -------------------- Deposit Raw -------------------- */
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
-pub struct WithdrawIx {
+pub struct DepositRawIx {
     #[ix_args(run)]
     pub amount: u64,
 }
 
 #[derive(AccountSet)]
-pub struct WithdrawAccounts {
-    pub user: Signer<Mut<SystemAccount>>,
-    #[validate(arg = SeedsWithBump {
-        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
-        bump: self.vault_state.data_mut()?.vault_bump,
-    })]
-    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
-    // Validate that the user is the owner of the vault state account
-    #[validate(arg = self.user.pubkey())]
-    pub vault_state: ValidatedAccount<VaultState>,
+pub struct DepositRawAccounts {
+    pub funder: Signer<Mut<SystemAccount>>,
+    // Seeds and state are not checked: deposits need no ownership, so this is a plain
+    // system transfer that skips the state load and PDA derivation
+    pub vault: Mut<SystemAccount>,
     pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
 }
 
+/// Plain transfer into a vault without loading its state. The vault's `auto_forward` is not
+/// applied, so lamports sent this way stay in the vault until the owner withdraws them.
 #[star_frame_instruction]
-fn WithdrawIx(a: &mut WithdrawAccounts, amount: u64, ctx: &mut Context) -> Result<()> {
-    let minimum_lamports = ctx.get_rent()?.minimum_balance(0);
-    let available_lamports = a.vault.lamports().saturating_sub(minimum_lamports);
-    ensure!(
-        a.vault.lamports() >= available_lamports,
-        "Insufficient funds"
-    );
-
-    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
-    System::cpi(
-        Transfer { lamports: amount },
-        TransferCpiAccounts {
-            funder: *a.vault.account_info(),
-            recipient: *a.user.account_info(),
-        },
-        None,
-    )
-    .invoke_signed(&[&signer_seeds])?;
+fn DepositRawIx(a: &mut DepositRawAccounts, amount: u64) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    transfer_lamports(
+        *a.funder.account_info(),
+        *a.vault.account_info(),
+        amount,
+        &[],
+    )?;
 
     Ok(())
 }
 
-/* -------------------- Close -------------------- */
+/* -------------------- Tip -------------------- */
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
-pub struct CloseIx;
-
-#[derive(AccountSet)]
+pub struct TipIx {
+    #[ix_args(run)]
+    pub amount: u64,
+}
+
+#[derive(AccountSet)]
+pub struct TipAccounts {
+    // Anyone can tip, the funder is not checked against the owner
+    pub funder: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    pub vault_state: Mut<Account<VaultState>>,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+    // Only needed when the vault has `auto_forward` set, like `DepositAccounts::forward_to`
+    pub forward_to: Option<Mut<SystemAccount>>,
+}
+
+/// Tips `amount` into any vault. Tips count towards `total_tips` and are forwarded like a
+/// [`DepositIx`] when the vault has `auto_forward` set.
+#[star_frame_instruction]
+fn TipIx(a: &mut TipAccounts, amount: u64, ctx: &mut Context) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let minimum_lamports = vault_rent_exempt_lamports(ctx)?;
+    a.vault_state
+        .data()?
+        .check_deposit_limits(a.vault.lamports().saturating_sub(minimum_lamports), amount)?;
+
+    transfer_lamports(
+        *a.funder.account_info(),
+        *a.vault.account_info(),
+        amount,
+        &[],
+    )?;
+
+    let mut state = a.vault_state.data_mut()?;
+    state.total_tips = state
+        .total_tips
+        .checked_add(amount)
+        .ok_or_else(|| VaultError::BalanceOverflow.context("Tip total overflow"))?;
+
+    if state.logs(LogLevel::Detailed) {
+        emit_event(&TipEvent {
+            from: *a.funder.pubkey(),
+            amount,
+        });
+    }
+    let auto_forward = state.auto_forward();
+    drop(state);
+    forward_deposit(&a.vault, a.forward_to.as_ref(), auto_forward, amount)
+}
+
+/// Tips `amount` into any vault from a PDA of the calling program, signing for the funder with
+/// `funder_signer_seeds` (including the bump). This is how a protocol routes its own funds into
+/// user vaults, since [`cpi_deposit`] requires the funder to be the vault owner.
+///
+/// The funder PDA must be system-owned and hold no data, since the lamports are moved with a
+/// System transfer. `vault_program` stands in for `forward_to` when it is `None`, as in
+/// [`cpi_deposit`]. Depend on this crate with the `no-entrypoint` feature to call it.
+pub fn cpi_deposit_from_pda(
+    accounts: TipCpiAccounts,
+    vault_program: &AccountInfo,
+    amount: u64,
+    funder_signer_seeds: &[&[u8]],
+) -> Result<()> {
+    VaultProgram::cpi(TipIx { amount }, accounts, vault_program)
+        .invoke_signed(&[funder_signer_seeds])
+}
+
+/* -------------------- Deposit To Owner -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct DepositToOwnerIx {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(AccountSet)]
+pub struct DepositToOwnerAccounts {
+    // Anyone can fund a vault, the funder is not checked against the owner
+    pub funder: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: VaultState::read_tolerant(&self.vault_state)?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Checked against the PDA of `owner` in the handler, read tolerantly like `DepositIx`
+    pub vault_state: AccountInfo,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+    // Only needed when the vault has `auto_forward` set, like `DepositAccounts::forward_to`
+    pub forward_to: Option<Mut<SystemAccount>>,
+}
+
+/// Funds `owner`'s vault from any account. The state must be the PDA derived from `owner`, so a
+/// client can't be tricked into funding a vault other than the one it named. The vault's
+/// deposit limits and `auto_forward` apply as they would to the owner's own [`DepositIx`].
+#[star_frame_instruction]
+fn DepositToOwnerIx(
+    a: &mut DepositToOwnerAccounts,
+    ix: &DepositToOwnerIx,
+    ctx: &mut Context,
+) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let state = VaultState::read_tolerant(&a.vault_state)?;
+    require!(
+        state.owner == ix.owner,
+        VaultError::Unauthorized,
+        "Incorrect owner"
+    );
+    let expected_state = Pubkey::create_program_address(
+        &SeedsWithBump {
+            seeds: VaultStateSeeds { owner: ix.owner },
+            bump: state.state_bump,
+        }
+        .seeds_with_bump(),
+        &VaultProgram::ID,
+    )
+    .map_err(|_| VaultError::InvalidAccount.context("Invalid state bump"))?;
+    require!(
+        *a.vault_state.pubkey() == expected_state,
+        VaultError::InvalidAccount,
+        "Vault state is not the PDA of the owner"
+    );
+
+    if state.max_deposit_per_tx != 0 && ix.amount > state.max_deposit_per_tx {
+        return Err(VaultError::DepositCapExceeded.into());
+    }
+    let minimum_lamports = vault_rent_exempt_lamports(ctx)?;
+    state.check_deposit_limits(
+        a.vault.lamports().saturating_sub(minimum_lamports),
+        ix.amount,
+    )?;
+
+    transfer_lamports(
+        *a.funder.account_info(),
+        *a.vault.account_info(),
+        ix.amount,
+        &[],
+    )?;
+    forward_deposit(
+        &a.vault,
+        a.forward_to.as_ref(),
+        state.auto_forward(),
+        ix.amount,
+    )
+}
+
+/* -------------------- Wrap Deposit -------------------- */
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
+/// Most token accounts a vault tracks at once, see [`VaultState::track_token_account`]
+pub const MAX_TOKEN_ACCOUNTS: usize = 4;
+
+// SPL token account layout: mint, owner, then amount, 165 bytes in total
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+// `TokenInstruction::SyncNative` tag
+const SYNC_NATIVE_TAG: u8 = 17;
+// `TokenInstruction::Transfer` tag, followed by the amount
+const TOKEN_TRANSFER_TAG: u8 = 3;
+
+/// The mint and token owner of `token_account`, an account of the token program.
+fn read_token_account(token_account: &AccountInfo) -> Result<(Pubkey, Pubkey)> {
+    require!(
+        token_account.owner_pubkey() == TOKEN_PROGRAM_ID,
+        VaultError::InvalidAccount,
+        "Token account is not owned by the token program"
+    );
+    let data = token_account.try_borrow_data()?;
+    require!(
+        data.len() == TOKEN_ACCOUNT_LEN,
+        VaultError::InvalidAccount,
+        "Invalid token account"
+    );
+    let read_pubkey = |at: usize| Pubkey::new_from_array(data[at..at + 32].try_into().unwrap());
+    Ok((read_pubkey(0), read_pubkey(TOKEN_ACCOUNT_OWNER_OFFSET)))
+}
+
+// Token balance of an account already checked with `read_token_account`
+fn read_token_amount(token_account: &AccountInfo) -> Result<u64> {
+    let data = token_account.try_borrow_data()?;
+    let amount = &data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8];
+    Ok(u64::from_le_bytes(amount.try_into().unwrap()))
+}
+
+/// Checks `token_account` is a wrapped SOL account of the token program owned by `vault`
+fn ensure_vault_wsol_account(token_account: &AccountInfo, vault: &Pubkey) -> Result<()> {
+    let (mint, token_owner) = read_token_account(token_account)?;
+    require!(
+        mint == NATIVE_MINT,
+        VaultError::InvalidAccount,
+        "Token account is not wrapped SOL"
+    );
+    require!(
+        token_owner == *vault,
+        VaultError::InvalidAccount,
+        "wSOL account is not owned by the vault"
+    );
+    Ok(())
+}
+
+/// Owner deposit of native SOL as wrapped SOL into a token account held by the vault, for
+/// integrations that only speak SPL tokens. The wSOL is not part of the vault's lamport
+/// balance, so only `max_deposit_per_tx` applies. The vault can't be closed until the wSOL is
+/// paid out with [`WithdrawTokenBatchIx`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WrapDepositIx {
+    #[ix_args(run)]
+    pub amount: u64,
+}
+
+#[derive(AccountSet)]
+pub struct WrapDepositAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    // Native mint token account whose token owner is `vault`, e.g. its associated token account
+    pub wsol_account: Mut<AccountInfo>,
+    pub token_program: AccountInfo,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn WrapDepositIx(a: &mut WrapDepositAccounts, amount: u64) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let max_deposit_per_tx = a.vault_state.data()?.max_deposit_per_tx;
+    if max_deposit_per_tx != 0 && amount > max_deposit_per_tx {
+        return Err(VaultError::DepositCapExceeded.into());
+    }
+    require!(
+        *a.token_program.pubkey() == TOKEN_PROGRAM_ID,
+        VaultError::InvalidAccount,
+        "Incorrect token program"
+    );
+    ensure_vault_wsol_account(a.wsol_account.account_info(), a.vault.pubkey())?;
+
+    transfer_lamports(
+        *a.user.account_info(),
+        *a.wsol_account.account_info(),
+        amount,
+        &[],
+    )?;
+    // Lamports sent to a native token account only count as wSOL once synced
+    slice_invoke(
+        &PinocchioInstruction {
+            program_id: a.token_program.key(),
+            data: &[SYNC_NATIVE_TAG],
+            accounts: &[PinocchioAccountMeta::writable(a.wsol_account.key())],
+        },
+        &[a.wsol_account.account_info()],
+    )?;
+    let amount = read_token_amount(a.wsol_account.account_info())?;
+    a.vault_state
+        .data_mut()?
+        .track_token_account(a.wsol_account.pubkey(), amount)?;
+
+    Ok(())
+}
+
+/* -------------------- Withdraw Token Batch -------------------- */
+
+/// Pays `amounts[i]` tokens to the `i`th recipient token account, all from one token account
+/// held by the vault, e.g. a payroll run. Every recipient must hold the same mint, and belong to
+/// the bound recipient if the vault has one. The batch counts as one withdrawal for the cooldown.
+///
+/// A source left with a balance is tracked so the vault can't be closed before it is emptied.
+/// The sandwich guard doesn't apply, see [`SetSandwichGuardIx`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WithdrawTokenBatchIx {
+    #[ix_args(&run)]
+    pub amounts: Vec<u64>,
+}
+
+#[derive(AccountSet)]
+pub struct WithdrawTokenBatchAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    // Token account whose token owner is `vault`
+    pub source: Mut<AccountInfo>,
+    pub token_program: AccountInfo,
+    pub global_config: Account<GlobalConfig>,
+    // Writable recipient token accounts, one per amount
+    pub recipients: Rest<AccountInfo>,
+}
+
+#[star_frame_instruction]
+fn WithdrawTokenBatchIx(
+    a: &mut WithdrawTokenBatchAccounts,
+    amounts: &Vec<u64>,
+    ctx: &mut Context,
+) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    require!(
+        *a.token_program.pubkey() == TOKEN_PROGRAM_ID,
+        VaultError::InvalidAccount,
+        "Incorrect token program"
+    );
+    require!(
+        !amounts.is_empty() && amounts.len() == a.recipients.len(),
+        VaultError::InvalidArgument,
+        "Pass one recipient token account per amount"
+    );
+    let (mint, token_owner) = read_token_account(a.source.account_info())?;
+    require!(
+        token_owner == *a.vault.pubkey(),
+        VaultError::InvalidAccount,
+        "Source token account is not owned by the vault"
+    );
+    {
+        let mut state = a.vault_state.data_mut()?;
+        for recipient in a.recipients.iter() {
+            let (recipient_mint, recipient_owner) = read_token_account(recipient)?;
+            require!(
+                recipient_mint == mint,
+                VaultError::InvalidAccount,
+                "Recipient token account mint does not match the source"
+            );
+            state.ensure_recipient_allowed(&recipient_owner)?;
+        }
+        state.record_withdraw(ctx.get_clock()?.slot)?;
+    }
+
+    let vault_seeds = a.vault.access_seeds().seeds_with_bump();
+    let seeds = vault_seeds
+        .iter()
+        .map(|seed| Seed::from(*seed))
+        .collect::<Vec<_>>();
+    for (recipient, amount) in a.recipients.iter().zip(amounts) {
+        let mut data = [0; 9];
+        data[0] = TOKEN_TRANSFER_TAG;
+        data[1..].copy_from_slice(&amount.to_le_bytes());
+        slice_invoke_signed(
+            &PinocchioInstruction {
+                program_id: a.token_program.key(),
+                data: &data,
+                accounts: &[
+                    PinocchioAccountMeta::writable(a.source.key()),
+                    PinocchioAccountMeta::writable(recipient.key()),
+                    PinocchioAccountMeta::readonly_signer(a.vault.key()),
+                ],
+            },
+            &[a.source.account_info(), recipient, a.vault.account_info()],
+            &[PinocchioSigner::from(&seeds[..])],
+        )?;
+    }
+    let amount = read_token_amount(a.source.account_info())?;
+    a.vault_state
+        .data_mut()?
+        .track_token_account(a.source.pubkey(), amount)?;
+
+    Ok(())
+}
+
+/* -------------------- Withdraw -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WithdrawIx {
+    #[ix_args(run)]
+    pub amount: u64,
+}
+
+#[derive(AccountSet)]
+pub struct WithdrawAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data_mut()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+    // Only needed when the vault has `sandwich_guard` set, existing callers can leave it off
+    pub instructions_sysvar: Option<AccountInfo>,
+    // Only needed when the vault has a bound recipient, which is then paid instead of the user
+    pub recipient: Option<Mut<SystemAccount>>,
+}
+
+#[star_frame_instruction]
+fn WithdrawIx(a: &mut WithdrawAccounts, amount: u64, ctx: &mut Context) -> Result<()> {
+    withdraw(a, amount, ctx)
+}
+
+/// Basis points in 100% for [`WithdrawPercentIx`]
+pub const MAX_WITHDRAW_BPS: u16 = 10_000;
+
+/// Withdraws `bps` basis points of the withdrawable balance, rounded down to the lamport.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WithdrawPercentIx {
+    #[ix_args(run)]
+    pub bps: u16,
+}
+
+#[star_frame_instruction]
+fn WithdrawPercentIx(a: &mut WithdrawAccounts, bps: u16, ctx: &mut Context) -> Result<()> {
+    require!(
+        bps <= MAX_WITHDRAW_BPS,
+        VaultError::InvalidArgument,
+        "Basis points must be at most 10000"
+    );
+    let available_lamports = withdrawable_lamports(a, ctx)?;
+    // Can't overflow: the product fits in u128 and the result is at most `available_lamports`
+    let amount = (available_lamports as u128 * bps as u128 / MAX_WITHDRAW_BPS as u128) as u64;
+    withdraw(a, amount, ctx)
+}
+
+/// Withdraws `amount` to `destination`, which unlike the owner or a bound recipient may be any
+/// writable account, e.g. another program's PDA. The owner signs, and a bound vault still only
+/// pays its bound recipient.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WithdrawToProgramIx {
+    #[ix_args(run)]
+    pub amount: u64,
+}
+
+#[derive(AccountSet)]
+pub struct WithdrawToProgramAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data_mut()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    // Any account the system program can credit, whatever program owns it
+    pub destination: Mut<AccountInfo>,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+    // Only needed when the vault has `sandwich_guard` set
+    pub instructions_sysvar: Option<AccountInfo>,
+}
+
+#[star_frame_instruction]
+fn WithdrawToProgramIx(
+    a: &mut WithdrawToProgramAccounts,
+    amount: u64,
+    ctx: &mut Context,
+) -> Result<()> {
+    let destination = a.destination.account_info();
+    require!(
+        destination.pubkey() != a.vault.pubkey() && destination.pubkey() != a.vault_state.pubkey(),
+        VaultError::InvalidAccount,
+        "Destination cannot be the vault or its state"
+    );
+    require!(
+        !destination.executable(),
+        VaultError::InvalidAccount,
+        "Destination cannot be executable"
+    );
+    a.global_config.data()?.ensure_not_paused()?;
+    if a.vault_state.data()?.sandwich_guard.get() {
+        let instructions_sysvar = a.instructions_sysvar.as_ref().ok_or_else(|| {
+            VaultError::InvalidAccount.context("Instructions sysvar required by the sandwich guard")
+        })?;
+        ensure_no_prior_vault_write(instructions_sysvar, a.vault.pubkey())?;
+    }
+    let retained_lamports = a
+        .vault_state
+        .data()?
+        .retained_lamports(vault_rent_exempt_lamports(ctx)?);
+    if amount > a.vault.lamports().saturating_sub(retained_lamports) {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    let amount = a.vault_state.data()?.round_withdrawal(amount)?;
+
+    a.vault_state
+        .data()?
+        .ensure_recipient_allowed(destination.pubkey())?;
+    a.vault_state
+        .data_mut()?
+        .record_withdraw(ctx.get_clock()?.slot)?;
+
+    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+    transfer_lamports(
+        *a.vault.account_info(),
+        *destination,
+        amount,
+        &[&signer_seeds],
+    )?;
+    Ok(())
+}
+
+// The vault must stay rent exempt with its buffer, only the excess can be withdrawn
+fn withdrawable_lamports(a: &WithdrawAccounts, ctx: &mut Context) -> Result<u64> {
+    let retained_lamports = a
+        .vault_state
+        .data()?
+        .retained_lamports(vault_rent_exempt_lamports(ctx)?);
+    Ok(a.vault.lamports().saturating_sub(retained_lamports))
+}
+
+/// Fails if an instruction before the current one in the transaction lists `vault` as writable.
+///
+/// Any of them could have paid into the vault, whether a deposit of this program, a System
+/// transfer or another program depositing through CPI, so this covers a deposit and withdrawal
+/// sandwiching someone else's transaction. Earlier withdrawals from the vault count too.
+fn ensure_no_prior_vault_write(instructions_sysvar: &AccountInfo, vault: &Pubkey) -> Result<()> {
+    let instructions = Instructions::try_from(instructions_sysvar)?;
+    for index in 0..instructions.load_current_index() {
+        let instruction = instructions.load_instruction_at(index.into())?;
+        let writes_vault = (0..)
+            .map_while(|at| instruction.get_account_meta_at(at).ok())
+            .any(|meta| meta.key == vault.to_bytes() && meta.is_writable());
+        require!(
+            !writes_vault,
+            VaultError::WithdrawalAfterDeposit,
+            "Withdrawal follows a deposit into the vault in the same transaction"
+        );
+    }
+    Ok(())
+}
+
+fn withdraw(a: &mut WithdrawAccounts, amount: u64, ctx: &mut Context) -> Result<()> {
+    // A vault paying itself is a no-op signed transfer, reject it outright
+    require!(
+        a.user.pubkey() != a.vault.pubkey(),
+        VaultError::InvalidAccount,
+        "User cannot be the vault"
+    );
+    a.global_config.data()?.ensure_not_paused()?;
+    if a.vault_state.data()?.sandwich_guard.get() {
+        let instructions_sysvar = a.instructions_sysvar.as_ref().ok_or_else(|| {
+            VaultError::InvalidAccount.context("Instructions sysvar required by the sandwich guard")
+        })?;
+        ensure_no_prior_vault_write(instructions_sysvar, a.vault.pubkey())?;
+    }
+    let available_lamports = withdrawable_lamports(a, ctx)?;
+    if amount > available_lamports {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    let amount = a.vault_state.data()?.round_withdrawal(amount)?;
+
+    let recipient = payout_account(
+        &**a.vault_state.data()?,
+        *a.user.account_info(),
+        a.recipient
+            .as_ref()
+            .map(|recipient| *recipient.account_info()),
+    )?;
+    a.vault_state
+        .data_mut()?
+        .record_withdraw(ctx.get_clock()?.slot)?;
+
+    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+    transfer_lamports(*a.vault.account_info(), recipient, amount, &[&signer_seeds])?;
+
+    Ok(())
+}
+
+/// Where a payout to the owner goes: `owner` itself, or `recipient` once the vault is bound,
+/// which must then be passed and be the bound address.
+fn payout_account(
+    state: &VaultState,
+    owner: AccountInfo,
+    recipient: Option<AccountInfo>,
+) -> Result<AccountInfo> {
+    let Some(bound_recipient) = state.bound_recipient() else {
+        return Ok(owner);
+    };
+    let recipient = recipient
+        .ok_or_else(|| VaultError::InvalidAccount.context("Bound recipient account required"))?;
+    require!(
+        *recipient.pubkey() == bound_recipient,
+        VaultError::RecipientNotAllowed,
+        "Vault only pays its bound recipient"
+    );
+    Ok(recipient)
+}
+
+/* -------------------- Withdraw With Approval -------------------- */
+
+pub const ED25519_PROGRAM_ID: Pubkey = pubkey!("Ed25519SigVerify111111111111111111111111111");
+
+/// Off-chain withdrawal approval. The owner signs its raw bytes with the ed25519 precompile.
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct WithdrawApproval {
+    // Binds the approval to a single vault
+    pub state: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub nonce: u64,
+    // Unix timestamp after which the approval is rejected
+    pub expiry: i64,
+    // Last slot the approval can land in. Unlike `expiry` it is held to the vault's
+    // `max_slot_age`, so a relayer can't sit on an approval. `u64::MAX` when only `expiry` applies.
+    pub valid_until_slot: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct WithdrawWithApprovalIx {
+    pub amount: u64,
+    pub nonce: u64,
+    pub expiry: i64,
+    pub valid_until_slot: u64,
+}
+
+// The relayer only pays the transaction fee, so it doesn't appear in the accounts
+#[derive(AccountSet)]
+pub struct WithdrawWithApprovalAccounts {
+    pub recipient: Mut<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    pub vault_state: Mut<Account<VaultState>>,
+    pub instructions_sysvar: AccountInfo,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+/// Checks that the instruction right before the current one is an ed25519 precompile
+/// verification of `message` signed by `signer`. The precompile itself rejects bad signatures.
+fn verify_ed25519_signature(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let instructions = Instructions::try_from(instructions_sysvar)?;
+    let verify_ix = instructions.get_instruction_relative(-1)?;
+    require!(
+        *verify_ix.get_program_id() == ED25519_PROGRAM_ID.to_bytes(),
+        VaultError::InvalidApproval,
+        "Missing ed25519 signature verification"
+    );
+
+    // Signature count and padding, followed by a single set of offsets
+    let data = verify_ix.get_instruction_data();
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        VaultError::InvalidApproval,
+        "Expected a single ed25519 signature"
+    );
+    let read_offset = |at: usize| usize::from(u16::from_le_bytes([data[at], data[at + 1]]));
+    // Offsets into other instructions could point at data the owner never signed
+    require!(
+        [4, 8, 14]
+            .into_iter()
+            .all(|at| read_offset(at) == usize::from(u16::MAX)),
+        VaultError::InvalidApproval,
+        "ed25519 offsets must reference their own instruction"
+    );
+
+    let pubkey_offset = read_offset(6);
+    let message_offset = read_offset(10);
+    let message_size = read_offset(12);
+    require!(
+        data.get(pubkey_offset..pubkey_offset + 32) == Some(signer.as_ref()),
+        VaultError::InvalidApproval,
+        "Approval not signed by the owner"
+    );
+    require!(
+        data.get(message_offset..message_offset + message_size) == Some(message),
+        VaultError::InvalidApproval,
+        "Approval does not match the withdrawal"
+    );
+    Ok(())
+}
+
+#[star_frame_instruction]
+fn WithdrawWithApprovalIx(
+    a: &mut WithdrawWithApprovalAccounts,
+    ix: &WithdrawWithApprovalIx,
+    ctx: &mut Context,
+) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    require!(
+        a.recipient.pubkey() != a.vault.pubkey(),
+        VaultError::InvalidAccount,
+        "Recipient cannot be the vault"
+    );
+    a.vault_state
+        .data()?
+        .ensure_recipient_allowed(a.recipient.pubkey())?;
+    require!(
+        ctx.get_clock()?.unix_timestamp <= ix.expiry,
+        VaultError::InvalidApproval,
+        "Approval expired"
+    );
+
+    let owner = {
+        let state = a.vault_state.data()?;
+        state.ensure_fresh(ctx.get_clock()?.slot, ix.valid_until_slot)?;
+        require!(
+            ix.nonce == state.withdraw_nonce,
+            VaultError::InvalidApproval,
+            "Invalid approval nonce"
+        );
+        state.owner
+    };
+    let approval = WithdrawApproval {
+        state: *a.vault_state.pubkey(),
+        recipient: *a.recipient.pubkey(),
+        amount: ix.amount,
+        nonce: ix.nonce,
+        expiry: ix.expiry,
+        valid_until_slot: ix.valid_until_slot,
+    };
+    verify_ed25519_signature(
+        &a.instructions_sysvar,
+        &owner,
+        star_frame::bytemuck::bytes_of(&approval),
+    )?;
+    if a.vault_state.data()?.sandwich_guard.get() {
+        ensure_no_prior_vault_write(&a.instructions_sysvar, a.vault.pubkey())?;
+    }
+
+    let retained_lamports = a
+        .vault_state
+        .data()?
+        .retained_lamports(vault_rent_exempt_lamports(ctx)?);
+    let available_lamports = a.vault.lamports().saturating_sub(retained_lamports);
+    if ix.amount > available_lamports {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    // The owner signed `ix.amount`, the rounding only ever pays out less
+    let amount = a.vault_state.data()?.round_withdrawal(ix.amount)?;
+
+    // Consume the nonce so the same approval can't be replayed
+    {
+        let mut state = a.vault_state.data_mut()?;
+        state.withdraw_nonce += 1;
+        state.record_withdraw(ctx.get_clock()?.slot)?;
+    }
+
+    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+    transfer_lamports(
+        *a.vault.account_info(),
+        *a.recipient.account_info(),
+        amount,
+        &[&signer_seeds],
+    )?;
+
+    Ok(())
+}
+
+/* -------------------- Escrowed Withdraw -------------------- */
+
+/// Starts a held withdrawal of `amount` to `recipient`, which [`ExecuteWithdrawIx`] releases
+/// from `release_slot` on unless the owner cancels it first with [`CancelWithdrawIx`]. The
+/// amount stays reserved in the vault meanwhile. Only one withdrawal can be pending at a time.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct RequestWithdrawIx {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub release_slot: u64,
+}
+
+#[derive(AccountSet)]
+pub struct RequestWithdrawAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn RequestWithdrawIx(
+    a: &mut RequestWithdrawAccounts,
+    ix: &RequestWithdrawIx,
+    ctx: &mut Context,
+) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    require!(
+        ix.amount > 0,
+        VaultError::InvalidArgument,
+        "Withdraw amount must be positive"
+    );
+    require!(
+        ix.recipient != *a.vault.pubkey(),
+        VaultError::InvalidAccount,
+        "Recipient cannot be the vault"
+    );
+    a.vault_state
+        .data()?
+        .ensure_recipient_allowed(&ix.recipient)?;
+    let slot = ctx.get_clock()?.slot;
+    require!(
+        ix.release_slot > slot,
+        VaultError::InvalidArgument,
+        "Release slot must be in the future"
+    );
+
+    let rent_exempt_lamports = vault_rent_exempt_lamports(ctx)?;
+    let mut state = a.vault_state.data_mut()?;
+    require!(
+        state.pending_amount == 0,
+        VaultError::WithdrawalPending,
+        "A withdrawal is already pending"
+    );
+    let available_lamports = a
+        .vault
+        .lamports()
+        .saturating_sub(state.retained_lamports(rent_exempt_lamports));
+    if ix.amount > available_lamports {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    // Rounded now so the reserved amount is what `ExecuteWithdrawIx` pays out
+    let amount = state.round_withdrawal(ix.amount)?;
+    // The cooldown and first withdraw delay apply when the withdrawal is requested
+    state.record_withdraw(slot)?;
+    state.pending_recipient = ix.recipient;
+    state.pending_amount = amount;
+    state.pending_release_slot = ix.release_slot;
+    Ok(())
+}
+
+/// Pays out the pending withdrawal once its release slot is reached. Anyone can call it, the
+/// lamports only ever go to the recipient recorded by [`RequestWithdrawIx`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct ExecuteWithdrawIx;
+
+#[derive(AccountSet)]
+pub struct ExecuteWithdrawAccounts {
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    pub vault_state: Mut<Account<VaultState>>,
+    pub recipient: Mut<SystemAccount>,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn ExecuteWithdrawIx(a: &mut ExecuteWithdrawAccounts, _run: (), ctx: &mut Context) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let amount = {
+        let mut state = a.vault_state.data_mut()?;
+        require!(
+            state.pending_amount != 0,
+            VaultError::NoWithdrawalPending,
+            "No withdrawal is pending"
+        );
+        require!(
+            state.pending_recipient == *a.recipient.pubkey(),
+            VaultError::RecipientNotAllowed,
+            "Incorrect recipient"
+        );
+        require!(
+            ctx.get_clock()?.slot >= state.pending_release_slot,
+            VaultError::WithdrawalLocked,
+            "Withdrawal is still held"
+        );
+        let amount = state.pending_amount;
+        state.pending_recipient = Pubkey::default();
+        state.pending_amount = 0;
+        state.pending_release_slot = 0;
+        amount
+    };
+
+    // Checked again without the hold, in case the rent or buffer went up since the request
+    let retained_lamports = a
+        .vault_state
+        .data()?
+        .retained_lamports(vault_rent_exempt_lamports(ctx)?);
+    if amount > a.vault.lamports().saturating_sub(retained_lamports) {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+
+    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+    transfer_lamports(
+        *a.vault.account_info(),
+        *a.recipient.account_info(),
+        amount,
+        &[&signer_seeds],
+    )?;
+    Ok(())
+}
+
+/// Aborts the pending withdrawal, releasing the held lamports back to the withdrawable balance.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct CancelWithdrawIx;
+
+#[derive(AccountSet)]
+pub struct CancelWithdrawAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn CancelWithdrawIx(a: &mut CancelWithdrawAccounts, _run: ()) -> Result<()> {
+    let mut state = a.vault_state.data_mut()?;
+    require!(
+        state.pending_amount != 0,
+        VaultError::NoWithdrawalPending,
+        "No withdrawal is pending"
+    );
+    state.pending_recipient = Pubkey::default();
+    state.pending_amount = 0;
+    state.pending_release_slot = 0;
+    Ok(())
+}
+
+/* -------------------- Keeper Sweep -------------------- */
+
+/// Sends everything withdrawable above the vault's `sweep_threshold` to the owner, paying
+/// `keeper_tip` of it to whoever calls. Does nothing while the vault is at or below the threshold.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct KeeperSweepIx;
+
+#[derive(AccountSet)]
+pub struct KeeperSweepAccounts {
+    // Anyone can sweep, the funds only ever go to the owner
+    pub keeper: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    #[validate(arg = self.owner.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    pub owner: Mut<SystemAccount>,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn KeeperSweepIx(a: &mut KeeperSweepAccounts, _run: (), ctx: &mut Context) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let (sweep_threshold, keeper_tip, retained_lamports) = {
+        let state = a.vault_state.data()?;
+        require!(
+            state.sweep_threshold != 0,
+            VaultError::InvalidState,
+            "Keeper sweeps are disabled"
+        );
+        // Sweeps pay the owner and the keeper, neither of which is the bound recipient
+        require!(
+            state.bound_recipient().is_none(),
+            VaultError::InvalidState,
+            "Keeper sweeps are disabled on a vault bound to a recipient"
+        );
+        (
+            state.sweep_threshold,
+            state.keeper_tip,
+            state.retained_lamports(vault_rent_exempt_lamports(ctx)?),
+        )
+    };
+    let available_lamports = a.vault.lamports().saturating_sub(retained_lamports);
+    if available_lamports <= sweep_threshold {
+        return Ok(());
+    }
+
+    a.vault_state
+        .data_mut()?
+        .record_withdraw(ctx.get_clock()?.slot)?;
+
+    let swept = available_lamports - sweep_threshold;
+    let tip = keeper_tip.min(swept);
+    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+    transfer_lamports(
+        *a.vault.account_info(),
+        *a.owner.account_info(),
+        swept - tip,
+        &[&signer_seeds],
+    )?;
+    if tip > 0 {
+        transfer_lamports(
+            *a.vault.account_info(),
+            *a.keeper.account_info(),
+            tip,
+            &[&signer_seeds],
+        )?;
+    }
+    Ok(())
+}
+
+/* -------------------- Close -------------------- */
+
+/// The incinerator, lamports sent here are burned at the end of the slot.
+pub const BURN_ADDRESS: Pubkey = pubkey!("1nc1nerator11111111111111111111111111111111");
+
+/// Vault balances up to this are dust when closing. It sits just above the vault's own
+/// rent-exempt minimum, so a vault that only holds its rent counts as dust.
+pub const DUST_THRESHOLD_LAMPORTS: u64 = 1_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct CloseIx;
+
+#[derive(AccountSet)]
 pub struct CloseAccounts {
+    // The owner or the vault's close authority
+    pub user: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data_mut()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Validate that the user is the owner or close authority of the vault state account. The
+    // state account is closed to the owner by the handler.
+    #[validate(arg = VaultCloser(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    pub system_program: Program<System>,
+    // Only needed when the vault has `burn_dust` set, existing callers can leave it off
+    pub burn_address: Option<Mut<AccountInfo>>,
+    // Only needed when the vault has a bound recipient, which then receives the vault balance
+    pub recipient: Option<Mut<SystemAccount>>,
+    // Only needed when the close authority closes the vault, the owner still gets the refund
+    pub owner: Option<Mut<SystemAccount>>,
+    // Only needed when the vault has metadata, which is closed to the owner as well
+    pub metadata: Option<Mut<Account<VaultMetadata>>>,
+}
+
+/// Returned from [`CloseIx`] and [`SafeCloseIx`] so a CPI caller knows how much came back.
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct CloseResult {
+    // Vault balance plus the state and metadata account rent returned to the owner
+    pub refunded: u64,
+}
+
+#[star_frame_instruction]
+fn CloseIx(a: &mut CloseAccounts, _run: (), ctx: &mut Context) -> Result<CloseResult> {
+    close_vault(a, ctx)
+}
+
+/// Closes the vault like [`CloseIx`], but only once everything withdrawable has been withdrawn
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SafeCloseIx;
+
+#[star_frame_instruction]
+fn SafeCloseIx(a: &mut CloseAccounts, _run: (), ctx: &mut Context) -> Result<CloseResult> {
+    let retained_lamports = a
+        .vault_state
+        .data()?
+        .retained_lamports(vault_rent_exempt_lamports(ctx)?);
+    if a.vault.lamports() > retained_lamports {
+        return Err(VaultError::VaultNotEmpty.into());
+    }
+    close_vault(a, ctx)
+}
+
+// Sweeps the vault to the owner, the bound recipient, or the burn address for dust, then closes
+// the state to the owner
+fn close_vault(a: &mut CloseAccounts, ctx: &mut Context) -> Result<CloseResult> {
+    require!(
+        a.user.pubkey() != a.vault.pubkey(),
+        VaultError::InvalidAccount,
+        "User cannot be the vault"
+    );
+    let owner_account = close_owner_account(a)?;
+    let owner = owner_account.account_to_modify();
+    require!(
+        owner.pubkey() != a.vault.pubkey(),
+        VaultError::InvalidAccount,
+        "Owner cannot be the vault"
+    );
+    // Closing pays out the whole vault, so it counts as a withdrawal
+    a.vault_state
+        .data()?
+        .ensure_first_withdraw_elapsed(ctx.get_clock()?.slot)?;
+    require!(
+        a.vault_state.data()?.pending_amount == 0,
+        VaultError::WithdrawalPending,
+        "Cancel the pending withdrawal before closing"
+    );
+    a.vault_state.data()?.ensure_no_holdings()?;
+    let lamports = a.vault.lamports();
+    let owner_lamports_before = owner.lamports();
+    let state = **a.vault_state.data()?;
+    // An empty vault has nothing to burn, so it closes without the burn address
+    let burn = state.burn_dust.get() && lamports > 0 && lamports <= DUST_THRESHOLD_LAMPORTS;
+    // A bound vault pays its recipient even when it would otherwise burn the dust
+    let recipient = if state.bound_recipient().is_some() {
+        payout_account(
+            &state,
+            owner,
+            a.recipient
+                .as_ref()
+                .map(|recipient| *recipient.account_info()),
+        )?
+    } else if burn {
+        let burn_address = a.burn_address.as_ref().ok_or_else(|| {
+            VaultError::InvalidAccount.context("Burn address required to close a dust vault")
+        })?;
+        require!(
+            *burn_address.pubkey() == BURN_ADDRESS,
+            VaultError::InvalidAccount,
+            "Incorrect burn address"
+        );
+        *burn_address.account_info()
+    } else {
+        owner
+    };
+
+    if lamports > 0 {
+        let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+        transfer_lamports(
+            *a.vault.account_info(),
+            recipient,
+            lamports,
+            &[&signer_seeds],
+        )?;
+    }
+
+    let vault_refund = if recipient.pubkey() == owner.pubkey() {
+        lamports
+    } else {
+        0
+    };
+    let metadata_lamports = if state.has_metadata.get() {
+        let metadata = a.metadata.as_ref().ok_or_else(|| {
+            VaultError::InvalidAccount.context("Metadata account required to close the vault")
+        })?;
+        require!(
+            metadata.data()?.state == *a.vault_state.pubkey(),
+            VaultError::InvalidAccount,
+            "Metadata belongs to another vault"
+        );
+        let metadata_lamports = metadata.account_info().lamports();
+        metadata.close_account_full(owner_account)?;
+        metadata_lamports
+    } else {
+        0
+    };
+    let state_lamports = a.vault_state.account_info().lamports();
+    a.vault_state
+        .account_info()
+        .close_account_full(owner_account)?;
+    let refunded = vault_refund
+        .checked_add(state_lamports)
+        .and_then(|refunded| refunded.checked_add(metadata_lamports))
+        .ok_or(VaultError::CloseAccountingMismatch)?;
+    let expected_owner_lamports = owner_lamports_before
+        .checked_add(refunded)
+        .ok_or(VaultError::CloseAccountingMismatch)?;
+    if owner.lamports() != expected_owner_lamports || a.vault.lamports() != 0 {
+        return Err(VaultError::CloseAccountingMismatch.into());
+    }
+    if state.logs(LogLevel::Summary) {
+        emit_event(&VaultClosedEvent {
+            owner: *owner.pubkey(),
+            refunded,
+        });
+    }
+    Ok(CloseResult { refunded })
+}
+
+// The account the close refunds, the signer itself unless the close authority signed for the
+// owner
+fn close_owner_account(a: &CloseAccounts) -> Result<&dyn CanAddLamports> {
+    let owner = a.vault_state.data()?.owner;
+    if *a.user.pubkey() == owner {
+        return Ok(&a.user);
+    }
+    let owner_account = a.owner.as_ref().ok_or_else(|| {
+        VaultError::InvalidAccount.context("Owner account required when the close authority closes")
+    })?;
+    require!(
+        *owner_account.pubkey() == owner,
+        VaultError::InvalidAccount,
+        "Incorrect owner account"
+    );
+    Ok(owner_account)
+}
+
+/* -------------------- Close Many -------------------- */
+
+/// Closes several of the signer's vaults at once, refunding each vault balance and state rent
+/// to the owner. An owner only holds more than one vault after taking others over with
+/// [`GuardianRecoverIx`]. Each vault is checked like in [`CloseIx`], and ones that would pay
+/// out elsewhere, to a bound recipient or by burning dust, or that have metadata must be closed
+/// with [`CloseIx`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct CloseManyIx;
+
+#[derive(AccountSet)]
+pub struct CloseManyAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    pub system_program: Program<System>,
+    // Writable `vault, state` pairs, checked against each other in the handler
+    pub vaults: Rest<AccountInfo>,
+}
+
+#[star_frame_instruction]
+fn CloseManyIx(a: &mut CloseManyAccounts, _run: (), ctx: &mut Context) -> Result<CloseResult> {
+    require!(
+        !a.vaults.is_empty() && a.vaults.len().is_multiple_of(2),
+        VaultError::InvalidAccount,
+        "Vaults must be passed as vault and state pairs"
+    );
+    let slot = ctx.get_clock()?.slot;
+    let mut refunded = 0u64;
+    for pair in a.vaults.chunks_exact(2) {
+        let (vault, state_info) = (&pair[0], &pair[1]);
+        // Also rejects a state passed twice, which is no longer program owned once closed
+        let state = VaultState::read_tolerant(state_info)?;
+        require!(
+            state.owner == *a.user.pubkey(),
+            VaultError::Unauthorized,
+            "Incorrect owner"
+        );
+        let vault_seeds = SeedsWithBump {
+            seeds: VaultSeeds {
+                state: *state_info.pubkey(),
+            },
+            bump: state.vault_bump,
+        };
+        let signer_seeds = vault_seeds.seeds_with_bump();
+        let expected_vault = Pubkey::create_program_address(&signer_seeds, &VaultProgram::ID)
+            .map_err(|_| VaultError::InvalidAccount.context("Invalid vault bump"))?;
+        require!(
+            *vault.pubkey() == expected_vault,
+            VaultError::InvalidAccount,
+            "Incorrect vault"
+        );
+
+        state.ensure_first_withdraw_elapsed(slot)?;
+        require!(
+            state.pending_amount == 0,
+            VaultError::WithdrawalPending,
+            "Cancel the pending withdrawal before closing"
+        );
+        state.ensure_no_holdings()?;
+        let lamports = vault.lamports();
+        // An empty vault has nothing to burn, so it closes without the burn address
+        let burn = state.burn_dust.get() && lamports > 0 && lamports <= DUST_THRESHOLD_LAMPORTS;
+        require!(
+            state.bound_recipient().is_none() && !burn,
+            VaultError::InvalidState,
+            "Vault pays out elsewhere on close, close it with CloseIx"
+        );
+        require!(
+            !state.has_metadata.get(),
+            VaultError::InvalidState,
+            "Vault has metadata to close, close it with CloseIx"
+        );
+
+        if lamports > 0 {
+            transfer_lamports(*vault, *a.user.account_info(), lamports, &[&signer_seeds])?;
+        }
+        let state_lamports = state_info.lamports();
+        state_info.close_account_full(&a.user)?;
+
+        let vault_refunded = lamports
+            .checked_add(state_lamports)
+            .ok_or(VaultError::CloseAccountingMismatch)?;
+        refunded = refunded
+            .checked_add(vault_refunded)
+            .ok_or(VaultError::CloseAccountingMismatch)?;
+        if state.logs(LogLevel::Summary) {
+            emit_event(&VaultClosedEvent {
+                owner: *a.user.pubkey(),
+                refunded: vault_refunded,
+            });
+        }
+    }
+    Ok(CloseResult { refunded })
+}
+
+/* -------------------- Set Yield Program -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetYieldProgramIx {
+    #[ix_args(run)]
+    pub yield_program: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct SetYieldProgramAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetYieldProgramIx(a: &mut SetYieldProgramAccounts, yield_program: Pubkey) -> Result<()> {
+    a.vault_state.data_mut()?.yield_program = yield_program;
+    Ok(())
+}
+
+/* -------------------- Set Withdraw Cooldown -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetWithdrawCooldownIx {
+    #[ix_args(run)]
+    pub cooldown_slots: u64,
+}
+
+// Owner only, a long enough cooldown would lock the owner's funds
+#[derive(AccountSet)]
+pub struct SetWithdrawCooldownAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetWithdrawCooldownIx(a: &mut SetWithdrawCooldownAccounts, cooldown_slots: u64) -> Result<()> {
+    a.vault_state.data_mut()?.cooldown_slots = cooldown_slots;
+    Ok(())
+}
+
+/* -------------------- Set Burn Dust -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetBurnDustIx {
+    #[ix_args(run)]
+    pub burn_dust: bool,
+}
+
+#[derive(AccountSet)]
+pub struct SetBurnDustAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetBurnDustIx(a: &mut SetBurnDustAccounts, burn_dust: bool) -> Result<()> {
+    a.vault_state.data_mut()?.burn_dust.set(burn_dust);
+    Ok(())
+}
+
+/* -------------------- Set Round To -------------------- */
+
+/// Rounds [`WithdrawIx`], [`WithdrawPercentIx`], [`WithdrawToProgramIx`] and
+/// [`WithdrawWithApprovalIx`] payouts down to a multiple of `round_to` lamports, e.g.
+/// `LAMPORTS_PER_SOL` for whole-SOL payouts. [`RequestWithdrawIx`] rounds the amount it holds.
+/// 0 turns rounding off.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetRoundToIx {
+    #[ix_args(run)]
+    pub round_to: u64,
+}
+
+#[derive(AccountSet)]
+pub struct SetRoundToAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetRoundToIx(a: &mut SetRoundToAccounts, round_to: u64) -> Result<()> {
+    a.vault_state.data_mut()?.round_to = round_to;
+    Ok(())
+}
+
+/* -------------------- Set Max Deposit Per Tx -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetMaxDepositPerTxIx {
+    #[ix_args(run)]
+    pub max_deposit_per_tx: u64,
+}
+
+#[derive(AccountSet)]
+pub struct SetMaxDepositPerTxAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetMaxDepositPerTxIx(a: &mut SetMaxDepositPerTxAccounts, max_deposit_per_tx: u64) -> Result<()> {
+    a.vault_state.data_mut()?.max_deposit_per_tx = max_deposit_per_tx;
+    Ok(())
+}
+
+/* -------------------- Set Deposit Limits -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct SetDepositLimitsIx {
+    pub min_deposit: u64,
+    pub balance_cap: u64,
+}
+
+#[derive(AccountSet)]
+pub struct SetDepositLimitsAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetDepositLimitsIx(a: &mut SetDepositLimitsAccounts, ix: &SetDepositLimitsIx) -> Result<()> {
+    let mut state = a.vault_state.data_mut()?;
+    state.min_deposit = ix.min_deposit;
+    state.balance_cap = ix.balance_cap;
+    Ok(())
+}
+
+/* -------------------- Set Rent Buffer -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetRentBufferIx {
+    #[ix_args(run)]
+    pub rent_buffer_bps: u16,
+}
+
+#[derive(AccountSet)]
+pub struct SetRentBufferAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetRentBufferIx(a: &mut SetRentBufferAccounts, rent_buffer_bps: u16) -> Result<()> {
+    a.vault_state.data_mut()?.rent_buffer_bps = rent_buffer_bps;
+    Ok(())
+}
+
+/* -------------------- Set First Withdraw Delay -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetFirstWithdrawDelayIx {
+    #[ix_args(run)]
+    pub first_withdraw_delay: u64,
+}
+
+// Owner only, a long enough delay would lock the owner's funds
+#[derive(AccountSet)]
+pub struct SetFirstWithdrawDelayAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+/// Sets the slots after opening before the vault can first be withdrawn from or closed.
+/// It's a commitment, so the delay can only ever be raised.
+#[star_frame_instruction]
+fn SetFirstWithdrawDelayIx(
+    a: &mut SetFirstWithdrawDelayAccounts,
+    first_withdraw_delay: u64,
+) -> Result<()> {
+    a.vault_state
+        .data_mut()?
+        .set_first_withdraw_delay(first_withdraw_delay)
+}
+
+/* -------------------- Set Label -------------------- */
+
+/// Longest label in bytes, it is stored inline in [`VaultState`].
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Names the vault for wallets to display. An empty label clears it.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetLabelIx {
+    #[ix_args(&run)]
+    pub label: Vec<u8>,
+}
+
+#[derive(AccountSet)]
+pub struct SetLabelAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetLabelIx(a: &mut SetLabelAccounts, label: &Vec<u8>) -> Result<()> {
+    a.vault_state.data_mut()?.set_label(label)
+}
+
+/* -------------------- Metadata -------------------- */
+
+/// Longest metadata blob in bytes, see [`VaultMetadata`].
+pub const MAX_METADATA_LEN: usize = 256;
+
+#[derive(Debug, GetSeeds, Clone)]
+#[get_seeds(seed_const = b"METADATA")]
+pub struct VaultMetadataSeeds {
+    pub state: Pubkey,
+}
+
+/// User-defined data about a vault, e.g. for wallets, in its own account so the hot
+/// [`VaultState`] stays small. Opened by the first [`SetMetadataIx`], closed by
+/// [`ClearMetadataIx`] or along with the vault.
+#[zero_copy(pod)]
+#[derive(Debug, Eq, PartialEq, ProgramAccount)]
+#[program_account(seeds = VaultMetadataSeeds)]
+pub struct VaultMetadata {
+    // State of the vault the metadata describes
+    pub state: Pubkey,
+    pub len: u16,
+    // Zero padded past `len`
+    pub data: [u8; MAX_METADATA_LEN],
+}
+
+// Arrays this long have no `Default`, and an all-zero metadata account is the empty one
+impl Default for VaultMetadata {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl VaultMetadata {
+    /// The stored blob without its padding.
+    pub fn metadata(&self) -> &[u8] {
+        &self.data[..usize::from(self.len).min(MAX_METADATA_LEN)]
+    }
+}
+
+/// Stores up to [`MAX_METADATA_LEN`] bytes of metadata for the vault, replacing any already
+/// there. The owner pays the metadata account's rent the first time.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetMetadataIx {
+    #[ix_args(&run)]
+    pub data: Vec<u8>,
+}
+
+#[derive(AccountSet)]
+pub struct SetMetadataAccounts {
+    #[validate(funder)]
+    pub user: Signer<Mut<SystemAccount>>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    #[validate(arg = (
+        CreateIfNeeded(()),
+        Seeds(VaultMetadataSeeds { state: *self.vault_state.pubkey() }),
+    ))]
+    pub metadata: Init<Seeded<Account<VaultMetadata>>>,
+    pub system_program: Program<System>,
+}
+
+#[star_frame_instruction]
+fn SetMetadataIx(a: &mut SetMetadataAccounts, data: &Vec<u8>) -> Result<()> {
+    require!(
+        data.len() <= MAX_METADATA_LEN,
+        VaultError::InvalidArgument,
+        "Metadata is too long"
+    );
+    let mut padded = [0; MAX_METADATA_LEN];
+    padded[..data.len()].copy_from_slice(data);
+    **a.metadata.data_mut()? = VaultMetadata {
+        state: *a.vault_state.pubkey(),
+        // Fits, it is at most `MAX_METADATA_LEN`
+        len: data.len() as u16,
+        data: padded,
+    };
+    a.vault_state.data_mut()?.has_metadata = PodBool::from(true);
+    Ok(())
+}
+
+/// Closes the vault's metadata account, refunding its rent to the owner.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct ClearMetadataIx;
+
+#[derive(AccountSet)]
+pub struct ClearMetadataAccounts {
     #[validate(recipient)]
     pub user: Signer<Mut<SystemAccount>>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    // Checked against the state in the handler
+    #[cleanup(arg = CloseAccount(()))]
+    pub metadata: Mut<Account<VaultMetadata>>,
+}
+
+#[star_frame_instruction]
+fn ClearMetadataIx(a: &mut ClearMetadataAccounts, _run: ()) -> Result<()> {
+    require!(
+        a.metadata.data()?.state == *a.vault_state.pubkey(),
+        VaultError::InvalidAccount,
+        "Metadata belongs to another vault"
+    );
+    a.vault_state.data_mut()?.has_metadata = PodBool::from(false);
+    Ok(())
+}
+
+/* -------------------- Set Keeper Sweep -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct SetKeeperSweepIx {
+    pub sweep_threshold: u64,
+    pub keeper_tip: u64,
+}
+
+// Owner only: the keeper tip is paid out of the vault, so an admin could set it to the whole
+// balance and sweep it to themselves
+#[derive(AccountSet)]
+pub struct SetKeeperSweepAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetKeeperSweepIx(a: &mut SetKeeperSweepAccounts, ix: &SetKeeperSweepIx) -> Result<()> {
+    let mut state = a.vault_state.data_mut()?;
+    state.sweep_threshold = ix.sweep_threshold;
+    state.keeper_tip = ix.keeper_tip;
+    Ok(())
+}
+
+/* -------------------- Set Sandwich Guard -------------------- */
+
+/// Makes [`WithdrawIx`], [`WithdrawPercentIx`], [`WithdrawToProgramIx`] and
+/// [`WithdrawWithApprovalIx`] fail when an earlier instruction in the transaction writes the
+/// vault. [`WithdrawTokenBatchIx`] pays out of a token account rather than the vault's lamports,
+/// so it isn't guarded.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetSandwichGuardIx {
+    #[ix_args(run)]
+    pub sandwich_guard: bool,
+}
+
+#[derive(AccountSet)]
+pub struct SetSandwichGuardAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetSandwichGuardIx(a: &mut SetSandwichGuardAccounts, sandwich_guard: bool) -> Result<()> {
+    a.vault_state.data_mut()?.sandwich_guard.set(sandwich_guard);
+    Ok(())
+}
+
+/* -------------------- Set Max Slot Age -------------------- */
+
+/// Caps how long a relayed [`WithdrawWithApprovalIx`] stays valid, in slots. 0 lifts the cap.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetMaxSlotAgeIx {
+    #[ix_args(run)]
+    pub max_slot_age: u64,
+}
+
+#[derive(AccountSet)]
+pub struct SetMaxSlotAgeAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetMaxSlotAgeIx(a: &mut SetMaxSlotAgeAccounts, max_slot_age: u64) -> Result<()> {
+    a.vault_state.data_mut()?.max_slot_age = max_slot_age;
+    Ok(())
+}
+
+/* -------------------- Set Withdraw Policy -------------------- */
+
+/// Selects the [`WithdrawPolicy`]. Owner-only, since every policy but `All` and `RateLimit`
+/// switches off the owner's cooldown. A bound recipient and an unexpired first withdraw delay
+/// are commitments, so while either holds only a policy that keeps running its guard is
+/// accepted.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetWithdrawPolicyIx {
+    #[ix_args(run)]
+    pub policy: u8,
+}
+
+#[derive(AccountSet)]
+pub struct SetWithdrawPolicyAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetWithdrawPolicyIx(
+    a: &mut SetWithdrawPolicyAccounts,
+    policy: u8,
+    ctx: &mut Context,
+) -> Result<()> {
+    WithdrawPolicy::try_from(policy)?;
+    let slot = ctx.get_clock()?.slot;
+    let mut state = a.vault_state.data_mut()?;
+    let timelocked = slot < state.init_slot.saturating_add(state.first_withdraw_delay);
+    let bound = state.bound_recipient().is_some();
+    state.withdraw_policy = policy;
+    require!(
+        !timelocked || state.guards(WithdrawPolicy::Timelock),
+        VaultError::InvalidState,
+        "The first withdraw delay has not elapsed"
+    );
+    require!(
+        !bound || state.guards(WithdrawPolicy::Whitelist),
+        VaultError::InvalidState,
+        "Vault is bound to a recipient"
+    );
+    Ok(())
+}
+
+/* -------------------- Set Log Level -------------------- */
+
+/// Sets which of the vault's events are logged, see [`LogLevel`]. Events are only logged at all
+/// on builds with the `verbose-logs` feature.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetLogLevelIx {
+    #[ix_args(run)]
+    pub log_level: u8,
+}
+
+#[derive(AccountSet)]
+pub struct SetLogLevelAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetLogLevelIx(a: &mut SetLogLevelAccounts, log_level: u8) -> Result<()> {
+    LogLevel::try_from(log_level)?;
+    a.vault_state.data_mut()?.log_level = log_level;
+    Ok(())
+}
+
+/* -------------------- Set Admin -------------------- */
+
+/// Hands the vault's limits and policy to `admin`, e.g. a service managing the vault for its
+/// owner. Signed by the current admin or the owner, who can revoke the role at any time.
+/// Setting the owner or `Pubkey::default()` returns the role to the owner.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetAdminIx {
+    #[ix_args(run)]
+    pub admin: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct SetAdminAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin or the owner of the vault state account
+    #[validate(arg = VaultAdminOrOwner(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetAdminIx(a: &mut SetAdminAccounts, admin: Pubkey) -> Result<()> {
+    let mut state = a.vault_state.data_mut()?;
+    state.admin = if admin == state.owner {
+        Pubkey::default()
+    } else {
+        admin
+    };
+    Ok(())
+}
+
+/* -------------------- Set Close Authority -------------------- */
+
+/// Lets `close_authority`, e.g. an estate executor, close the vault with [`CloseIx`] or
+/// [`SafeCloseIx`] as well as the owner. The refund still goes to the owner. Setting the owner or
+/// `Pubkey::default()` leaves closing to the owner alone.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetCloseAuthorityIx {
+    #[ix_args(run)]
+    pub close_authority: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct SetCloseAuthorityAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetCloseAuthorityIx(a: &mut SetCloseAuthorityAccounts, close_authority: Pubkey) -> Result<()> {
+    let mut state = a.vault_state.data_mut()?;
+    state.close_authority = if close_authority == state.owner {
+        Pubkey::default()
+    } else {
+        close_authority
+    };
+    Ok(())
+}
+
+/* -------------------- Bind Recipient -------------------- */
+
+/// Binds the vault to `recipient` for good: from then on every withdrawal, held withdrawal and
+/// close pays only that address. It can't be changed or unset, so a lost or stolen owner key
+/// can't redirect the funds.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct BindRecipientIx {
+    #[ix_args(run)]
+    pub recipient: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct BindRecipientAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn BindRecipientIx(a: &mut BindRecipientAccounts, recipient: Pubkey) -> Result<()> {
+    require!(
+        recipient != Pubkey::default(),
+        VaultError::InvalidArgument,
+        "Recipient must not be the default pubkey"
+    );
+    // Paying the vault or its state would only move the lamports in a loop
+    let vault_seeds = SeedsWithBump {
+        seeds: VaultSeeds {
+            state: *a.vault_state.pubkey(),
+        },
+        bump: a.vault_state.data()?.vault_bump,
+    };
+    let vault = Pubkey::create_program_address(&vault_seeds.seeds_with_bump(), &VaultProgram::ID)
+        .map_err(|_| VaultError::InvalidAccount.context("Invalid vault bump"))?;
+    require!(
+        recipient != vault && recipient != *a.vault_state.pubkey(),
+        VaultError::InvalidArgument,
+        "Recipient must not be the vault or its state"
+    );
+    let mut state = a.vault_state.data_mut()?;
+    require!(
+        state.bound_recipient().is_none(),
+        VaultError::InvalidState,
+        "Vault is already bound to a recipient"
+    );
+    require!(
+        state.guards(WithdrawPolicy::Whitelist),
+        VaultError::InvalidState,
+        "Withdraw policy does not run the recipient whitelist"
+    );
+    // Forwarded deposits never reach the vault, so they have to go to the bound recipient too
+    require!(
+        state
+            .auto_forward()
+            .is_none_or(|auto_forward| auto_forward == recipient),
+        VaultError::InvalidState,
+        "Vault auto-forwards to another address"
+    );
+    // A withdrawal requested before binding would still pay its own recipient
+    require!(
+        state.pending_amount == 0,
+        VaultError::WithdrawalPending,
+        "Cancel the pending withdrawal before binding a recipient"
+    );
+    state.bound_recipient = recipient;
+    Ok(())
+}
+
+/* -------------------- Set Auto Forward -------------------- */
+
+/// Turns the vault into a pass-through: every [`DepositIx`], [`TipIx`] and [`DepositToOwnerIx`]
+/// is forwarded to `auto_forward`, which those then need as `forward_to`. [`DepositRawIx`] and
+/// plain transfers skip the state and still land in the vault. `Pubkey::default()` turns it off. Forwarding skips the withdrawal guards, so it can't be set
+/// while the first withdraw delay runs, and a bound vault can only forward to its recipient.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetAutoForwardIx {
+    #[ix_args(run)]
+    pub auto_forward: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct SetAutoForwardAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetAutoForwardIx(
+    a: &mut SetAutoForwardAccounts,
+    auto_forward: Pubkey,
+    ctx: &mut Context,
+) -> Result<()> {
+    let mut state = a.vault_state.data_mut()?;
+    if auto_forward != Pubkey::default() {
+        state.ensure_first_withdraw_elapsed(ctx.get_clock()?.slot)?;
+        state.ensure_recipient_allowed(&auto_forward)?;
+    }
+    state.auto_forward = auto_forward;
+    Ok(())
+}
+
+/* -------------------- Guardian Recovery -------------------- */
+
+pub const MAX_GUARDIANS: usize = 3;
+
+/// Sets the guardians who can recover the vault if the owner loses their key, and how many of
+/// them must sign. A `threshold` of 0 disables recovery.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+#[ix_args(&run)]
+pub struct SetGuardiansIx {
+    pub guardians: [Pubkey; MAX_GUARDIANS],
+    pub threshold: u8,
+}
+
+#[derive(AccountSet)]
+pub struct SetGuardiansAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn SetGuardiansIx(a: &mut SetGuardiansAccounts, ix: &SetGuardiansIx) -> Result<()> {
+    let set = ix
+        .guardians
+        .iter()
+        .filter(|guardian| **guardian != Pubkey::default())
+        .collect::<Vec<_>>();
+    for (i, guardian) in set.iter().enumerate() {
+        require!(
+            !set[..i].contains(guardian),
+            VaultError::InvalidArgument,
+            "Duplicate guardian"
+        );
+    }
+    require!(
+        usize::from(ix.threshold) <= set.len(),
+        VaultError::InvalidArgument,
+        "Threshold exceeds the number of guardians"
+    );
+
+    let mut state = a.vault_state.data_mut()?;
+    state.guardians = ix.guardians;
+    state.guardian_threshold = ix.threshold;
+    Ok(())
+}
+
+/// Hands the vault to `new_owner` when at least `guardian_threshold` guardians sign, passed as
+/// the remaining accounts. Whoever holds the lost key may have set the vault up to pay them, so
+/// any pending withdrawal is cancelled and `auto_forward`, `admin` and `close_authority` are
+/// cleared.
+///
+/// The state keeps its address, which stays derived from the original owner.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct GuardianRecoverIx {
+    #[ix_args(run)]
+    pub new_owner: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct GuardianRecoverAccounts {
+    pub vault_state: Mut<Account<VaultState>>,
+    pub guardians: Rest<AccountInfo>,
+}
+
+#[star_frame_instruction]
+fn GuardianRecoverIx(a: &mut GuardianRecoverAccounts, new_owner: Pubkey) -> Result<()> {
+    require!(
+        new_owner != Pubkey::default(),
+        VaultError::InvalidArgument,
+        "Invalid new owner"
+    );
+    let mut state = a.vault_state.data_mut()?;
+    require!(
+        state.guardian_threshold != 0,
+        VaultError::InvalidState,
+        "Guardian recovery is disabled"
+    );
+
+    // Each guardian counts once, however many times it is passed
+    let approvals = state
+        .guardians
+        .iter()
+        .filter(|guardian| **guardian != Pubkey::default())
+        .filter(|guardian| {
+            a.guardians
+                .iter()
+                .any(|info| info.pubkey() == *guardian && info.is_signer())
+        })
+        .count();
+    require!(
+        approvals >= usize::from(state.guardian_threshold),
+        VaultError::Unauthorized,
+        "Not enough guardian signatures"
+    );
+
+    state.owner = new_owner;
+    state.pending_recipient = Pubkey::default();
+    state.pending_amount = 0;
+    state.pending_release_slot = 0;
+    state.auto_forward = Pubkey::default();
+    state.admin = Pubkey::default();
+    state.close_authority = Pubkey::default();
+    Ok(())
+}
+
+/* -------------------- Configure -------------------- */
+
+/// Updates any subset of the vault settings at once, `None` leaves a setting as it is. Each
+/// field is checked like its single-setting instruction, and nothing changes if any check fails.
+///
+/// Signed by the admin. `yield_program`, `cooldown_slots`, `burn_dust`, `first_withdraw_delay`,
+/// `label`, `sweep_threshold` and `keeper_tip` stay with the owner like their single-setting
+/// instructions, so setting any of them also needs the admin to be the owner.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, InstructionArgs)]
+#[ix_args(&run)]
+pub struct ConfigureIx {
+    pub yield_program: Option<Pubkey>,
+    pub cooldown_slots: Option<u64>,
+    pub burn_dust: Option<bool>,
+    pub max_deposit_per_tx: Option<u64>,
+    pub min_deposit: Option<u64>,
+    pub balance_cap: Option<u64>,
+    pub rent_buffer_bps: Option<u16>,
+    pub first_withdraw_delay: Option<u64>,
+    pub label: Option<Vec<u8>>,
+    pub sweep_threshold: Option<u64>,
+    pub keeper_tip: Option<u64>,
+    pub sandwich_guard: Option<bool>,
+}
+
+#[derive(AccountSet)]
+pub struct ConfigureAccounts {
+    pub user: Signer<SystemAccount>,
+    // Validate that the user is the admin of the vault state account
+    #[validate(arg = VaultAdmin(self.user.pubkey()))]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn ConfigureIx(a: &mut ConfigureAccounts, ix: &ConfigureIx) -> Result<()> {
+    // Applied to a copy, so a failing check leaves the stored state untouched
+    let mut state = **a.vault_state.data()?;
+    if ix.yield_program.is_some()
+        || ix.cooldown_slots.is_some()
+        || ix.burn_dust.is_some()
+        || ix.first_withdraw_delay.is_some()
+        || ix.label.is_some()
+        || ix.sweep_threshold.is_some()
+        || ix.keeper_tip.is_some()
+    {
+        require!(
+            state.owner == *a.user.pubkey(),
+            VaultError::Unauthorized,
+            "Incorrect owner"
+        );
+    }
+    if let Some(yield_program) = ix.yield_program {
+        state.yield_program = yield_program;
+    }
+    if let Some(cooldown_slots) = ix.cooldown_slots {
+        state.cooldown_slots = cooldown_slots;
+    }
+    if let Some(burn_dust) = ix.burn_dust {
+        state.burn_dust.set(burn_dust);
+    }
+    if let Some(max_deposit_per_tx) = ix.max_deposit_per_tx {
+        state.max_deposit_per_tx = max_deposit_per_tx;
+    }
+    if let Some(min_deposit) = ix.min_deposit {
+        state.min_deposit = min_deposit;
+    }
+    if let Some(balance_cap) = ix.balance_cap {
+        state.balance_cap = balance_cap;
+    }
+    if let Some(rent_buffer_bps) = ix.rent_buffer_bps {
+        state.rent_buffer_bps = rent_buffer_bps;
+    }
+    if let Some(first_withdraw_delay) = ix.first_withdraw_delay {
+        state.set_first_withdraw_delay(first_withdraw_delay)?;
+    }
+    if let Some(label) = &ix.label {
+        state.set_label(label)?;
+    }
+    if let Some(sweep_threshold) = ix.sweep_threshold {
+        state.sweep_threshold = sweep_threshold;
+    }
+    if let Some(keeper_tip) = ix.keeper_tip {
+        state.keeper_tip = keeper_tip;
+    }
+    if let Some(sandwich_guard) = ix.sandwich_guard {
+        state.sandwich_guard.set(sandwich_guard);
+    }
+    **a.vault_state.data_mut()? = state;
+    Ok(())
+}
+
+/* -------------------- Harvest -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct HarvestIx {
+    // Instruction data forwarded to the yield program untouched
+    #[ix_args(&run)]
+    pub data: Vec<u8>,
+}
+
+#[derive(AccountSet)]
+pub struct HarvestAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data_mut()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: ValidatedAccount<VaultState>,
+    pub yield_program: AccountInfo,
+    pub global_config: Account<GlobalConfig>,
+    // Remaining accounts are handed to the yield program with their signer/writable flags
+    pub passthrough: Rest<AccountInfo>,
+}
+
+#[star_frame_instruction]
+fn HarvestIx(a: &mut HarvestAccounts, data: &Vec<u8>) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let yield_program = a.vault_state.data()?.yield_program;
+    // No adapter configured, nothing to sweep
+    if yield_program == Pubkey::default() {
+        return Ok(());
+    }
+    require!(
+        *a.yield_program.pubkey() == yield_program,
+        VaultError::InvalidAccount,
+        "Incorrect yield program"
+    );
+
+    let metas = a
+        .passthrough
+        .iter()
+        .map(|info| PinocchioAccountMeta::new(info.key(), info.is_writable(), info.is_signer()))
+        .collect::<Vec<_>>();
+    let infos = a.passthrough.iter().collect::<Vec<_>>();
+
+    // The vault never signs, so the yield program can only add lamports to it
+    let lamports_before = a.vault.lamports();
+    slice_invoke(
+        &PinocchioInstruction {
+            program_id: a.yield_program.key(),
+            data,
+            accounts: &metas,
+        },
+        &infos,
+    )?;
+    require!(
+        a.vault.lamports() >= lamports_before,
+        VaultError::InvalidState,
+        "Harvest decreased the vault balance"
+    );
+
+    Ok(())
+}
+
+/* -------------------- Stake Vault -------------------- */
+
+pub const STAKE_PROGRAM_ID: Pubkey = pubkey!("Stake11111111111111111111111111111111111111");
+
+// Size of a `StakeStateV2` account
+const STAKE_ACCOUNT_LEN: u64 = 200;
+// `StakeInstruction` tags, bincode encodes them as a u32
+const STAKE_INITIALIZE_TAG: u32 = 0;
+const STAKE_DELEGATE_TAG: u32 = 2;
+const STAKE_WITHDRAW_TAG: u32 = 4;
+const STAKE_DEACTIVATE_TAG: u32 = 5;
+
+/// Calls the stake program with `accounts` as (account, writable) pairs. The vault signs, as
+/// the stake account's staker and withdrawer.
+fn invoke_stake_program(
+    stake_program: &AccountInfo,
+    data: &[u8],
+    accounts: &[(&AccountInfo, bool)],
+    vault: &Pubkey,
+    vault_seeds: &[&[u8]],
+) -> Result<()> {
+    require!(
+        *stake_program.pubkey() == STAKE_PROGRAM_ID,
+        VaultError::InvalidAccount,
+        "Incorrect stake program"
+    );
+    let metas = accounts
+        .iter()
+        .map(|(info, writable)| {
+            PinocchioAccountMeta::new(info.key(), *writable, info.pubkey() == vault)
+        })
+        .collect::<Vec<_>>();
+    let infos = accounts.iter().map(|(info, _)| *info).collect::<Vec<_>>();
+    let seeds = vault_seeds
+        .iter()
+        .map(|seed| Seed::from(*seed))
+        .collect::<Vec<_>>();
+    slice_invoke_signed(
+        &PinocchioInstruction {
+            program_id: stake_program.key(),
+            data,
+            accounts: &metas,
+        },
+        &infos,
+        &[PinocchioSigner::from(&seeds[..])],
+    )?;
+    Ok(())
+}
+
+/// Opens the vault's stake account, a PDA of the vault, funded with `lamports` from the owner
+/// (including its rent-exempt minimum). The vault is its staker and withdrawer, so it's only
+/// managed through [`DelegateIx`], [`DeactivateIx`] and [`WithdrawStakeIx`], which returns the
+/// lamports to the vault. The vault can't be closed until the stake account is withdrawn in full.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct InitializeStakeVaultIx {
+    #[ix_args(run)]
+    pub lamports: u64,
+}
+
+#[derive(AccountSet)]
+pub struct InitializeStakeVaultAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    #[validate(arg = Seeds(StakeVaultSeeds { vault: *self.vault.pubkey() }))]
+    pub stake_account: Seeded<Mut<AccountInfo>, StakeVaultSeeds>,
+    pub rent_sysvar: AccountInfo,
+    pub stake_program: AccountInfo,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn InitializeStakeVaultIx(a: &mut InitializeStakeVaultAccounts, lamports: u64) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let stake_info = *a.stake_account.account_info();
+    let stake_seeds = a.stake_account.access_seeds().seeds_with_bump();
+    // Lamports sent to the address beforehand would make `CreateAccount` fail, so a prefunded
+    // account is topped up, allocated and assigned instead
+    let prefunded = stake_info.lamports();
+    if prefunded == 0 {
+        if a.user.account_info().lamports() < lamports {
+            return Err(VaultError::InsufficientFunds.into());
+        }
+        System::cpi(
+            CreateAccount {
+                lamports,
+                space: STAKE_ACCOUNT_LEN,
+                owner: STAKE_PROGRAM_ID,
+            },
+            CreateAccountCpiAccounts {
+                funder: *a.user.account_info(),
+                new_account: stake_info,
+            },
+            None,
+        )
+        .invoke_signed(&[&stake_seeds])?;
+    } else {
+        if lamports > prefunded {
+            transfer_lamports(
+                *a.user.account_info(),
+                stake_info,
+                lamports - prefunded,
+                &[],
+            )?;
+        }
+        System::cpi(
+            Allocate {
+                space: STAKE_ACCOUNT_LEN,
+            },
+            AllocateCpiAccounts {
+                account: stake_info,
+            },
+            None,
+        )
+        .invoke_signed(&[&stake_seeds])?;
+        System::cpi(
+            Assign {
+                owner: STAKE_PROGRAM_ID,
+            },
+            AssignCpiAccounts {
+                account: stake_info,
+            },
+            None,
+        )
+        .invoke_signed(&[&stake_seeds])?;
+    }
+
+    // `Authorized { staker, withdrawer }`, then a lockup that never binds
+    let vault = *a.vault.pubkey();
+    let mut data = STAKE_INITIALIZE_TAG.to_le_bytes().to_vec();
+    data.extend_from_slice(vault.as_ref());
+    data.extend_from_slice(vault.as_ref());
+    data.extend_from_slice(&[0; 8 + 8 + 32]);
+    invoke_stake_program(
+        &a.stake_program,
+        &data,
+        &[(&stake_info, true), (&a.rent_sysvar, false)],
+        &vault,
+        &a.vault.access_seeds().seeds_with_bump(),
+    )?;
+    a.vault_state.data_mut()?.has_stake_account = PodBool::from(true);
+    Ok(())
+}
+
+/// Delegates the vault's stake account to `vote_account`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct DelegateIx;
+
+#[derive(AccountSet)]
+pub struct DelegateAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: ValidatedAccount<VaultState>,
+    #[validate(arg = Seeds(StakeVaultSeeds { vault: *self.vault.pubkey() }))]
+    pub stake_account: Seeded<Mut<AccountInfo>, StakeVaultSeeds>,
+    pub vote_account: AccountInfo,
+    pub clock_sysvar: AccountInfo,
+    pub stake_history_sysvar: AccountInfo,
+    // Still part of the stake program's accounts, though it no longer reads it
+    pub stake_config: AccountInfo,
+    pub stake_program: AccountInfo,
+}
+
+#[star_frame_instruction]
+fn DelegateIx(a: &mut DelegateAccounts, _run: ()) -> Result<()> {
+    let vault = *a.vault.pubkey();
+    invoke_stake_program(
+        &a.stake_program,
+        &STAKE_DELEGATE_TAG.to_le_bytes(),
+        &[
+            (a.stake_account.account_info(), true),
+            (&a.vote_account, false),
+            (&a.clock_sysvar, false),
+            (&a.stake_history_sysvar, false),
+            (&a.stake_config, false),
+            (a.vault.account_info(), false),
+        ],
+        &vault,
+        &a.vault.access_seeds().seeds_with_bump(),
+    )
+}
+
+/// Deactivates the vault's stake, which can be withdrawn with [`WithdrawStakeIx`] once it has
+/// cooled down.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct DeactivateIx;
+
+#[derive(AccountSet)]
+pub struct DeactivateAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: ValidatedAccount<VaultState>,
+    #[validate(arg = Seeds(StakeVaultSeeds { vault: *self.vault.pubkey() }))]
+    pub stake_account: Seeded<Mut<AccountInfo>, StakeVaultSeeds>,
+    pub clock_sysvar: AccountInfo,
+    pub stake_program: AccountInfo,
+}
+
+#[star_frame_instruction]
+fn DeactivateIx(a: &mut DeactivateAccounts, _run: ()) -> Result<()> {
+    let vault = *a.vault.pubkey();
+    invoke_stake_program(
+        &a.stake_program,
+        &STAKE_DEACTIVATE_TAG.to_le_bytes(),
+        &[
+            (a.stake_account.account_info(), true),
+            (&a.clock_sysvar, false),
+            (a.vault.account_info(), false),
+        ],
+        &vault,
+        &a.vault.access_seeds().seeds_with_bump(),
+    )
+}
+
+/// Moves `lamports` of inactive stake, or the whole stake account to close it, back into the
+/// vault, where it is withdrawn like any other balance. Once the stake account is closed the vault
+/// can be closed too. It only ever pays the vault, so like
+/// closing it still works during an emergency pause.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WithdrawStakeIx {
+    #[ix_args(run)]
+    pub lamports: u64,
+}
+
+#[derive(AccountSet)]
+pub struct WithdrawStakeAccounts {
+    pub user: Signer<SystemAccount>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+    #[validate(arg = Seeds(StakeVaultSeeds { vault: *self.vault.pubkey() }))]
+    pub stake_account: Seeded<Mut<AccountInfo>, StakeVaultSeeds>,
+    pub clock_sysvar: AccountInfo,
+    pub stake_history_sysvar: AccountInfo,
+    pub stake_program: AccountInfo,
+}
+
+#[star_frame_instruction]
+fn WithdrawStakeIx(a: &mut WithdrawStakeAccounts, lamports: u64) -> Result<()> {
+    let vault = *a.vault.pubkey();
+    let mut data = STAKE_WITHDRAW_TAG.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    // The vault is both the recipient and the withdraw authority
+    invoke_stake_program(
+        &a.stake_program,
+        &data,
+        &[
+            (a.stake_account.account_info(), true),
+            (a.vault.account_info(), true),
+            (&a.clock_sysvar, false),
+            (&a.stake_history_sysvar, false),
+            (a.vault.account_info(), true),
+        ],
+        &vault,
+        &a.vault.access_seeds().seeds_with_bump(),
+    )?;
+    // Withdrawing everything closes the stake account
+    if a.stake_account.account_info().lamports() == 0 {
+        a.vault_state.data_mut()?.has_stake_account = PodBool::from(false);
+    }
+    Ok(())
+}
+
+/* -------------------- Resize State -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct ResizeStateIx;
+
+#[derive(AccountSet)]
+pub struct ResizeStateAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    // Not a `ValidatedAccount`: an outdated layout can't be loaded as the current `VaultState`,
+    // so ownership is checked against the raw bytes in the handler
+    pub vault_state: Mut<AccountInfo>,
+    pub system_program: Program<System>,
+}
+
+#[star_frame_instruction]
+fn ResizeStateIx(a: &mut ResizeStateAccounts, _run: (), ctx: &mut Context) -> Result<()> {
+    let info = *a.vault_state.account_info();
+    // Checks the program owner and discriminant, but not the data length
+    VaultState::validate_account_info(info)?;
+
+    // Fields are only ever appended, so `owner` stays right after the discriminant
+    let owner_offset = size_of_val(&VaultState::DISCRIMINANT);
+    let current_len = info.data_len();
+    {
+        let data = info.try_borrow_data()?;
+        require!(
+            data.get(owner_offset..owner_offset + size_of::<Pubkey>())
+                == Some(a.user.pubkey().as_ref()),
+            VaultError::Unauthorized,
+            "Incorrect owner"
+        );
+    }
+
+    let new_len = owner_offset + size_of::<VaultState>();
+    require!(
+        current_len <= new_len,
+        VaultError::InvalidAccount,
+        "State account is larger than the current layout"
+    );
+    // New fields are zero-initialized, which is their default
+    info.resize(new_len)?;
+    a.vault_state.normalize_rent(&a.user, ctx)?;
+
+    Ok(())
+}
+
+/* -------------------- Recover State Lamports -------------------- */
+
+/// Returns lamports sent to the state PDA by mistake (rather than to the vault) to the owner,
+/// leaving the state with exactly its rent-exempt minimum.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct RecoverStateLamportsIx;
+
+#[derive(AccountSet)]
+pub struct RecoverStateLamportsAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    // Validate that the user is the owner of the vault state account
+    #[validate(arg = self.user.pubkey())]
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
+}
+
+#[star_frame_instruction]
+fn RecoverStateLamportsIx(
+    a: &mut RecoverStateLamportsAccounts,
+    _run: (),
+    ctx: &mut Context,
+) -> Result<()> {
+    // The state is owned by this program, so the excess is moved directly without a CPI
+    a.vault_state.refund_rent(&a.user, ctx)?;
+    Ok(())
+}
+
+/* -------------------- Normalize Bump -------------------- */
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct NormalizeBumpIx;
+
+#[derive(AccountSet)]
+pub struct NormalizeBumpAccounts {
+    pub user: Signer<SystemAccount>,
+    // The vault at the bump currently stored in the state
     #[validate(arg = SeedsWithBump {
         seeds: VaultSeeds { state: *self.vault_state.pubkey() },
-        bump: self.vault_state.data_mut()?.vault_bump,
+        bump: self.vault_state.data()?.vault_bump,
     })]
     pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Derived with `find_program_address`, so always the canonical bump
+    #[validate(arg = Seeds(VaultSeeds { state: *self.vault_state.pubkey() }))]
+    pub canonical_vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
     // Validate that the user is the owner of the vault state account
     #[validate(arg = self.user.pubkey())]
-    // Close the vault state account at the end of the instruction
-    #[cleanup(arg = CloseAccount(()))]
-    pub vault_state: ValidatedAccount<VaultState>,
+    pub vault_state: Mut<ValidatedAccount<VaultState>>,
     pub system_program: Program<System>,
 }
 
 #[star_frame_instruction]
-fn CloseIx(a: &mut CloseAccounts, _run: (), _ctx: &mut Context) -> Result<()> {
+fn NormalizeBumpIx(a: &mut NormalizeBumpAccounts, _run: ()) -> Result<()> {
+    let canonical_bump = a.canonical_vault.access_seeds().bump;
+    if a.vault.access_seeds().bump == canonical_bump {
+        return Ok(());
+    }
+
+    // Move everything to the canonical address, leaving the old vault empty
     let lamports = a.vault.lamports();
     if lamports > 0 {
         let signer_seeds = a.vault.access_seeds().seeds_with_bump();
-        System::cpi(
-            Transfer { lamports },
-            TransferCpiAccounts {
-                funder: *a.vault.account_info(),
-                recipient: *a.user.account_info(),
-            },
-            None,
-        )
-        .invoke_signed(&[&signer_seeds])?;
+        transfer_lamports(
+            *a.vault.account_info(),
+            *a.canonical_vault.account_info(),
+            lamports,
+            &[&signer_seeds],
+        )?;
+    }
+
+    a.vault_state.data_mut()?.vault_bump = canonical_bump;
+    Ok(())
+}
+
+/* -------------------- Assert Balance -------------------- */
+
+/// Fails the transaction unless the vault holds at least `expected` lamports, so builders can
+/// check the vault before or after other instructions in the same transaction.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct AssertBalanceIx {
+    #[ix_args(run)]
+    pub expected: u64,
+}
+
+#[derive(AccountSet)]
+pub struct AssertBalanceAccounts {
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: self.vault_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    pub vault_state: Account<VaultState>,
+}
+
+#[star_frame_instruction]
+fn AssertBalanceIx(a: &mut AssertBalanceAccounts, expected: u64) -> Result<()> {
+    if a.vault.lamports() < expected {
+        return Err(VaultError::BalanceBelowExpected.into());
+    }
+    Ok(())
+}
+
+/* -------------------- Read State -------------------- */
+
+/// Returns the whole [`VaultState`] and the vault balance as return data, so a client can read
+/// them by simulating this instruction instead of fetching and decoding the accounts. Decode it
+/// with `client::decode_read_state_return_data`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct ReadStateIx;
+
+#[derive(AccountSet)]
+pub struct ReadStateAccounts {
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.vault_state.pubkey() },
+        bump: VaultState::read_tolerant(&self.vault_state)?.vault_bump,
+    })]
+    pub vault: Seeded<SystemAccount, VaultSeeds>,
+    // Read with `read_tolerant`, so states on an older layout come back with defaulted fields
+    pub vault_state: AccountInfo,
+}
+
+/// Returned from [`ReadStateIx`]
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct ReadStateResult {
+    pub state: VaultState,
+    pub vault_lamports: u64,
+}
+
+#[star_frame_instruction]
+fn ReadStateIx(a: &mut ReadStateAccounts, _run: ()) -> Result<ReadStateResult> {
+    Ok(ReadStateResult {
+        state: VaultState::read_tolerant(&a.vault_state)?,
+        vault_lamports: a.vault.lamports(),
+    })
+}
+
+/* -------------------- Derive Vault -------------------- */
+
+/// Returns the vault PDA of `state` and its canonical bump, for integrations that need the
+/// address before the state exists. Takes no accounts, so it can be simulated for any state.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct DeriveVaultIx {
+    #[ix_args(run)]
+    pub state: Pubkey,
+}
+
+#[derive(AccountSet)]
+pub struct DeriveVaultAccounts {}
+
+/// Returned from [`DeriveVaultIx`]
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct DerivedVault {
+    pub vault: Pubkey,
+    pub vault_bump: u8,
+}
+
+#[star_frame_instruction]
+fn DeriveVaultIx(_a: &mut DeriveVaultAccounts, state: Pubkey) -> Result<DerivedVault> {
+    let (vault, vault_bump) =
+        Pubkey::find_program_address(&VaultSeeds { state }.seeds(), &VaultProgram::ID);
+    Ok(DerivedVault { vault, vault_bump })
+}
+
+/* -------------------- Global Config -------------------- */
+
+/// The upgradeable BPF loader, owner of this program's `ProgramData` account
+pub const BPF_LOADER_UPGRADEABLE_ID: Pubkey =
+    pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+// `UpgradeableLoaderState::ProgramData` is a u32 variant tag, the deployment slot and then the
+// optional upgrade authority
+const PROGRAM_DATA_TAG: u32 = 3;
+const PROGRAM_DATA_AUTHORITY_OFFSET: usize = 4 + 8;
+
+/// Checks that `program_data` is this program's `ProgramData` account and that `authority`
+/// is its upgrade authority. Immutable programs have no upgrade authority and always fail.
+pub fn ensure_upgrade_authority(program_data: &AccountInfo, authority: &Pubkey) -> Result<()> {
+    let (expected, _) =
+        Pubkey::find_program_address(&[VaultProgram::ID.as_ref()], &BPF_LOADER_UPGRADEABLE_ID);
+    require!(
+        *program_data.pubkey() == expected,
+        VaultError::InvalidAccount,
+        "Incorrect program data account"
+    );
+    require!(
+        program_data.owner_pubkey() == BPF_LOADER_UPGRADEABLE_ID,
+        VaultError::InvalidAccount,
+        "Program data is not owned by the upgradeable loader"
+    );
+
+    let data = program_data.try_borrow_data()?;
+    require!(
+        data.len() >= PROGRAM_DATA_AUTHORITY_OFFSET + 1 + 32
+            && data[..4] == PROGRAM_DATA_TAG.to_le_bytes(),
+        VaultError::InvalidAccount,
+        "Invalid program data account"
+    );
+    let stored = &data[PROGRAM_DATA_AUTHORITY_OFFSET..PROGRAM_DATA_AUTHORITY_OFFSET + 1 + 32];
+    require!(
+        stored[0] == 1 && stored[1..] == authority.to_bytes(),
+        VaultError::Unauthorized,
+        "Signer is not the program upgrade authority"
+    );
+    Ok(())
+}
+
+/// Creates the [`GlobalConfig`] with the signer as admin. It can only be created once, so only
+/// the program's upgrade authority may do it, which stops anyone front-running the deployment.
+/// Config instructions afterwards check the stored admin instead.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct InitializeGlobalConfigIx;
+
+#[derive(AccountSet)]
+pub struct InitializeGlobalConfigAccounts {
+    #[validate(funder)]
+    pub admin: Signer<Mut<SystemAccount>>,
+    #[validate(arg = (
+        Create(()),
+        Seeds(GlobalConfigSeeds),
+    ))]
+    pub global_config: Init<Seeded<Account<GlobalConfig>>>,
+    // Checked by `ensure_upgrade_authority` in the instruction
+    pub program_data: AccountInfo,
+    pub system_program: Program<System>,
+}
+
+#[star_frame_instruction]
+fn InitializeGlobalConfigIx(a: &mut InitializeGlobalConfigAccounts, _run: ()) -> Result<()> {
+    ensure_upgrade_authority(&a.program_data, a.admin.pubkey())?;
+    **a.global_config.data_mut()? = GlobalConfig {
+        admin: *a.admin.pubkey(),
+        bump: a.global_config.access_seeds().bump,
+        ..Default::default()
+    };
+    Ok(())
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetEmergencyPausedIx {
+    #[ix_args(run)]
+    pub paused: bool,
+}
+
+#[derive(AccountSet)]
+pub struct SetEmergencyPausedAccounts {
+    pub admin: Signer<SystemAccount>,
+    #[validate(arg = self.admin.pubkey())]
+    pub global_config: Mut<ValidatedAccount<GlobalConfig>>,
+}
+
+#[star_frame_instruction]
+fn SetEmergencyPausedIx(a: &mut SetEmergencyPausedAccounts, paused: bool) -> Result<()> {
+    a.global_config.data_mut()?.emergency_paused.set(paused);
+    Ok(())
+}
+
+/// Sets the allowlist of owners who may open vaults, or clears it with all zeros.
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct SetAllowlistRootIx {
+    #[ix_args(run)]
+    pub allowlist_root: [u8; 32],
+}
+
+#[star_frame_instruction]
+fn SetAllowlistRootIx(a: &mut SetEmergencyPausedAccounts, allowlist_root: [u8; 32]) -> Result<()> {
+    a.global_config.data_mut()?.allowlist_root = allowlist_root;
+    Ok(())
+}
+
+/* -------------------- Shared Vault -------------------- */
+
+/// Maximum number of co-owners a [`SharedVaultState`] can hold.
+pub const MAX_SHARED_OWNERS: usize = 4;
+
+/// Basis points that the co-owner shares must add up to.
+pub const TOTAL_SHARE_BPS: u16 = 10_000;
+
+#[derive(Debug, GetSeeds, Clone)]
+#[get_seeds(seed_const = b"SHARED_STATE")]
+pub struct SharedVaultStateSeeds {
+    pub creator: Pubkey,
+}
+
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, TypeToIdl)]
+pub struct ShareEntry {
+    pub owner: Pubkey,
+    pub share_bps: u16,
+    // Lamports this owner has withdrawn so far
+    pub withdrawn: u64,
+}
+
+// The lamports live in a `VaultSeeds` PDA derived from the shared state, like a regular vault
+#[zero_copy(pod)]
+#[derive(Default, Debug, Eq, PartialEq, ProgramAccount)]
+#[program_account(seeds = SharedVaultStateSeeds)]
+pub struct SharedVaultState {
+    pub creator: Pubkey,
+    pub state_bump: u8,
+    pub vault_bump: u8,
+    pub owner_count: u8,
+    pub entries: [ShareEntry; MAX_SHARED_OWNERS],
+}
+
+impl SharedVaultState {
+    /// Index of `owner` among the active entries.
+    pub fn owner_index(&self, owner: &Pubkey) -> Option<usize> {
+        self.entries[..self.owner_count as usize]
+            .iter()
+            .position(|entry| entry.owner == *owner)
+    }
+}
+
+/* Only co-owners pass validation */
+impl AccountValidate<&Pubkey> for SharedVaultState {
+    fn validate_account(self_ref: &Self::Ref<'_>, owner: &Pubkey) -> Result<()> {
+        require!(
+            self_ref.owner_index(owner).is_some(),
+            VaultError::Unauthorized,
+            "Not a co-owner"
+        );
+        Ok(())
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, TypeToIdl)]
+pub struct SharedOwner {
+    pub owner: Pubkey,
+    pub share_bps: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct InitializeSharedIx {
+    #[ix_args(&run)]
+    pub owners: Vec<SharedOwner>,
+}
+
+#[derive(AccountSet)]
+pub struct InitializeSharedAccounts {
+    #[validate(funder)]
+    pub creator: Signer<Mut<SystemAccount>>,
+    #[validate(arg = (
+        Create(()),
+        Seeds(SharedVaultStateSeeds { creator: *self.creator.pubkey() }),
+    ))]
+    pub shared_state: Init<Seeded<Account<SharedVaultState>>>,
+    #[validate(arg = Seeds(VaultSeeds { state: *self.shared_state.pubkey() }))]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    pub system_program: Program<System>,
+}
+
+#[star_frame_instruction]
+fn InitializeSharedIx(
+    a: &mut InitializeSharedAccounts,
+    owners: &Vec<SharedOwner>,
+    ctx: &mut Context,
+) -> Result<()> {
+    require!(
+        !owners.is_empty() && owners.len() <= MAX_SHARED_OWNERS,
+        VaultError::InvalidArgument,
+        "Invalid number of co-owners"
+    );
+    let total_bps = owners.iter().map(|o| u32::from(o.share_bps)).sum::<u32>();
+    require!(
+        total_bps == u32::from(TOTAL_SHARE_BPS),
+        VaultError::InvalidArgument,
+        "Shares must add up to {TOTAL_SHARE_BPS} bps"
+    );
+    for (i, owner) in owners.iter().enumerate() {
+        require!(
+            owners[..i].iter().all(|o| o.owner != owner.owner),
+            VaultError::InvalidArgument,
+            "Duplicate co-owner"
+        );
+    }
+
+    let rent_exempt_lamports = vault_rent_exempt_lamports(ctx)?;
+    transfer_lamports(
+        *a.creator.account_info(),
+        *a.vault.account_info(),
+        rent_exempt_lamports,
+        &[],
+    )?;
+
+    let mut entries = [ShareEntry::default(); MAX_SHARED_OWNERS];
+    for (entry, owner) in entries.iter_mut().zip(owners) {
+        *entry = ShareEntry {
+            owner: owner.owner,
+            share_bps: owner.share_bps,
+            withdrawn: 0,
+        };
+    }
+    **a.shared_state.data_mut()? = SharedVaultState {
+        creator: *a.creator.pubkey(),
+        state_bump: a.shared_state.access_seeds().bump,
+        vault_bump: a.vault.access_seeds().bump,
+        owner_count: owners.len() as u8,
+        entries,
+    };
+
+    Ok(())
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, InstructionArgs)]
+pub struct WithdrawSharedIx {
+    #[ix_args(run)]
+    pub amount: u64,
+}
+
+#[derive(AccountSet)]
+pub struct WithdrawSharedAccounts {
+    pub user: Signer<Mut<SystemAccount>>,
+    #[validate(arg = SeedsWithBump {
+        seeds: VaultSeeds { state: *self.shared_state.pubkey() },
+        bump: self.shared_state.data()?.vault_bump,
+    })]
+    pub vault: Seeded<Mut<SystemAccount>, VaultSeeds>,
+    // Validate that the user is one of the co-owners
+    #[validate(arg = self.user.pubkey())]
+    pub shared_state: Mut<ValidatedAccount<SharedVaultState>>,
+    pub system_program: Program<System>,
+    pub global_config: Account<GlobalConfig>,
+}
+
+#[star_frame_instruction]
+fn WithdrawSharedIx(a: &mut WithdrawSharedAccounts, amount: u64, ctx: &mut Context) -> Result<()> {
+    a.global_config.data()?.ensure_not_paused()?;
+    let minimum_lamports = vault_rent_exempt_lamports(ctx)?;
+    let available_lamports = a.vault.lamports().saturating_sub(minimum_lamports);
+
+    {
+        let mut state = a.shared_state.data_mut()?;
+        let index = state
+            .owner_index(a.user.pubkey())
+            .ok_or_else(|| VaultError::Unauthorized.context("Not a co-owner"))?;
+        // Everything ever paid into the vault: what is left plus what was already taken out
+        let total_withdrawn = state.entries[..state.owner_count as usize]
+            .iter()
+            .map(|entry| u128::from(entry.withdrawn))
+            .sum::<u128>();
+        let pool = u128::from(available_lamports) + total_withdrawn;
+        let entry = &mut state.entries[index];
+        let entitled = pool * u128::from(entry.share_bps) / u128::from(TOTAL_SHARE_BPS);
+        let remaining = entitled.saturating_sub(u128::from(entry.withdrawn));
+        require!(
+            u128::from(amount) <= remaining,
+            VaultError::InsufficientFunds,
+            "Amount exceeds co-owner share"
+        );
+        if amount > available_lamports {
+            return Err(VaultError::InsufficientFunds.into());
+        }
+        entry.withdrawn += amount;
     }
+
+    let signer_seeds = a.vault.access_seeds().seeds_with_bump();
+    transfer_lamports(
+        *a.vault.account_info(),
+        *a.user.account_info(),
+        amount,
+        &[&signer_seeds],
+    )?;
+
     Ok(())
 }
@@ -0,0 +1,197 @@
+use solana_sdk::pubkey::Pubkey;
+use starframe_vault::client::{
+    check_deposit_funds, deposit_to_owner_instruction, ensure_vault, initialize_instruction,
+    lamports_to_sol, read_state_instruction, sol_to_lamports, AccountFetcher, EnsuredVault,
+    FetchedAccount, Sol, LAMPORTS_PER_SOL, SIGNATURE_FEE_LAMPORTS,
+};
+use starframe_vault::{discriminators, PROGRAM_ID};
+
+// Stands in for an RPC node that holds `accounts`
+struct MockRpc {
+    accounts: Vec<(Pubkey, FetchedAccount)>,
+}
+
+impl AccountFetcher for MockRpc {
+    async fn get_account(&self, address: &Pubkey) -> anyhow::Result<Option<FetchedAccount>> {
+        Ok(self
+            .accounts
+            .iter()
+            .find(|(key, _)| key == address)
+            .map(|(_, account)| account.clone()))
+    }
+}
+
+#[test]
+fn test_sol_to_lamports_rounds_to_nearest_lamport() {
+    assert_eq!(sol_to_lamports(1.0), LAMPORTS_PER_SOL);
+    assert_eq!(sol_to_lamports(0.5), 500_000_000);
+    assert_eq!(sol_to_lamports(0.000_000_001), 1);
+
+    // Half a lamport and above rounds up, anything below rounds down
+    assert_eq!(sol_to_lamports(0.000_000_000_5), 1);
+    assert_eq!(sol_to_lamports(0.000_000_000_49), 0);
+    assert_eq!(sol_to_lamports(1.000_000_001_6), 1_000_000_002);
+
+    // Values without an exact binary representation still land on the intended lamport
+    assert_eq!(sol_to_lamports(0.1), 100_000_000);
+    assert_eq!(sol_to_lamports(0.3), 300_000_000);
+}
+
+#[test]
+fn test_sol_to_lamports_saturates() {
+    assert_eq!(sol_to_lamports(-1.0), 0);
+    assert_eq!(sol_to_lamports(f64::NAN), 0);
+    assert_eq!(sol_to_lamports(f64::INFINITY), u64::MAX);
+}
+
+#[test]
+fn test_lamports_to_sol() {
+    assert_eq!(lamports_to_sol(LAMPORTS_PER_SOL), 1.0);
+    assert_eq!(lamports_to_sol(1_500_000_000), 1.5);
+    assert_eq!(lamports_to_sol(0), 0.0);
+
+    // Lamport amounts survive the round trip
+    for lamports in [1, 999_999_999, 1_000_000_001, 123_456_789_012] {
+        assert_eq!(sol_to_lamports(lamports_to_sol(lamports)), lamports);
+    }
+}
+
+#[test]
+fn test_sol_display() {
+    assert_eq!(Sol(0).to_string(), "0 SOL");
+    assert_eq!(Sol(LAMPORTS_PER_SOL).to_string(), "1 SOL");
+    assert_eq!(Sol(1_500_000_000).to_string(), "1.5 SOL");
+    assert_eq!(Sol(1).to_string(), "0.000000001 SOL");
+    assert_eq!(Sol(u64::MAX).to_string(), "18446744073.709551615 SOL");
+}
+
+#[test]
+fn test_check_deposit_funds() {
+    // Without a buffer this matches the program, the whole balance can be deposited
+    assert!(check_deposit_funds(1_000, 1_000, 0).is_ok());
+    assert!(check_deposit_funds(999, 1_000, 0).is_err());
+
+    // With a buffer the fee for a later transaction has to remain
+    let amount = LAMPORTS_PER_SOL;
+    assert!(check_deposit_funds(
+        amount + SIGNATURE_FEE_LAMPORTS,
+        amount,
+        SIGNATURE_FEE_LAMPORTS
+    )
+    .is_ok());
+    assert!(check_deposit_funds(amount, amount, SIGNATURE_FEE_LAMPORTS).is_err());
+    assert!(check_deposit_funds(u64::MAX, u64::MAX, 1).is_err());
+}
+
+#[tokio::test]
+async fn test_ensure_vault_returns_existing_state() {
+    let owner = Pubkey::new_unique();
+    let (state, _) = Pubkey::find_program_address(&[b"STATE", owner.as_ref()], &PROGRAM_ID);
+    let mut data = discriminators::VAULT_STATE.to_vec();
+    data.extend([0; 64]);
+    let rpc = MockRpc {
+        accounts: vec![(
+            state,
+            FetchedAccount {
+                owner: PROGRAM_ID,
+                data,
+            },
+        )],
+    };
+
+    let ensured = ensure_vault(&rpc, &owner).await.unwrap();
+    assert_eq!(ensured, EnsuredVault::Existing(state));
+}
+
+#[tokio::test]
+async fn test_ensure_vault_ignores_prefunded_state_address() {
+    let owner = Pubkey::new_unique();
+    let (state, _) = Pubkey::find_program_address(&[b"STATE", owner.as_ref()], &PROGRAM_ID);
+    // Someone sent lamports to the state address before the vault was opened
+    let rpc = MockRpc {
+        accounts: vec![(state, FetchedAccount::default())],
+    };
+
+    let ensured = ensure_vault(&rpc, &owner).await.unwrap();
+    assert_eq!(
+        ensured,
+        EnsuredVault::Missing {
+            state,
+            initialize: initialize_instruction(&owner).unwrap(),
+        }
+    );
+
+    // Program owned data that is not a vault state does not count either
+    let rpc = MockRpc {
+        accounts: vec![(
+            state,
+            FetchedAccount {
+                owner: PROGRAM_ID,
+                data: vec![0; 72],
+            },
+        )],
+    };
+    let ensured = ensure_vault(&rpc, &owner).await.unwrap();
+    assert!(matches!(ensured, EnsuredVault::Missing { .. }));
+}
+
+#[tokio::test]
+async fn test_ensure_vault_builds_initialize_when_missing() {
+    let owner = Pubkey::new_unique();
+    let (state, _) = Pubkey::find_program_address(&[b"STATE", owner.as_ref()], &PROGRAM_ID);
+    let (vault, _) = Pubkey::find_program_address(&[b"VAULT", state.as_ref()], &PROGRAM_ID);
+    let rpc = MockRpc { accounts: vec![] };
+
+    let ensured = ensure_vault(&rpc, &owner).await.unwrap();
+    assert_eq!(ensured.state(), state);
+    let EnsuredVault::Missing { initialize, .. } = ensured else {
+        panic!("expected a missing vault");
+    };
+    assert_eq!(initialize, initialize_instruction(&owner).unwrap());
+    assert_eq!(initialize.program_id, PROGRAM_ID);
+    let keys: Vec<_> = initialize.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert_eq!(keys[..3], [owner, state, vault]);
+    assert!(initialize.accounts[0].is_signer);
+}
+
+#[test]
+fn test_deposit_to_owner_instruction_targets_the_owners_vault() {
+    let funder = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let (state, _) = Pubkey::find_program_address(&[b"STATE", owner.as_ref()], &PROGRAM_ID);
+    let (vault, _) = Pubkey::find_program_address(&[b"VAULT", state.as_ref()], &PROGRAM_ID);
+
+    let instruction = deposit_to_owner_instruction(&funder, &owner, 42).unwrap();
+    assert_eq!(instruction.program_id, PROGRAM_ID);
+    let keys: Vec<_> = instruction
+        .accounts
+        .iter()
+        .map(|meta| meta.pubkey)
+        .collect();
+    assert_eq!(keys[..3], [funder, vault, state]);
+    assert!(instruction.accounts[0].is_signer && instruction.accounts[0].is_writable);
+    assert!(instruction.accounts[1].is_writable);
+    // Discriminant, then the owner and amount
+    assert_eq!(instruction.data[8..40], owner.to_bytes());
+    assert_eq!(instruction.data[40..], 42u64.to_le_bytes());
+}
+
+#[test]
+fn test_read_state_instruction_is_read_only() {
+    let owner = Pubkey::new_unique();
+    let (state, _) = Pubkey::find_program_address(&[b"STATE", owner.as_ref()], &PROGRAM_ID);
+    let (vault, _) = Pubkey::find_program_address(&[b"VAULT", state.as_ref()], &PROGRAM_ID);
+
+    // Simulated without signatures, so nothing may need to sign or be written
+    let instruction = read_state_instruction(&owner).unwrap();
+    let keys: Vec<_> = instruction
+        .accounts
+        .iter()
+        .map(|meta| meta.pubkey)
+        .collect();
+    assert_eq!(keys, [vault, state]);
+    assert!(instruction
+        .accounts
+        .iter()
+        .all(|meta| !meta.is_signer && !meta.is_writable));
+}
@@ -1,25 +1,190 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use mollusk_svm::{result::Check, Mollusk};
+use mollusk_svm_programs_token::token;
+use solana_log_collector::LogCollector;
+use solana_instruction::{BorrowedAccountMeta, BorrowedInstruction};
+use solana_instructions_sysvar::{construct_instructions_data, store_current_index_checked};
 use solana_sdk::{
     account::Account,
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     pubkey::Pubkey,
+    signature::{Keypair, Signer},
 };
 use solana_system_program as system_program;
+use solana_vote_interface::state::{VoteInit, VoteState, VoteStateVersions};
+use std::{cell::RefCell, rc::Rc};
+use starframe_vault::{
+    client::{
+        bind_recipient_instruction, cancel_withdraw_instruction, clear_metadata_instruction, close_instruction,
+        configure_instruction, decode_read_state_return_data, deposit_to_owner_instruction, execute_withdraw_instruction,
+        close_many_cu, initialize_instruction, read_state_instruction, recommended_cu, request_withdraw_instruction,
+        safe_close_instruction, set_admin_instruction, set_auto_forward_instruction, set_burn_dust_instruction,
+        set_close_authority_instruction, set_deposit_limits_instruction, set_first_withdraw_delay_instruction,
+        set_guardians_instruction, set_keeper_sweep_instruction, set_label_instruction, set_log_level_instruction,
+        set_max_deposit_per_tx_instruction, set_max_slot_age_instruction, set_metadata_instruction,
+        set_rent_buffer_instruction, set_round_to_instruction, set_sandwich_guard_instruction,
+        set_withdraw_cooldown_instruction, set_withdraw_policy_instruction, set_yield_program_instruction,
+        with_compute_unit_limit, withdraw_instruction, withdraw_percent_instruction, withdraw_to_program_instruction,
+        withdraw_token_batch_instruction, withdraw_with_approval_instruction, COMPUTE_BUDGET_PROGRAM_ID,
+    },
+    allowlist_leaf, allowlist_node, discriminators, verify_allowlist_proof, AssertBalanceIx, BindRecipientIx,
+    CancelWithdrawIx, ClearMetadataIx, CloseIx, CloseManyIx, ConfigureIx, DeactivateIx, DelegateIx, DepositIx,
+    DepositToOwnerIx, DerivedVault, DepositRawIx, DeriveVaultIx, ExecuteWithdrawIx, GuardianRecoverIx,
+    InitializeAndDepositIx, InitializeGlobalConfigIx, InitializeIx, InitializeSharedIx, InitializeStakeVaultIx,
+    KeeperSweepIx, LogLevel, NormalizeBumpIx, ReadStateIx, RecoverStateLamportsIx, RequestWithdrawIx, ResizeStateIx,
+    SafeCloseIx, SetAdminIx, SetAllowlistRootIx, SetAutoForwardIx, SetBurnDustIx, SetCloseAuthorityIx,
+    SetDepositLimitsIx, SetEmergencyPausedIx, SetFirstWithdrawDelayIx, SetGuardiansIx, SetKeeperSweepIx, SetLabelIx,
+    SetLogLevelIx, SetMaxDepositPerTxIx, SetMaxSlotAgeIx, SetMetadataIx, SetRentBufferIx, SetRoundToIx,
+    SetSandwichGuardIx, SetWithdrawCooldownIx, SetWithdrawPolicyIx, SetYieldProgramIx, TipIx, VaultIxSet,
+    VaultMetadata, VaultState, WithdrawIx, WithdrawPercentIx, WithdrawPolicy, WithdrawSharedIx, WithdrawStakeIx,
+    WithdrawToProgramIx, WithdrawTokenBatchIx, WithdrawWithApprovalIx, WrapDepositIx, NATIVE_MINT, PROGRAM_ID,
+    SOL_CURRENCY,
+};
+
+// Instruction discriminators, exported by the crate so they can't drift from the program
+const INITIALIZE_DISCRIMINATOR: [u8; 8] = discriminators::INITIALIZE;
+const INITIALIZE_AND_DEPOSIT_DISCRIMINATOR: [u8; 8] = discriminators::INITIALIZE_AND_DEPOSIT;
+const DEPOSIT_DISCRIMINATOR: [u8; 8] = discriminators::DEPOSIT;
+const DEPOSIT_RAW_DISCRIMINATOR: [u8; 8] = discriminators::DEPOSIT_RAW;
+const TIP_DISCRIMINATOR: [u8; 8] = discriminators::TIP;
+const DEPOSIT_TO_OWNER_DISCRIMINATOR: [u8; 8] = discriminators::DEPOSIT_TO_OWNER;
+const WRAP_DEPOSIT_DISCRIMINATOR: [u8; 8] = discriminators::WRAP_DEPOSIT;
+const WITHDRAW_TOKEN_BATCH_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW_TOKEN_BATCH;
+const WITHDRAW_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW;
+const WITHDRAW_PERCENT_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW_PERCENT;
+const WITHDRAW_TO_PROGRAM_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW_TO_PROGRAM;
+const WITHDRAW_WITH_APPROVAL_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW_WITH_APPROVAL;
+const CLOSE_DISCRIMINATOR: [u8; 8] = discriminators::CLOSE;
+const CLOSE_MANY_DISCRIMINATOR: [u8; 8] = discriminators::CLOSE_MANY;
+const SAFE_CLOSE_DISCRIMINATOR: [u8; 8] = discriminators::SAFE_CLOSE;
+const SET_YIELD_PROGRAM_DISCRIMINATOR: [u8; 8] = discriminators::SET_YIELD_PROGRAM;
+const SET_WITHDRAW_COOLDOWN_DISCRIMINATOR: [u8; 8] = discriminators::SET_WITHDRAW_COOLDOWN;
+const SET_BURN_DUST_DISCRIMINATOR: [u8; 8] = discriminators::SET_BURN_DUST;
+const SET_ROUND_TO_DISCRIMINATOR: [u8; 8] = discriminators::SET_ROUND_TO;
+const SET_MAX_DEPOSIT_PER_TX_DISCRIMINATOR: [u8; 8] = discriminators::SET_MAX_DEPOSIT_PER_TX;
+const SET_DEPOSIT_LIMITS_DISCRIMINATOR: [u8; 8] = discriminators::SET_DEPOSIT_LIMITS;
+const SET_RENT_BUFFER_DISCRIMINATOR: [u8; 8] = discriminators::SET_RENT_BUFFER;
+const SET_FIRST_WITHDRAW_DELAY_DISCRIMINATOR: [u8; 8] = discriminators::SET_FIRST_WITHDRAW_DELAY;
+const SET_LABEL_DISCRIMINATOR: [u8; 8] = discriminators::SET_LABEL;
+const SET_METADATA_DISCRIMINATOR: [u8; 8] = discriminators::SET_METADATA;
+const CLEAR_METADATA_DISCRIMINATOR: [u8; 8] = discriminators::CLEAR_METADATA;
+const SET_KEEPER_SWEEP_DISCRIMINATOR: [u8; 8] = discriminators::SET_KEEPER_SWEEP;
+const KEEPER_SWEEP_DISCRIMINATOR: [u8; 8] = discriminators::KEEPER_SWEEP;
+const REQUEST_WITHDRAW_DISCRIMINATOR: [u8; 8] = discriminators::REQUEST_WITHDRAW;
+const EXECUTE_WITHDRAW_DISCRIMINATOR: [u8; 8] = discriminators::EXECUTE_WITHDRAW;
+const CANCEL_WITHDRAW_DISCRIMINATOR: [u8; 8] = discriminators::CANCEL_WITHDRAW;
+const SET_SANDWICH_GUARD_DISCRIMINATOR: [u8; 8] = discriminators::SET_SANDWICH_GUARD;
+const SET_MAX_SLOT_AGE_DISCRIMINATOR: [u8; 8] = discriminators::SET_MAX_SLOT_AGE;
+const SET_LOG_LEVEL_DISCRIMINATOR: [u8; 8] = discriminators::SET_LOG_LEVEL;
+const SET_ADMIN_DISCRIMINATOR: [u8; 8] = discriminators::SET_ADMIN;
+const SET_CLOSE_AUTHORITY_DISCRIMINATOR: [u8; 8] = discriminators::SET_CLOSE_AUTHORITY;
+const SET_WITHDRAW_POLICY_DISCRIMINATOR: [u8; 8] = discriminators::SET_WITHDRAW_POLICY;
+const SET_AUTO_FORWARD_DISCRIMINATOR: [u8; 8] = discriminators::SET_AUTO_FORWARD;
+const SET_GUARDIANS_DISCRIMINATOR: [u8; 8] = discriminators::SET_GUARDIANS;
+const BIND_RECIPIENT_DISCRIMINATOR: [u8; 8] = discriminators::BIND_RECIPIENT;
+const GUARDIAN_RECOVER_DISCRIMINATOR: [u8; 8] = discriminators::GUARDIAN_RECOVER;
+const READ_STATE_DISCRIMINATOR: [u8; 8] = discriminators::READ_STATE;
+const DERIVE_VAULT_DISCRIMINATOR: [u8; 8] = discriminators::DERIVE_VAULT;
+const CONFIGURE_DISCRIMINATOR: [u8; 8] = discriminators::CONFIGURE;
+const HARVEST_DISCRIMINATOR: [u8; 8] = discriminators::HARVEST;
+const INITIALIZE_STAKE_VAULT_DISCRIMINATOR: [u8; 8] = discriminators::INITIALIZE_STAKE_VAULT;
+const DELEGATE_DISCRIMINATOR: [u8; 8] = discriminators::DELEGATE;
+const DEACTIVATE_DISCRIMINATOR: [u8; 8] = discriminators::DEACTIVATE;
+const WITHDRAW_STAKE_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW_STAKE;
+const RESIZE_STATE_DISCRIMINATOR: [u8; 8] = discriminators::RESIZE_STATE;
+const RECOVER_STATE_LAMPORTS_DISCRIMINATOR: [u8; 8] = discriminators::RECOVER_STATE_LAMPORTS;
+const NORMALIZE_BUMP_DISCRIMINATOR: [u8; 8] = discriminators::NORMALIZE_BUMP;
+const ASSERT_BALANCE_DISCRIMINATOR: [u8; 8] = discriminators::ASSERT_BALANCE;
+const INITIALIZE_GLOBAL_CONFIG_DISCRIMINATOR: [u8; 8] = discriminators::INITIALIZE_GLOBAL_CONFIG;
+const SET_EMERGENCY_PAUSED_DISCRIMINATOR: [u8; 8] = discriminators::SET_EMERGENCY_PAUSED;
+const SET_ALLOWLIST_ROOT_DISCRIMINATOR: [u8; 8] = discriminators::SET_ALLOWLIST_ROOT;
+const INITIALIZE_SHARED_DISCRIMINATOR: [u8; 8] = discriminators::INITIALIZE_SHARED;
+const WITHDRAW_SHARED_DISCRIMINATOR: [u8; 8] = discriminators::WITHDRAW_SHARED;
+
+// Account discriminators
+const VAULT_STATE_DISCRIMINATOR: [u8; 8] = discriminators::VAULT_STATE;
+const GLOBAL_CONFIG_DISCRIMINATOR: [u8; 8] = discriminators::GLOBAL_CONFIG;
+const VAULT_METADATA_DISCRIMINATOR: [u8; 8] = discriminators::VAULT_METADATA;
+
+// Admin of the global config account used across tests
+const GLOBAL_CONFIG_ADMIN: Pubkey = solana_sdk::pubkey!("Admin11111111111111111111111111111111111111");
 
-const PROGRAM_ID: Pubkey = solana_sdk::pubkey!("GxpAtbXpkbDu5b86TidcmuF5RF9UJm821rqJ5W3S4T12");
+// VaultState field offsets (after the discriminator)
+const YIELD_PROGRAM_OFFSET: usize = 8 + 32 + 1 + 1;
+const TOTAL_TIPS_OFFSET: usize = YIELD_PROGRAM_OFFSET + 32;
+const WITHDRAW_NONCE_OFFSET: usize = TOTAL_TIPS_OFFSET + 8;
+const COOLDOWN_SLOTS_OFFSET: usize = WITHDRAW_NONCE_OFFSET + 8;
+const LAST_WITHDRAW_SLOT_OFFSET: usize = COOLDOWN_SLOTS_OFFSET + 8;
+const BURN_DUST_OFFSET: usize = LAST_WITHDRAW_SLOT_OFFSET + 8;
+const MAX_DEPOSIT_PER_TX_OFFSET: usize = BURN_DUST_OFFSET + 1;
+const BALANCE_CAP_OFFSET: usize = MAX_DEPOSIT_PER_TX_OFFSET + 8;
+const MIN_DEPOSIT_OFFSET: usize = BALANCE_CAP_OFFSET + 8;
+const RENT_BUFFER_BPS_OFFSET: usize = MIN_DEPOSIT_OFFSET + 8;
+const INIT_SLOT_OFFSET: usize = RENT_BUFFER_BPS_OFFSET + 2;
+const FIRST_WITHDRAW_DELAY_OFFSET: usize = INIT_SLOT_OFFSET + 8;
+const LABEL_OFFSET: usize = FIRST_WITHDRAW_DELAY_OFFSET + 8;
+const SWEEP_THRESHOLD_OFFSET: usize = LABEL_OFFSET + 32;
+const KEEPER_TIP_OFFSET: usize = SWEEP_THRESHOLD_OFFSET + 8;
+const PENDING_RECIPIENT_OFFSET: usize = KEEPER_TIP_OFFSET + 8;
+const PENDING_AMOUNT_OFFSET: usize = PENDING_RECIPIENT_OFFSET + 32;
+const PENDING_RELEASE_SLOT_OFFSET: usize = PENDING_AMOUNT_OFFSET + 8;
+const SANDWICH_GUARD_OFFSET: usize = PENDING_RELEASE_SLOT_OFFSET + 8;
+const CURRENCY_OFFSET: usize = SANDWICH_GUARD_OFFSET + 1;
+const GUARDIANS_OFFSET: usize = CURRENCY_OFFSET + 8;
+const GUARDIAN_THRESHOLD_OFFSET: usize = GUARDIANS_OFFSET + 3 * 32;
+const BOUND_RECIPIENT_OFFSET: usize = GUARDIAN_THRESHOLD_OFFSET + 1;
+const ADMIN_OFFSET: usize = BOUND_RECIPIENT_OFFSET + 32;
+const WITHDRAW_POLICY_OFFSET: usize = ADMIN_OFFSET + 32;
+const AUTO_FORWARD_OFFSET: usize = WITHDRAW_POLICY_OFFSET + 1;
+const ROUND_TO_OFFSET: usize = AUTO_FORWARD_OFFSET + 32;
+const MAX_SLOT_AGE_OFFSET: usize = ROUND_TO_OFFSET + 8;
+const CLOSE_AUTHORITY_OFFSET: usize = MAX_SLOT_AGE_OFFSET + 8;
+const LOG_LEVEL_OFFSET: usize = CLOSE_AUTHORITY_OFFSET + 32;
+const HAS_METADATA_OFFSET: usize = LOG_LEVEL_OFFSET + 1;
+const TOKEN_ACCOUNTS_OFFSET: usize = HAS_METADATA_OFFSET + 1;
+const HAS_STAKE_ACCOUNT_OFFSET: usize = TOKEN_ACCOUNTS_OFFSET + 4 * 32;
 
-// Instruction discriminators from IDL
-const INITIALIZE_DISCRIMINATOR: [u8; 8] = [0xaf, 0xaf, 0x6d, 0x1f, 0x0d, 0x98, 0x9b, 0xed];
-const DEPOSIT_DISCRIMINATOR: [u8; 8] = [0xf2, 0x23, 0xc6, 0x89, 0x52, 0xe1, 0xf2, 0xb6];
-const WITHDRAW_DISCRIMINATOR: [u8; 8] = [0xb7, 0x12, 0x46, 0x9c, 0x94, 0x6d, 0xa1, 0x22];
-const CLOSE_DISCRIMINATOR: [u8; 8] = [0x62, 0xa5, 0xc9, 0xb1, 0x6c, 0x41, 0xce, 0x60];
+// VaultMetadata layout: discriminator, state, len, then the zero padded data
+const VAULT_METADATA_LEN: usize = 8 + 32 + 2 + 256;
 
-// VaultState account discriminator
-const VAULT_STATE_DISCRIMINATOR: [u8; 8] = [0xe4, 0xc4, 0x52, 0xa5, 0x62, 0xd2, 0xeb, 0x98];
+// Size of the original VaultState layout: discriminator, owner, state_bump, vault_bump
+const VAULT_STATE_V0_LEN: usize = 8 + 32 + 1 + 1;
+
+// Event discriminators, sha256("event:<Name>")[..8]
+const VAULT_OPENED_EVENT_DISCRIMINATOR: [u8; 8] = [0x0c, 0xa8, 0xce, 0xba, 0x32, 0x3f, 0xdb, 0xe5];
+const VAULT_CLOSED_EVENT_DISCRIMINATOR: [u8; 8] = [0x68, 0x47, 0xd5, 0xf7, 0xc3, 0x85, 0x10, 0x6a];
+const TIP_EVENT_DISCRIMINATOR: [u8; 8] = [0xd5, 0x24, 0xbf, 0x32, 0x1c, 0x19, 0xbd, 0xfc];
+
+// VaultError codes
+const INSUFFICIENT_FUNDS_ERROR: u32 = 6000;
+const DEPOSIT_CAP_EXCEEDED_ERROR: u32 = 6002;
+const BALANCE_BELOW_EXPECTED_ERROR: u32 = 6003;
+const VAULT_NOT_EMPTY_ERROR: u32 = 6004;
+const EMERGENCY_PAUSED_ERROR: u32 = 6005;
+const BALANCE_CAP_EXCEEDED_ERROR: u32 = 6006;
+const DEPOSIT_BELOW_MINIMUM_ERROR: u32 = 6007;
+const NOT_ALLOWLISTED_ERROR: u32 = 6008;
+const BALANCE_OVERFLOW_ERROR: u32 = 6010;
+const UNAUTHORIZED_ERROR: u32 = 6011;
+const INVALID_ARGUMENT_ERROR: u32 = 6013;
+const WITHDRAWAL_LOCKED_ERROR: u32 = 6014;
+const RECIPIENT_NOT_ALLOWED_ERROR: u32 = 6015;
+const WITHDRAWAL_PENDING_ERROR: u32 = 6016;
+const INVALID_APPROVAL_ERROR: u32 = 6018;
+const WITHDRAWAL_AFTER_DEPOSIT_ERROR: u32 = 6019;
+const INVALID_STATE_ERROR: u32 = 6020;
 
 // PDA Seeds
 const STATE_SEED: &[u8] = b"STATE";
 const VAULT_SEED: &[u8] = b"VAULT";
+const SHARED_STATE_SEED: &[u8] = b"SHARED_STATE";
+const METADATA_SEED: &[u8] = b"METADATA";
+const GLOBAL_CONFIG_SEED: &[u8] = b"CONFIG";
+
+// SPL token account layout
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+const TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
 
 fn create_mollusk() -> Mollusk {
     let mut mollusk = Mollusk::default();
@@ -27,6 +192,25 @@ fn create_mollusk() -> Mollusk {
     mollusk
 }
 
+fn create_logging_mollusk() -> (Mollusk, Rc<RefCell<LogCollector>>) {
+    let mut mollusk = create_mollusk();
+    let logger = LogCollector::new_ref();
+    mollusk.logger = Some(logger.clone());
+    (mollusk, logger)
+}
+
+// Payload of the first `Program data:` log whose leading field is the event discriminator
+fn find_event_data(logger: &Rc<RefCell<LogCollector>>, discriminator: &[u8; 8]) -> Option<Vec<u8>> {
+    logger.borrow().get_recorded_content().iter().find_map(|line| {
+        let fields = line
+            .strip_prefix("Program data: ")?
+            .split(' ')
+            .map(|field| STANDARD.decode(field).ok())
+            .collect::<Option<Vec<_>>>()?;
+        (fields.first()? == discriminator).then(|| fields[1..].concat())
+    })
+}
+
 fn find_vault_state_pda(owner: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[STATE_SEED, owner.as_ref()], &PROGRAM_ID)
 }
@@ -35,28 +219,208 @@ fn find_vault_pda(state: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[VAULT_SEED, state.as_ref()], &PROGRAM_ID)
 }
 
+fn find_vault_metadata_pda(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[METADATA_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+fn find_shared_state_pda(creator: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SHARED_STATE_SEED, creator.as_ref()], &PROGRAM_ID)
+}
+
+fn find_global_config_pda() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_CONFIG_SEED], &PROGRAM_ID)
+}
+
+fn create_global_config_data(admin: &Pubkey, bump: u8, emergency_paused: bool) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&GLOBAL_CONFIG_DISCRIMINATOR);
+    data.extend_from_slice(admin.as_ref());
+    data.push(bump);
+    data.push(emergency_paused as u8);
+    data.extend_from_slice(&[0; 32]); // allowlist_root
+    data
+}
+
+// The global config every deposit and withdrawal reads, administered by `GLOBAL_CONFIG_ADMIN`
+fn keyed_account_for_global_config(mollusk: &Mollusk, emergency_paused: bool) -> (Pubkey, Account) {
+    let (global_config_pda, bump) = find_global_config_pda();
+    let data = create_global_config_data(&GLOBAL_CONFIG_ADMIN, bump, emergency_paused);
+    let account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(data.len()),
+        data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    (global_config_pda, account)
+}
+
+// This program's `ProgramData` account as the upgradeable loader stores it, `None` when immutable
+fn keyed_account_for_program_data(mollusk: &Mollusk, upgrade_authority: Option<&Pubkey>) -> (Pubkey, Account) {
+    let loader = solana_sdk::bpf_loader_upgradeable::id();
+    let (program_data, _) = Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &loader);
+    let mut data = Vec::new();
+    data.extend_from_slice(&3u32.to_le_bytes()); // ProgramData variant
+    data.extend_from_slice(&0u64.to_le_bytes()); // slot
+    match upgrade_authority {
+        Some(authority) => {
+            data.push(1);
+            data.extend_from_slice(authority.as_ref());
+        }
+        None => data.extend_from_slice(&[0; 33]),
+    }
+    let account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(data.len()),
+        data,
+        owner: loader,
+        executable: false,
+        rent_epoch: 0,
+    };
+    (program_data, account)
+}
+
 fn create_vault_state_data(owner: &Pubkey, state_bump: u8, vault_bump: u8) -> Vec<u8> {
     let mut data = Vec::new();
     data.extend_from_slice(&VAULT_STATE_DISCRIMINATOR);
     data.extend_from_slice(owner.as_ref());
     data.push(state_bump);
     data.push(vault_bump);
+    data.extend_from_slice(Pubkey::default().as_ref()); // yield_program
+    data.extend_from_slice(&0u64.to_le_bytes()); // total_tips
+    data.extend_from_slice(&0u64.to_le_bytes()); // withdraw_nonce
+    data.extend_from_slice(&0u64.to_le_bytes()); // cooldown_slots
+    data.extend_from_slice(&0u64.to_le_bytes()); // last_withdraw_slot
+    data.push(0); // burn_dust
+    data.extend_from_slice(&0u64.to_le_bytes()); // max_deposit_per_tx
+    data.extend_from_slice(&0u64.to_le_bytes()); // balance_cap
+    data.extend_from_slice(&0u64.to_le_bytes()); // min_deposit
+    data.extend_from_slice(&0u16.to_le_bytes()); // rent_buffer_bps
+    data.extend_from_slice(&0u64.to_le_bytes()); // init_slot
+    data.extend_from_slice(&0u64.to_le_bytes()); // first_withdraw_delay
+    data.extend_from_slice(&[0; 32]); // label
+    data.extend_from_slice(&0u64.to_le_bytes()); // sweep_threshold
+    data.extend_from_slice(&0u64.to_le_bytes()); // keeper_tip
+    data.extend_from_slice(Pubkey::default().as_ref()); // pending_recipient
+    data.extend_from_slice(&0u64.to_le_bytes()); // pending_amount
+    data.extend_from_slice(&0u64.to_le_bytes()); // pending_release_slot
+    data.push(0); // sandwich_guard
+    data.extend_from_slice(&SOL_CURRENCY); // currency
+    data.extend_from_slice(&[0; 3 * 32]); // guardians
+    data.push(0); // guardian_threshold
+    data.extend_from_slice(&[0; 32]); // bound_recipient
+    data.extend_from_slice(&[0; 32]); // admin
+    data.push(0); // withdraw_policy
+    data.extend_from_slice(Pubkey::default().as_ref()); // auto_forward
+    data.extend_from_slice(&0u64.to_le_bytes()); // round_to
+    data.extend_from_slice(&0u64.to_le_bytes()); // max_slot_age
+    data.extend_from_slice(Pubkey::default().as_ref()); // close_authority
+    data.push(0); // log_level
+    data.push(0); // has_metadata
+    data.extend_from_slice(&[0; 4 * 32]); // token_accounts
+    data.push(0); // has_stake_account
     data
 }
 
+// Owner balance `setup_initialized_vault` starts from
+const SETUP_OWNER_BALANCE: u64 = 10_000_000_000;
+
+// A vault its owner just opened: the state as `InitializeIx` writes it (`test_initialize_vault`
+// checks they match), the vault holding only its rent, and the system program and global config
+// most instructions read. Returns the owner, state and vault addresses and the accounts, in the
+// order owner, vault, state, system program, global config.
+fn setup_initialized_vault(mollusk: &Mollusk) -> (Pubkey, Pubkey, Pubkey, Vec<(Pubkey, Account)>) {
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let accounts = vec![
+        (owner, Account::new(SETUP_OWNER_BALANCE, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+        mollusk_svm::program::keyed_account_for_system_program(),
+        keyed_account_for_global_config(mollusk, false),
+    ];
+    (owner, state_pda, vault_pda, accounts)
+}
+
+// Sets the balance of `address` in `accounts`, e.g. to fund a vault from `setup_initialized_vault`
+fn set_lamports(accounts: &mut [(Pubkey, Account)], address: &Pubkey, lamports: u64) {
+    let (_, account) = accounts.iter_mut().find(|(key, _)| key == address).expect("account to fund");
+    account.lamports = lamports;
+}
+
 fn create_initialize_instruction(
     owner: &Pubkey,
     state: &Pubkey,
     vault: &Pubkey,
 ) -> Instruction {
+    create_initialize_instruction_with_proof(owner, state, vault, &[])
+}
+
+fn create_initialize_instruction_with_proof(
+    owner: &Pubkey,
+    state: &Pubkey,
+    vault: &Pubkey,
+    proof: &[[u8; 32]],
+) -> Instruction {
+    create_initialize_instruction_with_currency(owner, state, vault, proof, &SOL_CURRENCY)
+}
+
+fn create_initialize_instruction_with_currency(
+    owner: &Pubkey,
+    state: &Pubkey,
+    vault: &Pubkey,
+    proof: &[[u8; 32]],
+    currency: &[u8; 8],
+) -> Instruction {
+    let mut instruction_data = INITIALIZE_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+    instruction_data.extend_from_slice(proof.concat().as_slice());
+    instruction_data.extend_from_slice(currency);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(*state, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_initialize_and_deposit_instruction(
+    owner: &Pubkey,
+    state: &Pubkey,
+    vault: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction_data = INITIALIZE_AND_DEPOSIT_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+    instruction_data.extend_from_slice(&0u32.to_le_bytes()); // empty allowlist proof
+    instruction_data.extend_from_slice(&SOL_CURRENCY);
+
     Instruction::new_with_bytes(
         PROGRAM_ID,
-        &INITIALIZE_DISCRIMINATOR,
+        &instruction_data,
         vec![
             AccountMeta::new(*owner, true),
             AccountMeta::new(*state, false),
             AccountMeta::new(*vault, false),
             AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
         ],
     )
 }
@@ -76,8 +440,176 @@ fn create_deposit_instruction(
         vec![
             AccountMeta::new(*user, true),
             AccountMeta::new(*vault, false),
-            AccountMeta::new(*vault_state, false), // Made writable for ValidatedAccount
+            AccountMeta::new_readonly(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+// Passes the vault's `auto_forward` address, which is then paid the deposit
+fn create_forwarded_deposit_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    forward_to: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction = create_deposit_instruction(user, vault, vault_state, amount);
+    instruction.accounts.push(AccountMeta::new(*forward_to, false));
+    instruction
+}
+
+fn create_wrap_deposit_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    wsol_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction_data = WRAP_DEPOSIT_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(*wsol_account, false),
+            AccountMeta::new_readonly(token::ID, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+// Initialized native mint token account of `token_owner` with no tokens synced yet
+fn create_wsol_account(mollusk: &Mollusk, token_owner: &Pubkey) -> Account {
+    let rent_exempt_reserve = mollusk.sysvars.rent.minimum_balance(TOKEN_ACCOUNT_LEN);
+    let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+    data[..32].copy_from_slice(NATIVE_MINT.as_ref());
+    data[32..64].copy_from_slice(token_owner.as_ref());
+    // Initialized
+    data[TOKEN_ACCOUNT_STATE_OFFSET] = 1;
+    // `is_native: COption::Some(rent_exempt_reserve)`
+    data[TOKEN_ACCOUNT_STATE_OFFSET + 1] = 1;
+    data[TOKEN_ACCOUNT_STATE_OFFSET + 5..TOKEN_ACCOUNT_STATE_OFFSET + 13].copy_from_slice(&rent_exempt_reserve.to_le_bytes());
+    Account {
+        lamports: rent_exempt_reserve,
+        data,
+        owner: token::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+// Initialized token account of `token_owner` holding `amount` of `mint`
+fn create_token_account(mollusk: &Mollusk, mint: &Pubkey, token_owner: &Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+    data[..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(token_owner.as_ref());
+    data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+    // Initialized
+    data[TOKEN_ACCOUNT_STATE_OFFSET] = 1;
+    Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(TOKEN_ACCOUNT_LEN),
+        data,
+        owner: token::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn create_withdraw_token_batch_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    source: &Pubkey,
+    recipients: &[Pubkey],
+    amounts: &[u64],
+) -> Instruction {
+    let mut instruction_data = WITHDRAW_TOKEN_BATCH_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&(amounts.len() as u32).to_le_bytes());
+    for amount in amounts {
+        instruction_data.extend_from_slice(&amount.to_le_bytes());
+    }
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new_readonly(*vault, false),
+        AccountMeta::new(*vault_state, false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new_readonly(token::ID, false),
+        AccountMeta::new_readonly(find_global_config_pda().0, false),
+    ];
+    accounts.extend(recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)));
+    Instruction::new_with_bytes(PROGRAM_ID, &instruction_data, accounts)
+}
+
+fn create_deposit_raw_instruction(
+    funder: &Pubkey,
+    vault: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction_data = DEPOSIT_RAW_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_tip_instruction(
+    funder: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction_data = TIP_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_deposit_to_owner_instruction(
+    funder: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction_data = DEPOSIT_TO_OWNER_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(owner.as_ref());
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(*vault_state, false),
             AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
         ],
     )
 }
@@ -99,75 +631,5917 @@ fn create_withdraw_instruction(
             AccountMeta::new(*vault, false),
             AccountMeta::new(*vault_state, false), // Made writable for ValidatedAccount
             AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
         ],
     )
 }
 
-fn create_close_instruction(
+// Passes the instructions sysvar, which the sandwich guard reads
+fn create_guarded_withdraw_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction = create_withdraw_instruction(user, vault, vault_state, amount);
+    instruction.accounts.push(AccountMeta::new_readonly(solana_sdk::sysvar::instructions::id(), false));
+    instruction
+}
+
+fn create_withdraw_to_program_instruction(
     user: &Pubkey,
     vault: &Pubkey,
     vault_state: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
 ) -> Instruction {
+    let mut instruction_data = WITHDRAW_TO_PROGRAM_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
     Instruction::new_with_bytes(
         PROGRAM_ID,
-        &CLOSE_DISCRIMINATOR,
+        &instruction_data,
         vec![
-            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(*user, true),
             AccountMeta::new(*vault, false),
             AccountMeta::new(*vault_state, false),
+            AccountMeta::new(*destination, false),
             AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
         ],
     )
 }
 
-#[test]
-fn test_initialize_vault() {
-    let mollusk = create_mollusk();
-    
-    let owner = Pubkey::new_unique();
-    let (state_pda, state_bump) = find_vault_state_pda(&owner);
-    let (vault_pda, _) = find_vault_pda(&state_pda);
+// Passes `recipient` for a vault with a bound recipient, skipping the optional instructions sysvar
+fn create_bound_withdraw_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction = create_withdraw_instruction(user, vault, vault_state, amount);
+    instruction.accounts.push(AccountMeta::new_readonly(PROGRAM_ID, false));
+    instruction.accounts.push(AccountMeta::new(*recipient, false));
+    instruction
+}
 
-    let owner_account = Account::new(10_000_000_000, 0, &system_program::id());
-    let state_account = Account::default();
-    let vault_account = Account::default();
+fn create_bind_recipient_instruction(user: &Pubkey, vault_state: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let mut instruction_data = BIND_RECIPIENT_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(recipient.as_ref());
 
-    let instruction = create_initialize_instruction(&owner, &state_pda, &vault_pda);
-    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
-    
-    let accounts = vec![
-        (owner, owner_account),
-        (state_pda, state_account),
-        (vault_pda, vault_account),
-        (system_program_key, system_program_account),
-    ];
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
 
-    mollusk.process_and_validate_instruction(
-        &instruction,
-        &accounts,
-        &[
-            Check::success(),
-            Check::account(&state_pda).data(&create_vault_state_data(&owner, state_bump, find_vault_pda(&state_pda).1)).build(),
-            Check::account(&vault_pda).lamports(mollusk.sysvars.rent.minimum_balance(0)).build(),
+fn create_withdraw_percent_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    bps: u16,
+) -> Instruction {
+    let mut instruction_data = WITHDRAW_PERCENT_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&bps.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
         ],
-    );
+    )
 }
 
-#[test]
-fn test_deposit_to_vault() {
+fn create_withdraw_with_approval_instruction(
+    recipient: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    amount: u64,
+    nonce: u64,
+    expiry: i64,
+    valid_until_slot: u64,
+) -> Instruction {
+    let mut instruction_data = WITHDRAW_WITH_APPROVAL_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+    instruction_data.extend_from_slice(&nonce.to_le_bytes());
+    instruction_data.extend_from_slice(&expiry.to_le_bytes());
+    instruction_data.extend_from_slice(&valid_until_slot.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(solana_instructions_sysvar::ID, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+// The owner signs the raw WithdrawApproval layout: state, recipient, amount, nonce, expiry,
+// valid_until_slot
+fn create_approval_signature_instruction(
+    owner: &Keypair,
+    vault_state: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+    nonce: u64,
+    expiry: i64,
+    valid_until_slot: u64,
+) -> Instruction {
+    let mut message = Vec::new();
+    message.extend_from_slice(vault_state.as_ref());
+    message.extend_from_slice(recipient.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message.extend_from_slice(&valid_until_slot.to_le_bytes());
+
+    let signature = owner.sign_message(&message);
+    solana_ed25519_program::new_ed25519_instruction_with_signature(
+        &message,
+        signature.as_array(),
+        &owner.pubkey().to_bytes(),
+    )
+}
+
+// Mollusk doesn't populate the instructions sysvar, so build it for the chain being processed
+fn create_instructions_sysvar_account(instructions: &[&Instruction], current_index: u16) -> Account {
+    let borrowed = instructions
+        .iter()
+        .map(|instruction| BorrowedInstruction {
+            program_id: &instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &instruction.data,
+        })
+        .collect::<Vec<_>>();
+    let mut data = construct_instructions_data(&borrowed);
+    store_current_index_checked(&mut data, current_index).unwrap();
+
+    Account {
+        lamports: 1_000_000,
+        data,
+        owner: solana_sdk::sysvar::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn create_close_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &CLOSE_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// `vaults` holds `(vault, state)` pairs
+fn create_close_many_instruction(user: &Pubkey, vaults: &[(Pubkey, Pubkey)]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for (vault, vault_state) in vaults {
+        accounts.push(AccountMeta::new(*vault, false));
+        accounts.push(AccountMeta::new(*vault_state, false));
+    }
+    Instruction::new_with_bytes(PROGRAM_ID, &CLOSE_MANY_DISCRIMINATOR, accounts)
+}
+
+fn create_safe_close_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &SAFE_CLOSE_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// Signed by the vault's close authority, passing `owner` for the refund and skipping the
+// optional burn address and recipient
+fn create_authority_close_instruction(
+    close_authority: &Pubkey,
+    owner: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+) -> Instruction {
+    let mut instruction = create_close_instruction(close_authority, vault, vault_state);
+    instruction.accounts.push(AccountMeta::new_readonly(PROGRAM_ID, false));
+    instruction.accounts.push(AccountMeta::new_readonly(PROGRAM_ID, false));
+    instruction.accounts.push(AccountMeta::new(*owner, false));
+    instruction
+}
+
+// Passes the vault's metadata account, skipping the optional burn address, recipient and owner
+fn create_close_with_metadata_instruction(user: &Pubkey, vault: &Pubkey, vault_state: &Pubkey) -> Instruction {
+    let mut instruction = create_close_instruction(user, vault, vault_state);
+    for _ in 0..3 {
+        instruction.accounts.push(AccountMeta::new_readonly(PROGRAM_ID, false));
+    }
+    instruction.accounts.push(AccountMeta::new(find_vault_metadata_pda(vault_state).0, false));
+    instruction
+}
+
+fn create_close_with_burn_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+) -> Instruction {
+    let mut instruction = create_close_instruction(user, vault, vault_state);
+    instruction.accounts.push(AccountMeta::new(solana_sdk::incinerator::id(), false));
+    instruction
+}
+
+fn create_set_round_to_instruction(user: &Pubkey, vault_state: &Pubkey, round_to: u64) -> Instruction {
+    let mut instruction_data = SET_ROUND_TO_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&round_to.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_burn_dust_instruction(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+    burn_dust: bool,
+) -> Instruction {
+    let mut instruction_data = SET_BURN_DUST_DISCRIMINATOR.to_vec();
+    instruction_data.push(burn_dust as u8);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_max_deposit_per_tx_instruction(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+    max_deposit_per_tx: u64,
+) -> Instruction {
+    let mut instruction_data = SET_MAX_DEPOSIT_PER_TX_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&max_deposit_per_tx.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_deposit_limits_instruction(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+    min_deposit: u64,
+    balance_cap: u64,
+) -> Instruction {
+    let mut instruction_data = SET_DEPOSIT_LIMITS_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&min_deposit.to_le_bytes());
+    instruction_data.extend_from_slice(&balance_cap.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_rent_buffer_instruction(user: &Pubkey, vault_state: &Pubkey, rent_buffer_bps: u16) -> Instruction {
+    let mut instruction_data = SET_RENT_BUFFER_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&rent_buffer_bps.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_first_withdraw_delay_instruction(user: &Pubkey, vault_state: &Pubkey, first_withdraw_delay: u64) -> Instruction {
+    let mut instruction_data = SET_FIRST_WITHDRAW_DELAY_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&first_withdraw_delay.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_label_instruction(user: &Pubkey, vault_state: &Pubkey, label: &[u8]) -> Instruction {
+    let mut instruction_data = SET_LABEL_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&(label.len() as u32).to_le_bytes());
+    instruction_data.extend_from_slice(label);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_metadata_instruction(user: &Pubkey, vault_state: &Pubkey, metadata: &[u8]) -> Instruction {
+    let mut instruction_data = SET_METADATA_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    instruction_data.extend_from_slice(metadata);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(find_vault_metadata_pda(vault_state).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn create_clear_metadata_instruction(user: &Pubkey, vault_state: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &CLEAR_METADATA_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(find_vault_metadata_pda(vault_state).0, false),
+        ],
+    )
+}
+
+fn create_set_keeper_sweep_instruction(user: &Pubkey, vault_state: &Pubkey, sweep_threshold: u64, keeper_tip: u64) -> Instruction {
+    let mut instruction_data = SET_KEEPER_SWEEP_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&sweep_threshold.to_le_bytes());
+    instruction_data.extend_from_slice(&keeper_tip.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_request_withdraw_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+    release_slot: u64,
+) -> Instruction {
+    let mut instruction_data = REQUEST_WITHDRAW_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(recipient.as_ref());
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+    instruction_data.extend_from_slice(&release_slot.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_execute_withdraw_instruction(vault: &Pubkey, vault_state: &Pubkey, recipient: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &EXECUTE_WITHDRAW_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_cancel_withdraw_instruction(user: &Pubkey, vault_state: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &CANCEL_WITHDRAW_DISCRIMINATOR,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_keeper_sweep_instruction(keeper: &Pubkey, vault: &Pubkey, vault_state: &Pubkey, owner: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &KEEPER_SWEEP_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*keeper, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(*owner, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_set_sandwich_guard_instruction(user: &Pubkey, vault_state: &Pubkey, sandwich_guard: bool) -> Instruction {
+    let mut instruction_data = SET_SANDWICH_GUARD_DISCRIMINATOR.to_vec();
+    instruction_data.push(sandwich_guard as u8);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_max_slot_age_instruction(user: &Pubkey, vault_state: &Pubkey, max_slot_age: u64) -> Instruction {
+    let mut instruction_data = SET_MAX_SLOT_AGE_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&max_slot_age.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_log_level_instruction(user: &Pubkey, vault_state: &Pubkey, log_level: u8) -> Instruction {
+    let mut instruction_data = SET_LOG_LEVEL_DISCRIMINATOR.to_vec();
+    instruction_data.push(log_level);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_admin_instruction(user: &Pubkey, vault_state: &Pubkey, admin: &Pubkey) -> Instruction {
+    let mut instruction_data = SET_ADMIN_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(admin.as_ref());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_close_authority_instruction(user: &Pubkey, vault_state: &Pubkey, close_authority: &Pubkey) -> Instruction {
+    let mut instruction_data = SET_CLOSE_AUTHORITY_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(close_authority.as_ref());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_withdraw_policy_instruction(user: &Pubkey, vault_state: &Pubkey, policy: u8) -> Instruction {
+    let mut instruction_data = SET_WITHDRAW_POLICY_DISCRIMINATOR.to_vec();
+    instruction_data.push(policy);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_auto_forward_instruction(user: &Pubkey, vault_state: &Pubkey, auto_forward: &Pubkey) -> Instruction {
+    let mut instruction_data = SET_AUTO_FORWARD_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(auto_forward.as_ref());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_guardians_instruction(user: &Pubkey, vault_state: &Pubkey, guardians: &[Pubkey; 3], threshold: u8) -> Instruction {
+    let mut instruction_data = SET_GUARDIANS_DISCRIMINATOR.to_vec();
+    for guardian in guardians {
+        instruction_data.extend_from_slice(guardian.as_ref());
+    }
+    instruction_data.push(threshold);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_guardian_recover_instruction(vault_state: &Pubkey, new_owner: &Pubkey, signers: &[Pubkey]) -> Instruction {
+    let mut instruction_data = GUARDIAN_RECOVER_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(new_owner.as_ref());
+
+    let mut accounts = vec![AccountMeta::new(*vault_state, false)];
+    accounts.extend(signers.iter().map(|signer| AccountMeta::new_readonly(*signer, true)));
+    Instruction::new_with_bytes(PROGRAM_ID, &instruction_data, accounts)
+}
+
+fn create_configure_instruction(user: &Pubkey, vault_state: &Pubkey, configure: &ConfigureIx) -> Instruction {
+    let mut instruction_data = CONFIGURE_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&borsh::to_vec(configure).unwrap());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_yield_program_instruction(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+    yield_program: &Pubkey,
+) -> Instruction {
+    let mut instruction_data = SET_YIELD_PROGRAM_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(yield_program.as_ref());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_set_withdraw_cooldown_instruction(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+    cooldown_slots: u64,
+) -> Instruction {
+    let mut instruction_data = SET_WITHDRAW_COOLDOWN_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&cooldown_slots.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_harvest_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    vault_state: &Pubkey,
+    yield_program: &Pubkey,
+    yield_instruction: &Instruction,
+) -> Instruction {
+    let mut instruction_data = HARVEST_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&(yield_instruction.data.len() as u32).to_le_bytes());
+    instruction_data.extend_from_slice(&yield_instruction.data);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(*vault_state, false),
+        AccountMeta::new_readonly(*yield_program, false),
+        AccountMeta::new_readonly(find_global_config_pda().0, false),
+    ];
+    accounts.extend_from_slice(&yield_instruction.accounts);
+
+    Instruction::new_with_bytes(PROGRAM_ID, &instruction_data, accounts)
+}
+
+const STAKE_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("Stake11111111111111111111111111111111111111");
+const STAKE_CONFIG_ID: Pubkey = solana_sdk::pubkey!("StakeConfig11111111111111111111111111111111");
+const STAKE_ACCOUNT_LEN: usize = 200;
+// `StakeStateV2` offsets: a u32 tag, then `Meta { rent_exempt_reserve, authorized, lockup }`,
+// then the `Delegation` of a delegated account
+const STAKE_STATE_STAKER_OFFSET: usize = 12;
+const STAKE_STATE_WITHDRAWER_OFFSET: usize = 44;
+const STAKE_STATE_VOTER_OFFSET: usize = 124;
+const STAKE_STATE_DEACTIVATION_EPOCH_OFFSET: usize = 172;
+
+fn find_stake_vault_pda(vault: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"STAKE", vault.as_ref()], &PROGRAM_ID).0
+}
+
+fn create_initialize_stake_vault_instruction(user: &Pubkey, vault: &Pubkey, vault_state: &Pubkey, lamports: u64) -> Instruction {
+    let mut instruction_data = INITIALIZE_STAKE_VAULT_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&lamports.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(find_stake_vault_pda(vault), false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::rent::id(), false),
+            AccountMeta::new_readonly(STAKE_PROGRAM_ID, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+fn create_delegate_instruction(user: &Pubkey, vault: &Pubkey, vault_state: &Pubkey, vote_account: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &DELEGATE_DISCRIMINATOR,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new_readonly(*vault_state, false),
+            AccountMeta::new(find_stake_vault_pda(vault), false),
+            AccountMeta::new_readonly(*vote_account, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::id(), false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::stake_history::id(), false),
+            AccountMeta::new_readonly(STAKE_CONFIG_ID, false),
+            AccountMeta::new_readonly(STAKE_PROGRAM_ID, false),
+        ],
+    )
+}
+
+fn create_deactivate_instruction(user: &Pubkey, vault: &Pubkey, vault_state: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &DEACTIVATE_DISCRIMINATOR,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new_readonly(*vault_state, false),
+            AccountMeta::new(find_stake_vault_pda(vault), false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::id(), false),
+            AccountMeta::new_readonly(STAKE_PROGRAM_ID, false),
+        ],
+    )
+}
+
+fn create_withdraw_stake_instruction(user: &Pubkey, vault: &Pubkey, vault_state: &Pubkey, lamports: u64) -> Instruction {
+    let mut instruction_data = WITHDRAW_STAKE_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&lamports.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new(find_stake_vault_pda(vault), false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::id(), false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::stake_history::id(), false),
+            AccountMeta::new_readonly(STAKE_PROGRAM_ID, false),
+        ],
+    )
+}
+
+// A vote account the stake program accepts for delegation
+fn create_vote_account(mollusk: &Mollusk, node: &Pubkey) -> Account {
+    let vote_state = VoteState::new(
+        &VoteInit {
+            node_pubkey: *node,
+            authorized_voter: *node,
+            authorized_withdrawer: *node,
+            commission: 0,
+        },
+        &mollusk.sysvars.clock,
+    );
+    let mut data = vec![0; VoteState::size_of()];
+    VoteState::serialize(&VoteStateVersions::new_current(vote_state), &mut data).unwrap();
+    Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(data.len()),
+        data,
+        owner: solana_vote_interface::program::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn create_resize_state_instruction(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &RESIZE_STATE_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn create_recover_state_lamports_instruction(user: &Pubkey, vault_state: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &RECOVER_STATE_LAMPORTS_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault_state, false),
+        ],
+    )
+}
+
+fn create_normalize_bump_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    canonical_vault: &Pubkey,
+    vault_state: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &NORMALIZE_BUMP_DISCRIMINATOR,
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*canonical_vault, false),
+            AccountMeta::new(*vault_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn create_assert_balance_instruction(vault: &Pubkey, vault_state: &Pubkey, expected: u64) -> Instruction {
+    let mut instruction_data = ASSERT_BALANCE_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&expected.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new_readonly(*vault_state, false),
+        ],
+    )
+}
+
+fn create_read_state_instruction(vault: &Pubkey, vault_state: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &READ_STATE_DISCRIMINATOR,
+        vec![
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new_readonly(*vault_state, false),
+        ],
+    )
+}
+
+fn create_derive_vault_instruction(vault_state: &Pubkey) -> Instruction {
+    let mut data = DERIVE_VAULT_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(vault_state.as_ref());
+    Instruction::new_with_bytes(PROGRAM_ID, &data, vec![])
+}
+
+fn create_initialize_global_config_instruction(admin: &Pubkey, global_config: &Pubkey, program_data: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &INITIALIZE_GLOBAL_CONFIG_DISCRIMINATOR,
+        vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(*global_config, false),
+            AccountMeta::new_readonly(*program_data, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn create_set_emergency_paused_instruction(admin: &Pubkey, global_config: &Pubkey, paused: bool) -> Instruction {
+    let mut instruction_data = SET_EMERGENCY_PAUSED_DISCRIMINATOR.to_vec();
+    instruction_data.push(paused as u8);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*global_config, false),
+        ],
+    )
+}
+
+fn create_set_allowlist_root_instruction(admin: &Pubkey, global_config: &Pubkey, allowlist_root: &[u8; 32]) -> Instruction {
+    let mut instruction_data = SET_ALLOWLIST_ROOT_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(allowlist_root);
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*global_config, false),
+        ],
+    )
+}
+
+fn create_initialize_shared_instruction(
+    creator: &Pubkey,
+    shared_state: &Pubkey,
+    vault: &Pubkey,
+    owners: &[(Pubkey, u16)],
+) -> Instruction {
+    let mut instruction_data = INITIALIZE_SHARED_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&(owners.len() as u32).to_le_bytes());
+    for (owner, share_bps) in owners {
+        instruction_data.extend_from_slice(owner.as_ref());
+        instruction_data.extend_from_slice(&share_bps.to_le_bytes());
+    }
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*creator, true),
+            AccountMeta::new(*shared_state, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn create_withdraw_shared_instruction(
+    user: &Pubkey,
+    vault: &Pubkey,
+    shared_state: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction_data = WITHDRAW_SHARED_DISCRIMINATOR.to_vec();
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &instruction_data,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*shared_state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_global_config_pda().0, false),
+        ],
+    )
+}
+
+#[test]
+fn test_initialize_vault() {
+    let mollusk = create_mollusk();
+    
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+
+    let owner_initial_balance = 10_000_000_000;
+    let owner_account = Account::new(owner_initial_balance, 0, &system_program::id());
+    let state_account = Account::default();
+    let vault_account = Account::default();
+
+    let instruction = create_initialize_instruction(&owner, &state_pda, &vault_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    
+    let accounts = vec![
+        (owner, owner_account),
+        (state_pda, state_account),
+        (vault_pda, vault_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Opening a vault costs the owner exactly the rent for the vault PDA and the state account
+    let expected_state_data = create_vault_state_data(&owner, state_bump, find_vault_pda(&state_pda).1);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let state_rent = mollusk.sysvars.rent.minimum_balance(expected_state_data.len());
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&state_pda).data(&expected_state_data).lamports(state_rent).build(),
+            Check::account(&vault_pda).lamports(vault_rent).build(),
+            Check::account(&owner).lamports(owner_initial_balance - vault_rent - state_rent).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_deposit_to_owner() {
+    let mollusk = create_mollusk();
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+
+    let funder = Pubkey::new_unique();
+    let mut accounts = vec![
+        (funder, Account::new(5_000_000_000, 0, &system_program::id())),
+        mollusk_svm::program::keyed_account_for_system_program(),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    // Two vaults, B is funded and A is there to be confused with it
+    let mut vaults = vec![];
+    for _ in 0..2 {
+        let owner = Pubkey::new_unique();
+        let (state_pda, state_bump) = find_vault_state_pda(&owner);
+        let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+        let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        accounts.push((vault_pda, Account::new(vault_rent, 0, &system_program::id())));
+        accounts.push((
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ));
+        vaults.push((owner, state_pda, vault_pda));
+    }
+    let [(owner_a, state_a, vault_a), (owner_b, state_b, vault_b)] = vaults[..] else { unreachable!() };
+
+    let amount = 1_000_000_000;
+    mollusk.process_and_validate_instruction(
+        &create_deposit_to_owner_instruction(&funder, &vault_b, &state_b, &owner_b, amount),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_b).lamports(vault_rent + amount).build(),
+            Check::account(&vault_a).lamports(vault_rent).build(),
+            Check::account(&funder).lamports(5_000_000_000 - amount).build(),
+        ],
+    );
+
+    // The named owner must match the state, and the state must be the owner's PDA
+    for (vault, state, owner) in [(vault_b, state_b, owner_a), (vault_a, state_a, owner_b)] {
+        let result = mollusk.process_instruction(&create_deposit_to_owner_instruction(&funder, &vault, &state, &owner, amount), &accounts);
+        assert!(result.program_result.is_err());
+    }
+
+    // A state at another address that claims B as its owner is rejected
+    let (_, state_b_account) = accounts.iter().find(|(key, _)| *key == state_b).unwrap().clone();
+    let fake_state = Pubkey::new_unique();
+    let (fake_vault, _) = find_vault_pda(&fake_state);
+    let mut fake_accounts = accounts.clone();
+    fake_accounts.push((fake_state, state_b_account));
+    fake_accounts.push((fake_vault, Account::new(vault_rent, 0, &system_program::id())));
+    let result = mollusk.process_instruction(
+        &create_deposit_to_owner_instruction(&funder, &fake_vault, &fake_state, &owner_b, amount),
+        &fake_accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    // The vault's deposit cap applies to gifts too
+    let state_b_index = accounts.iter().position(|(key, _)| *key == state_b).unwrap();
+    accounts[state_b_index].1.data[MAX_DEPOSIT_PER_TX_OFFSET..MAX_DEPOSIT_PER_TX_OFFSET + 8].copy_from_slice(&(amount - 1).to_le_bytes());
+    mollusk.process_and_validate_instruction(
+        &create_deposit_to_owner_instruction(&funder, &vault_b, &state_b, &owner_b, amount),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(DEPOSIT_CAP_EXCEEDED_ERROR))],
+    );
+}
+
+#[test]
+fn test_initialize_prefunded_vault() {
+    let mollusk = create_mollusk();
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+
+    // Anyone can send lamports to the vault PDA before it is opened. The owner still pays the
+    // full rent on top, so the vault never ends below rent-exemption whatever was sent
+    for prefund in [1, vault_rent - 1, vault_rent, vault_rent + 1, 5_000_000_000] {
+        let owner = Pubkey::new_unique();
+        let (state_pda, state_bump) = find_vault_state_pda(&owner);
+        let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+        let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+        let owner_initial_balance = 10_000_000_000;
+        let accounts = vec![
+            (owner, Account::new(owner_initial_balance, 0, &system_program::id())),
+            (state_pda, Account::default()),
+            (vault_pda, Account::new(prefund, 0, &system_program::id())),
+            (system_program_key, system_program_account),
+            keyed_account_for_global_config(&mollusk, false),
+        ];
+
+        let expected_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        let state_rent = mollusk.sysvars.rent.minimum_balance(expected_state_data.len());
+        let result = mollusk.process_and_validate_instruction(
+            &create_initialize_instruction(&owner, &state_pda, &vault_pda),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&state_pda).data(&expected_state_data).lamports(state_rent).build(),
+                Check::account(&vault_pda).lamports(prefund + vault_rent).owner(&system_program::id()).build(),
+                Check::account(&owner).lamports(owner_initial_balance - vault_rent - state_rent).build(),
+            ],
+        );
+        assert!(result.get_account(&vault_pda).unwrap().lamports >= vault_rent);
+
+        // The donation is withdrawable like any other balance
+        let withdraw = create_withdraw_instruction(&owner, &vault_pda, &state_pda, prefund);
+        mollusk.process_and_validate_instruction(
+            &withdraw,
+            &result.resulting_accounts,
+            &[Check::success(), Check::account(&vault_pda).lamports(vault_rent).build()],
+        );
+    }
+
+    // A pre-funded state PDA is topped up to rent-exemption rather than failing to create
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::new(1, 0, &system_program::id())),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let expected_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    mollusk.process_and_validate_instruction(
+        &create_initialize_instruction(&owner, &state_pda, &vault_pda),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&state_pda)
+                .data(&expected_state_data)
+                .lamports(mollusk.sysvars.rent.minimum_balance(expected_state_data.len()))
+                .owner(&PROGRAM_ID)
+                .build(),
+            Check::account(&vault_pda).lamports(vault_rent).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_initialize_vault_currency() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let currency = *b"USDC\0\0\0\0";
+    let result = mollusk.process_and_validate_instruction(
+        &create_initialize_instruction_with_currency(&owner, &state_pda, &vault_pda, &[], &currency),
+        &accounts,
+        &[Check::success()],
+    );
+    let data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(data[CURRENCY_OFFSET..GUARDIANS_OFFSET], currency);
+    let state: &VaultState = bytemuck::from_bytes(&data[8..]);
+    assert_eq!(state.currency().unwrap(), "USDC");
+
+    // Empty, spaced, gapped or non-ASCII tags would render inconsistently across front-ends
+    let invalid = [[0; 8], *b"US DC\0\0\0", *b"US\0DC\0\0\0", [0xe2, 0x82, 0xac, 0, 0, 0, 0, 0]];
+    for currency in invalid {
+        let result = mollusk.process_instruction(
+            &create_initialize_instruction_with_currency(&owner, &state_pda, &vault_pda, &[], &currency),
+            &accounts,
+        );
+        assert!(result.program_result.is_err(), "{currency:?} was accepted");
+    }
+}
+
+#[test]
+#[cfg(feature = "verbose-logs")]
+fn test_initialize_emits_vault_opened_event() {
+    let (mollusk, logger) = create_logging_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+
+    let instruction = create_initialize_instruction(&owner, &state_pda, &vault_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+
+    let event = find_event_data(&logger, &VAULT_OPENED_EVENT_DISCRIMINATOR).expect("missing VaultOpenedEvent");
+    assert_eq!(event, [owner.to_bytes(), state_pda.to_bytes(), vault_pda.to_bytes()].concat());
+}
+
+#[test]
+fn test_initialize_and_deposit() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let deposit_amount = 2_000_000_000;
+    let owner_initial_balance = 10_000_000_000;
+
+    let instruction = create_initialize_and_deposit_instruction(&owner, &state_pda, &vault_pda, deposit_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(owner_initial_balance, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let expected_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let state_rent = mollusk.sysvars.rent.minimum_balance(expected_state_data.len());
+
+    // The vault holds its rent on top of the deposit, so all of the deposit stays withdrawable
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&state_pda).data(&expected_state_data).build(),
+            Check::account(&vault_pda).lamports(vault_rent + deposit_amount).build(),
+            Check::account(&owner).lamports(owner_initial_balance - vault_rent - state_rent - deposit_amount).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_initialize_returns_vault_addresses() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let instruction = create_initialize_instruction(&owner, &state_pda, &vault_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+
+    // Return data layout: state (32) | vault (32) | state_bump (1) | vault_bump (1)
+    let return_data = result.return_data;
+    assert_eq!(return_data.len(), 66);
+    assert_eq!(Pubkey::try_from(&return_data[..32]).unwrap(), state_pda);
+    assert_eq!(Pubkey::try_from(&return_data[32..64]).unwrap(), vault_pda);
+    assert_eq!(return_data[64], state_bump);
+    assert_eq!(return_data[65], vault_bump);
+}
+
+#[test]
+fn test_deposit_to_vault() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, vault_pda, accounts) = setup_initialized_vault(&mollusk);
+    let deposit_amount = 5_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE - deposit_amount).build(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + deposit_amount).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_deposits_to_distinct_vaults_in_one_transaction() {
+    let mollusk = create_mollusk();
+
+    let user_initial_balance = 10_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let mut accounts = vec![
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let mut vaults = Vec::new();
+    for deposit_amount in [1_000_000_000, 2_500_000_000] {
+        let owner = Pubkey::new_unique();
+        let (state_pda, state_bump) = find_vault_state_pda(&owner);
+        let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+        let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        let vault_state_account = Account {
+            lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+            data: vault_state_data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        accounts.push((owner, Account::new(user_initial_balance, 0, &system_program::id())));
+        accounts.push((vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())));
+        accounts.push((state_pda, vault_state_account));
+        vaults.push((owner, vault_pda, create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount), deposit_amount));
+    }
+
+    // The states and the global config are read-only, so the only write locks are each owner
+    // and their own vault. Transactions depositing into different vaults never contend.
+    let writable = |instruction: &Instruction| -> Vec<Pubkey> {
+        instruction.accounts.iter().filter(|meta| meta.is_writable).map(|meta| meta.pubkey).collect()
+    };
+    let (first, second) = (&vaults[0], &vaults[1]);
+    assert_eq!(writable(&first.2), [first.0, first.1]);
+    assert_eq!(writable(&second.2), [second.0, second.1]);
+
+    let checks: Vec<Vec<Check>> = vaults
+        .iter()
+        .map(|(owner, vault_pda, _, deposit_amount)| {
+            vec![
+                Check::success(),
+                Check::account(owner).lamports(user_initial_balance - deposit_amount).build(),
+                Check::account(vault_pda).lamports(vault_initial_balance + deposit_amount).build(),
+            ]
+        })
+        .collect();
+    mollusk.process_and_validate_instruction_chain(
+        &[(&first.2, &checks[0]), (&second.2, &checks[1])],
+        &accounts,
+    );
+}
+
+#[test]
+fn test_deposit_with_old_layout_state() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let deposit_amount = 1_000_000_000;
+
+    let user_initial_balance = 5_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    // A state created before any fields were appended, never resized
+    let old_data = create_vault_state_data(&owner, state_bump, vault_bump)[..VAULT_STATE_V0_LEN].to_vec();
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(old_data.len()),
+        data: old_data.clone(),
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Owner and bumps are read from the old layout, and the state is left untouched
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance - deposit_amount).build(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + deposit_amount).build(),
+            Check::account(&state_pda).data(&old_data).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_deposit_cap_per_transaction() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let cap = 1_000_000_000;
+    let user_initial_balance = 10_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let uncapped_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let set_cap_instruction =
+        create_set_max_deposit_per_tx_instruction(&owner, &state_pda, cap);
+    let result = mollusk.process_and_validate_instruction(
+        &set_cap_instruction,
+        &[
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (state_pda, uncapped_state_account.clone()),
+        ],
+        &[Check::success()],
+    );
+    let capped_state_account = result.get_account(&state_pda).unwrap().clone();
+    assert_eq!(capped_state_account.data[MAX_DEPOSIT_PER_TX_OFFSET..BALANCE_CAP_OFFSET], cap.to_le_bytes());
+
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts_with_state = |vault_state_account: &Account| {
+        vec![
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())),
+            (state_pda, vault_state_account.clone()),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ]
+    };
+
+    // Exactly the cap is allowed
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, cap),
+        &accounts_with_state(&capped_state_account),
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + cap).build(),
+        ],
+    );
+
+    // One lamport over is rejected
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, cap + 1),
+        &accounts_with_state(&capped_state_account),
+        &[
+            Check::err(solana_sdk::program_error::ProgramError::Custom(DEPOSIT_CAP_EXCEEDED_ERROR)),
+            Check::account(&vault_pda).lamports(vault_initial_balance).build(),
+        ],
+    );
+
+    // A cap of 0 means unlimited
+    let large_deposit = 5 * cap;
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, large_deposit),
+        &accounts_with_state(&uncapped_state_account),
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + large_deposit).build(),
+        ],
+    );
+}
+
+#[cfg(feature = "strict")]
+#[test]
+fn test_strict_deposit_rejects_writable_state() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let mut instruction = create_deposit_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000);
+    instruction.accounts[2] = AccountMeta::new(state_pda, false);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_min_deposit_prevents_balance_cap_griefing() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let griefer = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let balance_cap = 10_000_000_000;
+    let min_deposit = 100_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(20_000_000_000, 0, &system_program::id())),
+        (griefer, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_rent, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_deposit_limits_instruction(&owner, &state_pda, min_deposit, balance_cap),
+        &accounts,
+        &[Check::success()],
+    );
+    let data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(data[BALANCE_CAP_OFFSET..MIN_DEPOSIT_OFFSET], balance_cap.to_le_bytes());
+    assert_eq!(data[MIN_DEPOSIT_OFFSET..RENT_BUFFER_BPS_OFFSET], min_deposit.to_le_bytes());
+    let accounts = result.resulting_accounts;
+
+    // A 1 lamport tip can't be used to nudge the vault towards the cap
+    mollusk.process_and_validate_instruction(
+        &create_tip_instruction(&griefer, &vault_pda, &state_pda, 1),
+        &accounts,
+        &[
+            Check::err(solana_sdk::program_error::ProgramError::Custom(DEPOSIT_BELOW_MINIMUM_ERROR)),
+            Check::account(&vault_pda).lamports(vault_rent).build(),
+        ],
+    );
+
+    // The owner fills to just below the cap, tops up the gap even though it is below the minimum,
+    // and anything beyond the cap is rejected
+    let gap = 50_000_000;
+    let result = mollusk.process_and_validate_instruction_chain(
+        &[
+            (
+                &create_deposit_instruction(&owner, &vault_pda, &state_pda, balance_cap - gap),
+                &[Check::success()],
+            ),
+            (
+                &create_deposit_instruction(&owner, &vault_pda, &state_pda, gap),
+                &[Check::success(), Check::account(&vault_pda).lamports(vault_rent + balance_cap).build()],
+            ),
+        ],
+        &accounts,
+    );
+
+    for instruction in [
+        create_deposit_instruction(&owner, &vault_pda, &state_pda, min_deposit),
+        create_tip_instruction(&griefer, &vault_pda, &state_pda, min_deposit),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &result.resulting_accounts,
+            &[Check::err(solana_sdk::program_error::ProgramError::Custom(BALANCE_CAP_EXCEEDED_ERROR))],
+        );
+    }
+}
+
+#[test]
+fn test_deposit_raw_to_vault() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let funder = Pubkey::new_unique();
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let deposit_amount = 2_000_000_000;
+
+    let funder_initial_balance = 10_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    let funder_account = Account::new(funder_initial_balance, 0, &system_program::id());
+    let vault_account = Account::new(vault_initial_balance, 0, &system_program::id());
+
+    let instruction = create_deposit_raw_instruction(&funder, &vault_pda, deposit_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    // The raw path takes no state account and works for any funder
+    let accounts = vec![
+        (funder, funder_account),
+        (vault_pda, vault_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&funder).lamports(funder_initial_balance - deposit_amount).build(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + deposit_amount).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_non_owner_tip() {
+    let (mollusk, logger) = create_logging_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let tipper = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let tip_amount = 250_000_000;
+    let previous_tips: u64 = 100_000_000;
+
+    let tipper_initial_balance = 1_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[TOTAL_TIPS_OFFSET..WITHDRAW_NONCE_OFFSET].copy_from_slice(&previous_tips.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data.clone(),
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_tip_instruction(&tipper, &vault_pda, &state_pda, tip_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (tipper, Account::new(tipper_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let mut expected_data = vault_state_data;
+    expected_data[TOTAL_TIPS_OFFSET..WITHDRAW_NONCE_OFFSET].copy_from_slice(&(previous_tips + tip_amount).to_le_bytes());
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&tipper).lamports(tipper_initial_balance - tip_amount).build(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + tip_amount).build(),
+            Check::account(&state_pda).data(&expected_data).build(),
+        ],
+    );
+
+    if cfg!(feature = "verbose-logs") {
+        let event = find_event_data(&logger, &TIP_EVENT_DISCRIMINATOR).expect("missing TipEvent");
+        assert_eq!(event, [tipper.to_bytes().as_slice(), &tip_amount.to_le_bytes()].concat());
+    } else {
+        assert_eq!(find_event_data(&logger, &TIP_EVENT_DISCRIMINATOR), None);
+    }
+}
+
+#[test]
+fn test_tip_from_program_pda() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    // A system-owned PDA of some protocol, as `cpi_deposit_from_pda` would fund from
+    let (funder_pda, _) = Pubkey::find_program_address(&[b"fee_router"], &Pubkey::new_unique());
+    let tip_amount = 250_000_000;
+
+    let funder_initial_balance = 1_000_000_000;
+    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data.clone(),
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    // The funder is marked as a signer directly, standing in for the protocol's `invoke_signed`
+    let instruction = create_tip_instruction(&funder_pda, &vault_pda, &state_pda, tip_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (funder_pda, Account::new(funder_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let mut expected_data = vault_state_data;
+    expected_data[TOTAL_TIPS_OFFSET..WITHDRAW_NONCE_OFFSET].copy_from_slice(&tip_amount.to_le_bytes());
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&funder_pda).lamports(funder_initial_balance - tip_amount).build(),
+            Check::account(&vault_pda).lamports(vault_initial_balance + tip_amount).build(),
+            Check::account(&state_pda).data(&expected_data).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_underfunded_transfer_maps_to_vault_error() {
+    let mollusk = create_mollusk();
+
+    let funder = Pubkey::new_unique();
+    let (vault_pda, _) = find_vault_pda(&Pubkey::new_unique());
+    let funder_balance = 1_000_000_000;
+
+    // The raw deposit has no balance check of its own, so this would otherwise fail inside the
+    // system program with its own error code
+    let instruction = create_deposit_raw_instruction(&funder, &vault_pda, funder_balance + 1);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (funder, Account::new(funder_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::err(solana_sdk::program_error::ProgramError::Custom(INSUFFICIENT_FUNDS_ERROR)),
+            Check::account(&funder).lamports(funder_balance).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_initialize_with_caller_program_pda_owner() {
+    let mollusk = create_mollusk();
+
+    // As `cpi_initialize` would open it: the caller's `invoke_signed` presents its PDA as the
+    // signing owner, marked as a signer directly since Mollusk cannot load a mock caller here
+    let caller_program = Pubkey::new_unique();
+    let (caller_pda, _) = Pubkey::find_program_address(&[b"treasury"], &caller_program);
+    assert!(!caller_pda.is_on_curve());
+    let (state_pda, _) = find_vault_state_pda(&caller_pda);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let initialize_instruction = create_initialize_instruction(&caller_pda, &state_pda, &vault_pda);
+
+    let accounts = vec![
+        (caller_pda, Account::new(1_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let result = mollusk.process_and_validate_instruction(&initialize_instruction, &accounts, &[Check::success()]);
+    let state: &VaultState = bytemuck::from_bytes(&result.get_account(&state_pda).unwrap().data[8..]);
+    assert_eq!(state.owner, caller_pda.to_bytes().into());
+
+    // The owner pays the rent with a System transfer, so a PDA holding its program's data can't
+    let mut accounts = accounts;
+    accounts[0].1 = Account {
+        lamports: 1_000_000_000,
+        data: vec![1; 16],
+        owner: caller_program,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let result = mollusk.process_instruction(&initialize_instruction, &accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_deposit_from_caller_program_pda() {
+    let mollusk = create_mollusk();
+
+    // A vault owned by a PDA of some caller program (e.g. a fee router). Mollusk cannot load a
+    // mock caller here, so the PDA is marked as a signer directly, which is exactly what the
+    // caller's `invoke_signed` would present to the vault program
+    let caller_program = Pubkey::new_unique();
+    let (caller_pda, _) = Pubkey::find_program_address(&[b"fee_router"], &caller_program);
+    assert!(!caller_pda.is_on_curve());
+
+    let (state_pda, state_bump) = find_vault_state_pda(&caller_pda);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let caller_pda_balance = 10_000_000_000;
+    let deposit_amount = 3_000_000_000;
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let state_rent = mollusk.sysvars.rent.minimum_balance(create_vault_state_data(&caller_pda, state_bump, vault_bump).len());
+
+    let initialize_instruction = create_initialize_instruction(&caller_pda, &state_pda, &vault_pda);
+    let deposit_instruction = create_deposit_instruction(&caller_pda, &vault_pda, &state_pda, deposit_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (caller_pda, Account::new(caller_pda_balance, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.process_and_validate_instruction_chain(
+        &[
+            (
+                &initialize_instruction,
+                &[
+                    Check::success(),
+                    Check::account(&state_pda).data(&create_vault_state_data(&caller_pda, state_bump, vault_bump)).build(),
+                ],
+            ),
+            (
+                &deposit_instruction,
+                &[
+                    Check::success(),
+                    Check::account(&caller_pda).lamports(caller_pda_balance - vault_rent - state_rent - deposit_amount).build(),
+                    Check::account(&vault_pda).lamports(vault_rent + deposit_amount).build(),
+                ],
+            ),
+        ],
+        &accounts,
+    );
+}
+
+#[test]
+fn test_wrap_deposit() {
+    let mut mollusk = create_mollusk();
+    token::add_program(&mut mollusk);
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let wsol_account = Pubkey::new_unique();
+    let wrap_amount = 2_000_000_000;
+    let user_initial_balance = 5_000_000_000;
+    let vault_balance = mollusk.sysvars.rent.minimum_balance(0);
+    let rent_exempt_reserve = mollusk.sysvars.rent.minimum_balance(TOKEN_ACCOUNT_LEN);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = |wsol: Account| {
+        vec![
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+            (state_pda, vault_state_account.clone()),
+            (wsol_account, wsol),
+            token::keyed_account(),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ]
+    };
+    let instruction = create_wrap_deposit_instruction(&owner, &vault_pda, &state_pda, &wsol_account, wrap_amount);
+
+    // The token amount only moves if `SyncNative` ran after the transfer
+    let result = mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts(create_wsol_account(&mollusk, &vault_pda)),
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance - wrap_amount).build(),
+            Check::account(&vault_pda).lamports(vault_balance).build(),
+            Check::account(&wsol_account).lamports(rent_exempt_reserve + wrap_amount).build(),
+        ],
+    );
+    let data = &result.get_account(&wsol_account).unwrap().data;
+    assert_eq!(data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8], wrap_amount.to_le_bytes());
+
+    // A wSOL account the vault doesn't own is rejected before any lamports move
+    let result = mollusk.process_instruction(&instruction, &accounts(create_wsol_account(&mollusk, &owner)));
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&owner).unwrap().lamports, user_initial_balance);
+}
+
+#[test]
+fn test_withdraw_to_program() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let vault_balance = 2_000_000_000;
+    set_lamports(&mut accounts, &vault_pda, vault_balance);
+
+    // Another program's PDA, holding data that program owns
+    let other_program = Pubkey::new_unique();
+    let (program_pda, _) = Pubkey::find_program_address(&[b"escrow"], &other_program);
+    let pda_rent = mollusk.sysvars.rent.minimum_balance(16);
+    let executable = Pubkey::new_unique();
+    let stranger = Pubkey::new_unique();
+    accounts.extend([
+        (
+            program_pda,
+            Account {
+                lamports: pda_rent,
+                data: vec![7; 16],
+                owner: other_program,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+        (
+            executable,
+            Account {
+                lamports: pda_rent,
+                data: vec![],
+                owner: solana_sdk::bpf_loader_upgradeable::id(),
+                executable: true,
+                rent_epoch: 0,
+            },
+        ),
+        (stranger, Account::new(1_000_000_000, 0, &system_program::id())),
+    ]);
+
+    let amount = 500_000_000;
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &program_pda, amount),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&program_pda).lamports(pda_rent + amount).owner(&other_program).data(&[7; 16]).build(),
+            Check::account(&vault_pda).lamports(vault_balance - amount).build(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE).build(),
+        ],
+    );
+
+    // Only the owner, only the withdrawable balance, and never the vault, its state or a program
+    let failures = [
+        create_withdraw_to_program_instruction(&stranger, &vault_pda, &state_pda, &program_pda, amount),
+        create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &program_pda, vault_balance - vault_rent + 1),
+        create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &vault_pda, amount),
+        create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &state_pda, amount),
+        create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &executable, amount),
+    ];
+    for instruction in &failures {
+        let result = mollusk.process_instruction(instruction, &accounts);
+        assert!(result.program_result.is_err());
+    }
+
+    // A bound vault only pays its bound recipient
+    let bound_recipient = Pubkey::new_unique();
+    let mut bound_accounts = accounts.clone();
+    bound_accounts[2].1.data[BOUND_RECIPIENT_OFFSET..BOUND_RECIPIENT_OFFSET + 32].copy_from_slice(bound_recipient.as_ref());
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &program_pda, amount),
+        &bound_accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(RECIPIENT_NOT_ALLOWED_ERROR))],
+    );
+}
+
+#[test]
+fn test_withdraw_token_batch() {
+    let mut mollusk = create_mollusk();
+    token::add_program(&mut mollusk);
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let mint = Pubkey::new_unique();
+    let other_mint = Pubkey::new_unique();
+    let source = Pubkey::new_unique();
+    let employees = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let payroll = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let wrong_mint_account = Pubkey::new_unique();
+    accounts.extend([
+        (source, create_token_account(&mollusk, &mint, &vault_pda, 1_000)),
+        (payroll[0], create_token_account(&mollusk, &mint, &employees[0], 0)),
+        (payroll[1], create_token_account(&mollusk, &mint, &employees[1], 0)),
+        (wrong_mint_account, create_token_account(&mollusk, &other_mint, &employees[2], 0)),
+        token::keyed_account(),
+    ]);
+    let token_amount = |result: &mollusk_svm::result::InstructionResult, key: &Pubkey| {
+        let data = &result.get_account(key).unwrap().data;
+        u64::from_le_bytes(data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8].try_into().unwrap())
+    };
+
+    // Every recipient is paid from the vault's token account in one instruction
+    let result = mollusk.process_and_validate_instruction(
+        &create_withdraw_token_batch_instruction(&owner, &vault_pda, &state_pda, &source, &payroll, &[100, 250]),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(token_amount(&result, &source), 650);
+    assert_eq!(token_amount(&result, &payroll[0]), 100);
+    assert_eq!(token_amount(&result, &payroll[1]), 250);
+
+    // One recipient of another mint fails the whole batch
+    let result = mollusk.process_instruction(
+        &create_withdraw_token_batch_instruction(&owner, &vault_pda, &state_pda, &source, &[payroll[0], wrong_mint_account], &[100, 250]),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+    assert_eq!(token_amount(&result, &source), 1_000);
+
+    // Amounts and recipients have to pair up, and only the owner can pay out
+    let result = mollusk.process_instruction(
+        &create_withdraw_token_batch_instruction(&owner, &vault_pda, &state_pda, &source, &payroll, &[100]),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_instruction(
+        &create_withdraw_token_batch_instruction(&employees[0], &vault_pda, &state_pda, &source, &payroll, &[100, 250]),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_close_refuses_held_tokens() {
+    let mut mollusk = create_mollusk();
+    token::add_program(&mut mollusk);
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let wsol_account = Pubkey::new_unique();
+    let source = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    accounts.extend([
+        (wsol_account, create_wsol_account(&mollusk, &vault_pda)),
+        (source, create_token_account(&mollusk, &mint, &vault_pda, 1_000)),
+        (recipient, create_token_account(&mollusk, &mint, &owner, 0)),
+        token::keyed_account(),
+    ]);
+    let close = create_close_instruction(&owner, &vault_pda, &state_pda);
+    let close_many = create_close_many_instruction(&owner, &[(vault_pda, state_pda)]);
+    let not_empty = || [Check::err(solana_sdk::program_error::ProgramError::Custom(VAULT_NOT_EMPTY_ERROR))];
+
+    // Wrapped SOL stays in a token account of the vault, so neither close path may drop the state
+    let result = mollusk.process_and_validate_instruction(
+        &create_wrap_deposit_instruction(&owner, &vault_pda, &state_pda, &wsol_account, 1_000_000),
+        &accounts,
+        &[Check::success()],
+    );
+    let state_data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(state_data[TOKEN_ACCOUNTS_OFFSET..TOKEN_ACCOUNTS_OFFSET + 32], wsol_account.to_bytes());
+    mollusk.process_and_validate_instruction(&close, &result.resulting_accounts, &not_empty());
+    mollusk.process_and_validate_instruction(&close_many, &result.resulting_accounts, &not_empty());
+
+    // A token account the vault pays from is tracked until a batch empties it
+    let result = mollusk.process_and_validate_instruction(
+        &create_withdraw_token_batch_instruction(&owner, &vault_pda, &state_pda, &source, &[recipient], &[400]),
+        &accounts,
+        &[Check::success()],
+    );
+    let state_data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(state_data[TOKEN_ACCOUNTS_OFFSET..TOKEN_ACCOUNTS_OFFSET + 32], source.to_bytes());
+    mollusk.process_and_validate_instruction(&close, &result.resulting_accounts, &not_empty());
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_withdraw_token_batch_instruction(&owner, &vault_pda, &state_pda, &source, &[recipient], &[600]),
+        &result.resulting_accounts,
+        &[Check::success()],
+    );
+    let state_data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(state_data[TOKEN_ACCOUNTS_OFFSET..HAS_STAKE_ACCOUNT_OFFSET], [0; 4 * 32]);
+    mollusk.process_and_validate_instruction(
+        &close,
+        &result.resulting_accounts,
+        &[Check::success(), Check::account(&state_pda).closed().build()],
+    );
+}
+
+#[test]
+fn test_withdraw_from_vault() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let withdraw_amount = 3_000_000_000;
+
+    let user_initial_balance = 5_000_000_000;
+    let vault_initial_balance = 8_000_000_000;
+    set_lamports(&mut accounts, &owner, user_initial_balance);
+    set_lamports(&mut accounts, &vault_pda, vault_initial_balance);
+
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdraw_amount),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance + withdraw_amount).build(),
+            Check::account(&vault_pda).lamports(vault_initial_balance - withdraw_amount).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_withdraw_all_preserves_rent_exemption() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let withdrawable = 4_000_000_000;
+    let user_initial_balance = 1_000_000_000;
+    let deposit_amount = 500_000_000;
+    set_lamports(&mut accounts, &owner, user_initial_balance);
+    set_lamports(&mut accounts, &vault_pda, vault_rent + withdrawable);
+
+    // Dipping into the rent reserve by a single lamport is rejected
+    let over_withdraw = create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdrawable + 1);
+    let result = mollusk.process_instruction(&over_withdraw, &accounts);
+    assert!(result.program_result.is_err());
+
+    // Withdrawing everything available leaves exactly the rent-exempt minimum, and the vault
+    // keeps accepting deposits afterwards
+    let withdraw_all = create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdrawable);
+    let deposit = create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount);
+    mollusk.process_and_validate_instruction_chain(
+        &[
+            (
+                &withdraw_all,
+                &[
+                    Check::success(),
+                    Check::account(&vault_pda).lamports(vault_rent).build(),
+                    Check::account(&owner).lamports(user_initial_balance + withdrawable).build(),
+                ],
+            ),
+            (
+                &deposit,
+                &[
+                    Check::success(),
+                    Check::account(&vault_pda).lamports(vault_rent + deposit_amount).build(),
+                ],
+            ),
+        ],
+        &accounts,
+    );
+}
+
+#[test]
+fn test_withdraw_keeps_rent_buffer() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let vault_balance = vault_rent + 4_000_000_000;
+    let user_initial_balance = 1_000_000_000;
+    // Keep 50% more than the rent-exempt minimum
+    let rent_buffer_bps = 5_000;
+    let retained = vault_rent + vault_rent / 2;
+    let withdrawable = vault_balance - retained;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_rent_buffer_instruction(&owner, &state_pda, rent_buffer_bps),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[RENT_BUFFER_BPS_OFFSET..INIT_SLOT_OFFSET], rent_buffer_bps.to_le_bytes());
+    let accounts = result.resulting_accounts;
+
+    // Everything above the bare rent minimum is no longer withdrawable
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdrawable + 1),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INSUFFICIENT_FUNDS_ERROR))],
+    );
+
+    for instruction in [
+        create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdrawable),
+        create_withdraw_percent_instruction(&owner, &vault_pda, &state_pda, 10_000),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault_pda).lamports(retained).build(),
+                Check::account(&owner).lamports(user_initial_balance + withdrawable).build(),
+            ],
+        );
+    }
+}
+
+/// xorshift64, so the fuzz cases are reproducible without pulling in a rng crate
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[test]
+fn test_withdraw_boundary_fuzz() {
+    let mut mollusk = create_mollusk();
+    let mut seed: u64 = 0x5eed_0f7a_0175;
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let user_initial_balance = 1_000_000_000;
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    for case in 0..300 {
+        mollusk.sysvars.rent.lamports_per_byte_year = 1 + next_random(&mut seed) % 10_000;
+        let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+        let rent_buffer_bps = (next_random(&mut seed) % 10_001) as u16;
+        let retained = vault_rent + (vault_rent as u128 * rent_buffer_bps as u128 / 10_000) as u64;
+
+        // Balances both below and above what the vault has to keep
+        let vault_balance = next_random(&mut seed) % (retained * 3 + 10_000_000_000);
+        let withdrawable = vault_balance.saturating_sub(retained);
+        // Half the amounts land right at the boundary, the rest anywhere up to the full balance
+        let amount = match next_random(&mut seed) % 4 {
+            0 => withdrawable,
+            1 => withdrawable + 1,
+            2 => withdrawable.saturating_sub(1),
+            _ => next_random(&mut seed) % (vault_balance + 1),
+        };
+
+        let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        vault_state_data[RENT_BUFFER_BPS_OFFSET..INIT_SLOT_OFFSET].copy_from_slice(&rent_buffer_bps.to_le_bytes());
+        let vault_state_account = Account {
+            lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+            data: vault_state_data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let accounts = vec![
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+            (state_pda, vault_state_account),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ];
+
+        let result = mollusk.process_instruction(
+            &create_withdraw_instruction(&owner, &vault_pda, &state_pda, amount),
+            &accounts,
+        );
+        let context = format!("case {case}: balance {vault_balance}, amount {amount}, rent {vault_rent}, buffer {rent_buffer_bps} bps");
+        let vault_after = result.get_account(&vault_pda).unwrap().lamports;
+        let user_after = result.get_account(&owner).unwrap().lamports;
+
+        if amount <= withdrawable {
+            assert!(result.program_result.is_ok(), "{context}: {:?}", result.program_result);
+            // Exact accounting, nothing created or lost
+            assert_eq!(vault_after, vault_balance - amount, "{context}");
+            assert_eq!(user_after, user_initial_balance + amount, "{context}");
+            assert!(vault_after >= vault_rent, "{context}: vault dropped below rent");
+        } else {
+            assert_eq!(
+                result.program_result,
+                mollusk_svm::result::ProgramResult::Failure(solana_sdk::program_error::ProgramError::Custom(INSUFFICIENT_FUNDS_ERROR)),
+                "{context}"
+            );
+            assert_eq!(vault_after, vault_balance, "{context}");
+            assert_eq!(user_after, user_initial_balance, "{context}");
+        }
+    }
+}
+
+#[test]
+fn test_sandwich_guard() {
+    let mollusk = create_mollusk();
+
+    let owner_keypair = Keypair::new();
+    let owner = owner_keypair.pubkey();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let amount = 1_000_000_000;
+    let user_initial_balance = 5_000_000_000;
+    let vault_balance = 5_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_sandwich_guard_instruction(&owner, &state_pda, true),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[SANDWICH_GUARD_OFFSET], 1);
+    let accounts = result.resulting_accounts;
+
+    let withdraw = create_guarded_withdraw_instruction(&owner, &vault_pda, &state_pda, amount);
+    let deposit = create_deposit_instruction(&owner, &vault_pda, &state_pda, amount);
+    let transfer = solana_sdk::system_instruction::transfer(&owner, &vault_pda, amount);
+    let with_sysvar = |instructions: &[&Instruction], current_index: u16| {
+        let mut accounts = accounts.clone();
+        accounts.push((
+            solana_sdk::sysvar::instructions::id(),
+            create_instructions_sysvar_account(instructions, current_index),
+        ));
+        accounts
+    };
+
+    // A deposit or plain transfer into the vault ahead of the withdrawal trips the guard
+    for (case, instructions) in [("deposit", [&deposit, &withdraw]), ("system transfer", [&transfer, &withdraw])] {
+        let result = mollusk.process_instruction(&withdraw, &with_sysvar(&instructions, 1));
+        assert!(result.program_result.is_err(), "{case} then withdraw was accepted");
+        assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance, "{case}");
+    }
+
+    // Instructions that don't touch the vault, or come after the withdrawal, are fine
+    let unrelated = solana_sdk::system_instruction::transfer(&owner, &Pubkey::new_unique(), amount);
+    for instructions in [[&unrelated, &withdraw], [&withdraw, &deposit]] {
+        let current_index = instructions.iter().position(|instruction| *instruction == &withdraw).unwrap() as u16;
+        mollusk.process_and_validate_instruction(
+            &withdraw,
+            &with_sysvar(&instructions, current_index),
+            &[
+                Check::success(),
+                Check::account(&vault_pda).lamports(vault_balance - amount).build(),
+                Check::account(&owner).lamports(user_initial_balance + amount).build(),
+            ],
+        );
+    }
+
+    // Relayed approvals are guarded through the sysvar they already pass
+    let recipient = Pubkey::new_unique();
+    let expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+    let signature = create_approval_signature_instruction(&owner_keypair, &state_pda, &recipient, amount, 0, expiry, u64::MAX);
+    let approval = create_withdraw_with_approval_instruction(&recipient, &vault_pda, &state_pda, amount, 0, expiry, u64::MAX);
+    let with_recipient = |instructions: &[&Instruction], current_index: u16| {
+        let mut accounts = with_sysvar(instructions, current_index);
+        accounts.push((recipient, Account::new(0, 0, &system_program::id())));
+        accounts
+    };
+    mollusk.process_and_validate_instruction(
+        &approval,
+        &with_recipient(&[&transfer, &signature, &approval], 2),
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(WITHDRAWAL_AFTER_DEPOSIT_ERROR))],
+    );
+    mollusk.process_and_validate_instruction(
+        &approval,
+        &with_recipient(&[&signature, &approval], 1),
+        &[Check::success(), Check::account(&recipient).lamports(amount).build()],
+    );
+
+    // A guarded vault can't be withdrawn from without the sysvar to check against
+    let result = mollusk.process_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, amount),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_withdraw_percent() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    // Not divisible by 4, so 25% has to round
+    let withdrawable: u64 = 1_000_000_007;
+    set_lamports(&mut accounts, &vault_pda, vault_rent + withdrawable);
+
+    // 0%, 25% (1_000_000_007 / 4 = 250_000_001.75, rounded down) and 100%
+    for (bps, expected) in [(0, 0), (2_500, 250_000_001), (10_000, withdrawable)] {
+        mollusk.process_and_validate_instruction(
+            &create_withdraw_percent_instruction(&owner, &vault_pda, &state_pda, bps),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault_pda).lamports(vault_rent + withdrawable - expected).build(),
+                Check::account(&owner).lamports(SETUP_OWNER_BALANCE + expected).build(),
+            ],
+        );
+    }
+
+    let result = mollusk.process_instruction(
+        &create_withdraw_percent_instruction(&owner, &vault_pda, &state_pda, 10_001),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_withdraw_rounding() {
+    let mut mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let stranger = Pubkey::new_unique();
+    accounts.push((stranger, Account::new(1_000_000_000, 0, &system_program::id())));
+
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let withdrawable: u64 = 3_700_000_000;
+    let whole_sol = 1_000_000_000;
+    set_lamports(&mut accounts, &vault_pda, vault_rent + withdrawable);
+
+    // Only the owner chooses the payout unit
+    let result = mollusk.process_instruction(&create_set_round_to_instruction(&stranger, &state_pda, whole_sol), &accounts);
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_round_to_instruction(&owner, &state_pda, whole_sol),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[ROUND_TO_OFFSET..ROUND_TO_OFFSET + 8], whole_sol.to_le_bytes());
+    let accounts = result.resulting_accounts;
+
+    // 2.5 SOL pays 2 SOL, and withdrawing everything leaves the 0.7 SOL remainder in the vault
+    for (instruction, expected) in [
+        (create_withdraw_instruction(&owner, &vault_pda, &state_pda, 2_500_000_000), 2_000_000_000),
+        (create_withdraw_percent_instruction(&owner, &vault_pda, &state_pda, 10_000), 3_000_000_000),
+        (create_withdraw_instruction(&owner, &vault_pda, &state_pda, whole_sol), whole_sol),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault_pda).lamports(vault_rent + withdrawable - expected).build(),
+                Check::account(&owner).lamports(SETUP_OWNER_BALANCE + expected).build(),
+            ],
+        );
+    }
+
+    // Less than one unit would pay nothing, which fails instead
+    let result = mollusk.process_instruction(&create_withdraw_instruction(&owner, &vault_pda, &state_pda, whole_sol - 1), &accounts);
+    assert!(result.program_result.is_err());
+
+    // An escrowed withdrawal holds the rounded amount, which is what gets released
+    let release_slot = mollusk.sysvars.clock.slot + 10;
+    let result = mollusk.process_and_validate_instruction(
+        &create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &stranger, 2_500_000_000, release_slot),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(
+        result.get_account(&state_pda).unwrap().data[PENDING_AMOUNT_OFFSET..PENDING_RELEASE_SLOT_OFFSET],
+        2_000_000_000u64.to_le_bytes()
+    );
+    mollusk.warp_to_slot(release_slot);
+    mollusk.process_and_validate_instruction(
+        &create_execute_withdraw_instruction(&vault_pda, &state_pda, &stranger),
+        &result.resulting_accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_rent + withdrawable - 2_000_000_000).build(),
+            Check::account(&stranger).lamports(1_000_000_000 + 2_000_000_000).build(),
+        ],
+    );
+    let result = mollusk.process_instruction(
+        &create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &stranger, whole_sol - 1, release_slot + 10),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    // An approved withdrawal of 2.5 SOL pays 2 SOL as well
+    let approver = Keypair::new();
+    let (approver_state, approver_state_bump) = find_vault_state_pda(&approver.pubkey());
+    let (approver_vault, approver_vault_bump) = find_vault_pda(&approver_state);
+    let mut vault_state_data = create_vault_state_data(&approver.pubkey(), approver_state_bump, approver_vault_bump);
+    vault_state_data[ROUND_TO_OFFSET..ROUND_TO_OFFSET + 8].copy_from_slice(&whole_sol.to_le_bytes());
+    let expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+    let signature_instruction =
+        create_approval_signature_instruction(&approver, &approver_state, &stranger, 2_500_000_000, 0, expiry, u64::MAX);
+    let approval_instruction =
+        create_withdraw_with_approval_instruction(&stranger, &approver_vault, &approver_state, 2_500_000_000, 0, expiry, u64::MAX);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let approval_accounts = vec![
+        (stranger, Account::new(0, 0, &system_program::id())),
+        (approver_vault, Account::new(vault_rent + withdrawable, 0, &system_program::id())),
+        (
+            approver_state,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+        (
+            solana_instructions_sysvar::ID,
+            create_instructions_sysvar_account(&[&signature_instruction, &approval_instruction], 1),
+        ),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let result = mollusk.process_instruction_chain(&[signature_instruction, approval_instruction], &approval_accounts);
+    assert!(result.program_result.is_ok(), "unexpected result: {:?}", result.raw_result);
+    assert_eq!(result.get_account(&stranger).unwrap().lamports, 2_000_000_000);
+    assert_eq!(result.get_account(&approver_vault).unwrap().lamports, vault_rent + withdrawable - 2_000_000_000);
+
+    // Turned off, amounts are paid as asked
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_round_to_instruction(&owner, &state_pda, 0),
+        &accounts,
+        &[Check::success()],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, 2_500_000_000),
+        &result.resulting_accounts,
+        &[Check::success(), Check::account(&owner).lamports(SETUP_OWNER_BALANCE + 2_500_000_000).build()],
+    );
+}
+
+#[test]
+fn test_close_burns_dust_balance() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let incinerator = solana_sdk::incinerator::id();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    // An emptied vault only holds its own rent, which is below the dust threshold
+    let user_initial_balance = 5_000_000_000;
+    let vault_balance = mollusk.sysvars.rent.minimum_balance(0);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: vault_state_rent,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    // The owner opts in to burning dust first
+    let set_burn_dust_instruction = create_set_burn_dust_instruction(&owner, &state_pda, true);
+    let result = mollusk.process_and_validate_instruction(
+        &set_burn_dust_instruction,
+        &[(owner, Account::new(user_initial_balance, 0, &system_program::id())), (state_pda, vault_state_account)],
+        &[Check::success()],
+    );
+    let vault_state_account = result.get_account(&state_pda).unwrap().clone();
+    assert_eq!(vault_state_account.data[BURN_DUST_OFFSET], 1);
+
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        (incinerator, Account::default()),
+    ];
+
+    // Without the burn address the close is rejected rather than refunding the dust
+    let result = mollusk.process_instruction(&create_close_instruction(&owner, &vault_pda, &state_pda), &accounts);
+    assert!(
+        matches!(result.raw_result, Err(InstructionError::Custom(_))),
+        "unexpected result: {:?}",
+        result.raw_result
+    );
+
+    // The dust goes to the incinerator, the owner still gets the state account rent back
+    mollusk.process_and_validate_instruction(
+        &create_close_with_burn_instruction(&owner, &vault_pda, &state_pda),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&incinerator).lamports(vault_balance).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+            Check::account(&owner).lamports(user_initial_balance + vault_state_rent).build(),
+        ],
+    );
+}
+
+#[test]
+#[cfg(feature = "verbose-logs")]
+fn test_close_emits_vault_closed_event() {
+    let (mollusk, logger) = create_logging_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = 2_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: vault_state_rent,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_close_instruction(&owner, &vault_pda, &state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+    ];
+
+    mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+
+    // The refund covers both the vault balance and the closed state account's rent
+    let event = find_event_data(&logger, &VAULT_CLOSED_EVENT_DISCRIMINATOR).expect("missing VaultClosedEvent");
+    let refunded = vault_balance + vault_state_rent;
+    assert_eq!(event, [owner.to_bytes().as_slice(), &refunded.to_le_bytes()].concat());
+}
+
+#[test]
+#[cfg(not(feature = "verbose-logs"))]
+fn test_events_are_compiled_out_by_default() {
+    let (mollusk, logger) = create_logging_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let initialized = mollusk.process_and_validate_instruction(
+        &create_initialize_instruction(&owner, &state_pda, &vault_pda),
+        &[
+            (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+            (state_pda, Account::default()),
+            (vault_pda, Account::default()),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ],
+        &[Check::success()],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_close_instruction(&owner, &vault_pda, &state_pda),
+        &initialized.resulting_accounts,
+        &[Check::success()],
+    );
+
+    assert_eq!(find_event_data(&logger, &VAULT_OPENED_EVENT_DISCRIMINATOR), None);
+    assert_eq!(find_event_data(&logger, &VAULT_CLOSED_EVENT_DISCRIMINATOR), None);
+    assert!(!logger.borrow().get_recorded_content().iter().any(|line| line.starts_with("Program data: ")));
+}
+
+#[test]
+fn test_duplicated_accounts_are_rejected() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let amount = 1_000_000_000;
+
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    // Enabled so `KeeperSweepIx` gets past its own checks
+    vault_state_data[SWEEP_THRESHOLD_OFFSET..KEEPER_TIP_OFFSET].copy_from_slice(&1u64.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(5_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // One account passed for two roles. The vault's seeds, the state's program owner and
+    // discriminant and the user's system owner each pin a role to one address, so every
+    // collision fails validation before the handler runs.
+    let cases = [
+        ("deposit, state as vault", create_deposit_instruction(&owner, &state_pda, &state_pda, amount)),
+        ("deposit, vault as state", create_deposit_instruction(&owner, &vault_pda, &vault_pda, amount)),
+        ("deposit, owner as vault", create_deposit_instruction(&owner, &owner, &state_pda, amount)),
+        ("tip, state as vault", create_tip_instruction(&owner, &state_pda, &state_pda, amount)),
+        ("tip, vault as state", create_tip_instruction(&owner, &vault_pda, &vault_pda, amount)),
+        ("withdraw, state as vault", create_withdraw_instruction(&owner, &state_pda, &state_pda, amount)),
+        ("withdraw, vault as state", create_withdraw_instruction(&owner, &vault_pda, &vault_pda, amount)),
+        ("withdraw, owner as vault", create_withdraw_instruction(&owner, &owner, &state_pda, amount)),
+        ("withdraw percent, state as vault", create_withdraw_percent_instruction(&owner, &state_pda, &state_pda, 10_000)),
+        ("keeper sweep, state as vault", create_keeper_sweep_instruction(&owner, &state_pda, &state_pda, &owner)),
+        ("keeper sweep, vault as owner", create_keeper_sweep_instruction(&owner, &vault_pda, &state_pda, &vault_pda)),
+        ("close, state as vault", create_close_instruction(&owner, &state_pda, &state_pda)),
+        ("close, vault as state", create_close_instruction(&owner, &vault_pda, &vault_pda)),
+        ("close, state as user", create_close_instruction(&state_pda, &vault_pda, &state_pda)),
+        ("safe close, state as vault", create_safe_close_instruction(&owner, &state_pda, &state_pda)),
+        ("set withdraw cooldown, state as user", create_set_withdraw_cooldown_instruction(&state_pda, &state_pda, 10)),
+        ("configure, state as user", create_configure_instruction(&state_pda, &state_pda, &ConfigureIx { cooldown_slots: Some(10), ..Default::default() })),
+        ("assert balance, state as vault", create_assert_balance_instruction(&state_pda, &state_pda, 0)),
+    ];
+
+    for (case, instruction) in cases {
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.raw_result.is_err(), "{case}: accepted");
+        // A panic would mean the duplicate reached code that doesn't expect it
+        assert_ne!(result.raw_result, Err(InstructionError::ProgramFailedToComplete), "{case}");
+        for (key, account) in &accounts {
+            assert_eq!(result.get_account(key).unwrap(), account, "{case}: {key} changed");
+        }
+    }
+}
+
+#[test]
+fn test_gated_instructions_require_a_signature() {
+    let (mut mollusk, logger) = create_logging_mollusk();
+    token::add_program(&mut mollusk);
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let (global_config_pda, _) = find_global_config_pda();
+    let wsol_account = Pubkey::new_unique();
+    let yield_program = Pubkey::new_unique();
+
+    // With a pending withdrawal, so the cancel has something to cancel
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[PENDING_RECIPIENT_OFFSET..PENDING_AMOUNT_OFFSET].copy_from_slice(owner.as_ref());
+    vault_state_data[PENDING_AMOUNT_OFFSET..PENDING_RELEASE_SLOT_OFFSET].copy_from_slice(&1u64.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (GLOBAL_CONFIG_ADMIN, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(5_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (wsol_account, create_wsol_account(&mollusk, &vault_pda)),
+        (yield_program, Account::default()),
+        token::keyed_account(),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Matching the stored owner or admin is not enough, each of these has to be signed by it
+    let cases = [
+        ("deposit", create_deposit_instruction(&owner, &vault_pda, &state_pda, 1)),
+        ("wrap deposit", create_wrap_deposit_instruction(&owner, &vault_pda, &state_pda, &wsol_account, 1)),
+        ("withdraw", create_withdraw_instruction(&owner, &vault_pda, &state_pda, 1)),
+        ("withdraw percent", create_withdraw_percent_instruction(&owner, &vault_pda, &state_pda, 1)),
+        ("request withdraw", create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &owner, 1, 100)),
+        ("cancel withdraw", create_cancel_withdraw_instruction(&owner, &state_pda)),
+        ("close", create_close_instruction(&owner, &vault_pda, &state_pda)),
+        ("safe close", create_safe_close_instruction(&owner, &vault_pda, &state_pda)),
+        ("set yield program", create_set_yield_program_instruction(&owner, &state_pda, &yield_program)),
+        ("set withdraw cooldown", create_set_withdraw_cooldown_instruction(&owner, &state_pda, 1)),
+        ("set burn dust", create_set_burn_dust_instruction(&owner, &state_pda, true)),
+        ("set max deposit per tx", create_set_max_deposit_per_tx_instruction(&owner, &state_pda, 1)),
+        ("set deposit limits", create_set_deposit_limits_instruction(&owner, &state_pda, 1, 0)),
+        ("set rent buffer", create_set_rent_buffer_instruction(&owner, &state_pda, 1)),
+        ("set first withdraw delay", create_set_first_withdraw_delay_instruction(&owner, &state_pda, 1)),
+        ("set label", create_set_label_instruction(&owner, &state_pda, b"label")),
+        ("set keeper sweep", create_set_keeper_sweep_instruction(&owner, &state_pda, 1, 0)),
+        ("configure", create_configure_instruction(&owner, &state_pda, &ConfigureIx { cooldown_slots: Some(1), ..Default::default() })),
+        ("harvest", create_harvest_instruction(&owner, &vault_pda, &state_pda, &yield_program, &Instruction::new_with_bytes(yield_program, &[], vec![]))),
+        ("resize state", create_resize_state_instruction(&owner, &state_pda)),
+        ("recover state lamports", create_recover_state_lamports_instruction(&owner, &state_pda)),
+        ("normalize bump", create_normalize_bump_instruction(&owner, &vault_pda, &vault_pda, &state_pda)),
+        ("set emergency paused", create_set_emergency_paused_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, true)),
+        ("set allowlist root", create_set_allowlist_root_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, &[1; 32])),
+    ];
+
+    for (case, mut instruction) in cases {
+        for meta in &mut instruction.accounts {
+            meta.is_signer = false;
+        }
+        let seen = logger.borrow().get_recorded_content().len();
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.raw_result.is_err(), "{case}: accepted without a signature");
+        assert!(
+            logger.borrow().get_recorded_content()[seen..].iter().any(|line| line.contains("is not signed")),
+            "{case}: rejected for another reason"
+        );
+        for (key, account) in &accounts {
+            assert_eq!(result.get_account(key).unwrap(), account, "{case}: {key} changed");
+        }
+    }
+}
+
+#[test]
+fn test_withdraw_and_close_reject_vault_as_user() {
+    let mollusk = create_mollusk();
+
+    // A state whose recorded owner is its own vault PDA, so ownership checks pass with user == vault
+    let state = Pubkey::new_unique();
+    let (vault_pda, vault_bump) = find_vault_pda(&state);
+    let vault_balance = 2_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&vault_pda, 0, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // The vault is marked as a signer directly, standing in for a program signing with its seeds
+    let instructions = [
+        create_withdraw_instruction(&vault_pda, &vault_pda, &state, 500_000_000),
+        create_close_instruction(&vault_pda, &vault_pda, &state),
+    ];
+
+    for instruction in instructions {
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(
+            matches!(result.raw_result, Err(InstructionError::Custom(_))),
+            "unexpected result: {:?}",
+            result.raw_result
+        );
+    }
+}
+
+// Runs an owner-signed approval through a relayed WithdrawWithApproval, returning the mollusk result
+fn process_withdraw_with_approval(
+    mollusk: &Mollusk,
+    stored_nonce: u64,
+    approval_nonce: u64,
+    expiry: i64,
+) -> (mollusk_svm::result::InstructionResult, Pubkey, Pubkey, Pubkey) {
+    process_withdraw_with_slot_bound_approval(mollusk, stored_nonce, approval_nonce, expiry, 0, u64::MAX, false)
+}
+
+// Like `process_withdraw_with_approval`, on a vault with `max_slot_age` set and an approval valid
+// until `valid_until_slot`, optionally while the global config is paused
+fn process_withdraw_with_slot_bound_approval(
+    mollusk: &Mollusk,
+    stored_nonce: u64,
+    approval_nonce: u64,
+    expiry: i64,
+    max_slot_age: u64,
+    valid_until_slot: u64,
+    emergency_paused: bool,
+) -> (mollusk_svm::result::InstructionResult, Pubkey, Pubkey, Pubkey) {
+    let owner = Keypair::new();
+    let recipient = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner.pubkey());
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let amount = 1_000_000_000;
+
+    let mut vault_state_data = create_vault_state_data(&owner.pubkey(), state_bump, vault_bump);
+    vault_state_data[WITHDRAW_NONCE_OFFSET..COOLDOWN_SLOTS_OFFSET].copy_from_slice(&stored_nonce.to_le_bytes());
+    vault_state_data[MAX_SLOT_AGE_OFFSET..MAX_SLOT_AGE_OFFSET + 8].copy_from_slice(&max_slot_age.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let signature_instruction =
+        create_approval_signature_instruction(&owner, &state_pda, &recipient, amount, approval_nonce, expiry, valid_until_slot);
+    let withdraw_instruction = create_withdraw_with_approval_instruction(
+        &recipient,
+        &vault_pda,
+        &state_pda,
+        amount,
+        approval_nonce,
+        expiry,
+        valid_until_slot,
+    );
+    let instructions_sysvar_account =
+        create_instructions_sysvar_account(&[&signature_instruction, &withdraw_instruction], 1);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (recipient, Account::new(0, 0, &system_program::id())),
+        (vault_pda, Account::new(5_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (solana_instructions_sysvar::ID, instructions_sysvar_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(mollusk, emergency_paused),
+    ];
+
+    let result = mollusk.process_instruction_chain(&[signature_instruction, withdraw_instruction], &accounts);
+    (result, recipient, vault_pda, state_pda)
+}
+
+#[test]
+fn test_vault_pda_cannot_sign_externally() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let attacker = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = 2_000_000_000;
+
+    // Off the ed25519 curve, so there is no private key and the runtime rejects any transaction
+    // claiming the vault's signature. Only the program can sign for it, with `invoke_signed`
+    assert!(!vault_pda.is_on_curve());
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (attacker, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Mollusk doesn't check signatures, so even if the vault's signer flag got through it still
+    // authorizes nothing: every instruction checks the owner, never the vault's signature
+    let instructions = [
+        create_withdraw_instruction(&attacker, &vault_pda, &state_pda, 1_000_000_000),
+        create_close_instruction(&attacker, &vault_pda, &state_pda),
+    ];
+
+    for mut instruction in instructions {
+        instruction.accounts[1].is_signer = true;
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_err());
+        assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance);
+    }
+}
+
+#[test]
+fn test_withdraw_with_valid_approval() {
+    let mollusk = create_mollusk();
+    let expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+
+    let (result, recipient, vault_pda, state_pda) = process_withdraw_with_approval(&mollusk, 3, 3, expiry);
+    assert!(result.program_result.is_ok(), "unexpected result: {:?}", result.raw_result);
+
+    let lamports = |key: &Pubkey| result.get_account(key).unwrap().lamports;
+    assert_eq!(lamports(&recipient), 1_000_000_000);
+    assert_eq!(lamports(&vault_pda), 4_000_000_000);
+
+    // The nonce is consumed so the approval can't be submitted again
+    let state_data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(state_data[WITHDRAW_NONCE_OFFSET..COOLDOWN_SLOTS_OFFSET], 4u64.to_le_bytes());
+}
+
+#[test]
+fn test_withdraw_with_expired_approval() {
+    let mut mollusk = create_mollusk();
+    mollusk.sysvars.clock.unix_timestamp = 1_000;
+
+    let (result, ..) = process_withdraw_with_approval(&mollusk, 0, 0, 999);
+    assert!(
+        matches!(result.raw_result, Err(InstructionError::Custom(_))),
+        "unexpected result: {:?}",
+        result.raw_result
+    );
+}
+
+#[test]
+fn test_withdraw_with_slot_bound_approval() {
+    let mut mollusk = create_mollusk();
+    mollusk.sysvars.clock.slot = 1_000;
+    let expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+
+    // Fresh, and within the vault's max_slot_age of 50
+    let (result, recipient, ..) = process_withdraw_with_slot_bound_approval(&mollusk, 0, 0, expiry, 50, 1_010, false);
+    assert!(result.program_result.is_ok(), "unexpected result: {:?}", result.raw_result);
+    assert_eq!(result.get_account(&recipient).unwrap().lamports, 1_000_000_000);
+
+    // Landing after valid_until_slot, or valid for longer than max_slot_age, is rejected
+    for (max_slot_age, valid_until_slot) in [(50, 999), (0, 999), (50, 1_051)] {
+        let (result, ..) =
+            process_withdraw_with_slot_bound_approval(&mollusk, 0, 0, expiry, max_slot_age, valid_until_slot, false);
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::Custom(INVALID_APPROVAL_ERROR)),
+            "max_slot_age {max_slot_age}, valid until {valid_until_slot}"
+        );
+    }
+}
+
+#[test]
+fn test_set_log_level() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, _, mut accounts) = setup_initialized_vault(&mollusk);
+    let stranger = Pubkey::new_unique();
+    accounts.push((stranger, Account::new(1_000_000_000, 0, &system_program::id())));
+
+    let result = mollusk.process_instruction(&create_set_log_level_instruction(&stranger, &state_pda, 1), &accounts);
+    assert!(result.program_result.is_err());
+    // Only the known levels
+    mollusk.process_and_validate_instruction(
+        &create_set_log_level_instruction(&owner, &state_pda, 3),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_ARGUMENT_ERROR))],
+    );
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_log_level_instruction(&owner, &state_pda, 2),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[LOG_LEVEL_OFFSET], 2);
+}
+
+// Detailed logs every event, summary only the vault opening and closing, silent none
+#[test]
+#[cfg(feature = "verbose-logs")]
+fn test_log_levels() {
+    for (log_level, logs_tip, logs_close) in [(0, true, true), (1, false, true), (2, false, false)] {
+        let (mollusk, logger) = create_logging_mollusk();
+        let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+        accounts[2].1.data[LOG_LEVEL_OFFSET] = log_level;
+
+        let result = mollusk.process_and_validate_instruction(
+            &create_tip_instruction(&owner, &vault_pda, &state_pda, 100_000_000),
+            &accounts,
+            &[Check::success()],
+        );
+        mollusk.process_and_validate_instruction(
+            &create_close_instruction(&owner, &vault_pda, &state_pda),
+            &result.resulting_accounts,
+            &[Check::success()],
+        );
+        assert_eq!(find_event_data(&logger, &TIP_EVENT_DISCRIMINATOR).is_some(), logs_tip, "log level {log_level}");
+        assert_eq!(find_event_data(&logger, &VAULT_CLOSED_EVENT_DISCRIMINATOR).is_some(), logs_close, "log level {log_level}");
+    }
+}
+
+#[test]
+fn test_set_max_slot_age() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, _, mut accounts) = setup_initialized_vault(&mollusk);
+    let stranger = Pubkey::new_unique();
+    accounts.push((stranger, Account::new(1_000_000_000, 0, &system_program::id())));
+
+    let result = mollusk.process_instruction(&create_set_max_slot_age_instruction(&stranger, &state_pda, 150), &accounts);
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_max_slot_age_instruction(&owner, &state_pda, 150),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[MAX_SLOT_AGE_OFFSET..MAX_SLOT_AGE_OFFSET + 8], 150u64.to_le_bytes());
+}
+
+#[test]
+fn test_withdraw_with_replayed_approval() {
+    let mollusk = create_mollusk();
+    let expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+
+    // Nonce 0 was already used, the state expects 1 next
+    let (result, ..) = process_withdraw_with_approval(&mollusk, 1, 0, expiry);
+    assert!(
+        matches!(result.raw_result, Err(InstructionError::Custom(_))),
+        "unexpected result: {:?}",
+        result.raw_result
+    );
+}
+
+#[test]
+fn test_escrowed_withdraw() {
+    let mut mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = mollusk.sysvars.rent.minimum_balance(0) + 4_000_000_000;
+    let amount = 3_000_000_000;
+    let recipient_initial_balance = 1_000_000_000;
+    let request_slot = 100;
+    let release_slot = 150;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (recipient, Account::new(recipient_initial_balance, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.warp_to_slot(request_slot);
+    let result = mollusk.process_and_validate_instruction(
+        &create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &recipient, amount, release_slot),
+        &accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(vault_balance).build()],
+    );
+    let data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(data[PENDING_RECIPIENT_OFFSET..PENDING_AMOUNT_OFFSET], recipient.to_bytes());
+    assert_eq!(data[PENDING_AMOUNT_OFFSET..PENDING_RELEASE_SLOT_OFFSET], amount.to_le_bytes());
+    assert_eq!(data[PENDING_RELEASE_SLOT_OFFSET..SANDWICH_GUARD_OFFSET], release_slot.to_le_bytes());
+    let accounts = result.resulting_accounts;
+
+    // The held amount can't be withdrawn another way, and only one request can be pending
+    let result = mollusk.process_instruction(&create_withdraw_instruction(&owner, &vault_pda, &state_pda, amount), &accounts);
+    assert_eq!(result.raw_result, Err(InstructionError::Custom(INSUFFICIENT_FUNDS_ERROR)));
+    let result = mollusk.process_instruction(
+        &create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &recipient, 1, release_slot),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    let execute = create_execute_withdraw_instruction(&vault_pda, &state_pda, &recipient);
+
+    // Too early
+    mollusk.warp_to_slot(release_slot - 1);
+    let result = mollusk.process_instruction(&execute, &accounts);
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance);
+
+    // Only to the recorded recipient
+    let result = mollusk.process_instruction(
+        &create_execute_withdraw_instruction(&vault_pda, &state_pda, &owner),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    mollusk.warp_to_slot(release_slot);
+    let result = mollusk.process_and_validate_instruction(
+        &execute,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_balance - amount).build(),
+            Check::account(&recipient).lamports(recipient_initial_balance + amount).build(),
+        ],
+    );
+    assert!(result.get_account(&state_pda).unwrap().data[PENDING_RECIPIENT_OFFSET..].iter().all(|&byte| byte == 0));
+
+    // Released once
+    let result = mollusk.process_instruction(&execute, &result.resulting_accounts);
+    assert!(result.program_result.is_err());
+}
+
+// Closing would release the held lamports early, so no close works until the pending withdrawal
+// is executed or cancelled
+#[test]
+fn test_close_with_pending_withdraw() {
+    let mut mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let recipient = Pubkey::new_unique();
+    let vault_balance = mollusk.sysvars.rent.minimum_balance(0) + 4_000_000_000;
+    let amount = 3_000_000_000;
+    let release_slot = 150;
+    set_lamports(&mut accounts, &vault_pda, vault_balance);
+    accounts.push((recipient, Account::new(1_000_000_000, 0, &system_program::id())));
+    let state_rent = accounts[2].1.lamports;
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &recipient, amount, release_slot),
+        &accounts,
+        &[Check::success()],
+    );
+    let pending_accounts = result.resulting_accounts;
+
+    let closes = [
+        create_close_instruction(&owner, &vault_pda, &state_pda),
+        create_safe_close_instruction(&owner, &vault_pda, &state_pda),
+        create_close_many_instruction(&owner, &[(vault_pda, state_pda)]),
+    ];
+    for close in &closes {
+        let result = mollusk.process_and_validate_instruction(
+            close,
+            &pending_accounts,
+            &[Check::err(solana_sdk::program_error::ProgramError::Custom(WITHDRAWAL_PENDING_ERROR))],
+        );
+        assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance);
+    }
+    // Not even once the release slot has passed
+    mollusk.warp_to_slot(release_slot);
+    let result = mollusk.process_instruction(&closes[0], &pending_accounts);
+    assert_eq!(result.raw_result, Err(InstructionError::Custom(WITHDRAWAL_PENDING_ERROR)));
+
+    // Cancelled, the whole vault comes back on close
+    let result = mollusk.process_and_validate_instruction(
+        &create_cancel_withdraw_instruction(&owner, &state_pda),
+        &pending_accounts,
+        &[Check::success()],
+    );
+    mollusk.process_and_validate_instruction(
+        &closes[0],
+        &result.resulting_accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE + vault_balance + state_rent).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+        ],
+    );
+
+    // Executed, the rest of the vault comes back on close
+    let result = mollusk.process_and_validate_instruction(
+        &create_execute_withdraw_instruction(&vault_pda, &state_pda, &recipient),
+        &pending_accounts,
+        &[Check::success()],
+    );
+    mollusk.process_and_validate_instruction(
+        &closes[0],
+        &result.resulting_accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE + vault_balance - amount + state_rent).build(),
+            Check::account(&recipient).lamports(1_000_000_000 + amount).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_cancel_escrowed_withdraw() {
+    let mut mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = mollusk.sysvars.rent.minimum_balance(0) + 4_000_000_000;
+    let amount = 3_000_000_000;
+    let release_slot = 150;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (recipient, Account::new(1_000_000_000, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &recipient, amount, release_slot),
+        &accounts,
+        &[Check::success()],
+    );
+    let accounts = result.resulting_accounts;
+
+    // Only the owner can cancel
+    let result = mollusk.process_instruction(&create_cancel_withdraw_instruction(&recipient, &state_pda), &accounts);
+    assert!(result.program_result.is_err());
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_cancel_withdraw_instruction(&owner, &state_pda),
+        &accounts,
+        &[Check::success()],
+    );
+    assert!(result.get_account(&state_pda).unwrap().data[PENDING_RECIPIENT_OFFSET..].iter().all(|&byte| byte == 0));
+    let accounts = result.resulting_accounts;
+
+    // Nothing left to release, and the held lamports are withdrawable again
+    mollusk.warp_to_slot(release_slot);
+    let result = mollusk.process_instruction(&create_execute_withdraw_instruction(&vault_pda, &state_pda, &recipient), &accounts);
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance);
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, amount),
+        &accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(vault_balance - amount).build()],
+    );
+}
+
+#[test]
+fn test_withdraw_cooldown() {
+    let mut mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let cooldown_slots: u64 = 100;
+    let last_withdraw_slot: u64 = 1_000;
+    let withdraw_amount = 500_000_000;
+
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[LAST_WITHDRAW_SLOT_OFFSET..BURN_DUST_OFFSET].copy_from_slice(&last_withdraw_slot.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    // The owner turns the cooldown on first
+    let set_cooldown_instruction = create_set_withdraw_cooldown_instruction(&owner, &state_pda, cooldown_slots);
+    let result = mollusk.process_and_validate_instruction(
+        &set_cooldown_instruction,
+        &[(owner, Account::new(5_000_000_000, 0, &system_program::id())), (state_pda, vault_state_account)],
+        &[Check::success()],
+    );
+    let vault_state_account = result.get_account(&state_pda).unwrap().clone();
+    assert_eq!(vault_state_account.data[COOLDOWN_SLOTS_OFFSET..LAST_WITHDRAW_SLOT_OFFSET], cooldown_slots.to_le_bytes());
+
+    let instruction = create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdraw_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Halfway through the cooldown the withdrawal is rejected
+    mollusk.warp_to_slot(last_withdraw_slot + cooldown_slots / 2);
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(
+        matches!(result.raw_result, Err(InstructionError::Custom(_))),
+        "unexpected result: {:?}",
+        result.raw_result
+    );
+
+    // Once it has elapsed the withdrawal goes through and restarts the cooldown
+    let allowed_slot = last_withdraw_slot + cooldown_slots;
+    mollusk.warp_to_slot(allowed_slot);
+    let result = mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(3_000_000_000 - withdraw_amount).build(),
+        ],
+    );
+    let state_data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(state_data[LAST_WITHDRAW_SLOT_OFFSET..BURN_DUST_OFFSET], allowed_slot.to_le_bytes());
+}
+
+#[test]
+fn test_first_withdraw_delay() {
+    let mut mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let init_slot: u64 = 1_000;
+    let first_withdraw_delay: u64 = 200;
+    let withdraw_amount = 500_000_000;
+
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[INIT_SLOT_OFFSET..FIRST_WITHDRAW_DELAY_OFFSET].copy_from_slice(&init_slot.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let owner_account = Account::new(5_000_000_000, 0, &system_program::id());
+
+    // The owner commits to the delay, after which it can't be lowered
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_first_withdraw_delay_instruction(&owner, &state_pda, first_withdraw_delay),
+        &[(owner, owner_account.clone()), (state_pda, vault_state_account)],
+        &[Check::success()],
+    );
+    let vault_state_account = result.get_account(&state_pda).unwrap().clone();
+    assert_eq!(vault_state_account.data[FIRST_WITHDRAW_DELAY_OFFSET..LABEL_OFFSET], first_withdraw_delay.to_le_bytes());
+    let result = mollusk.process_instruction(
+        &create_set_first_withdraw_delay_instruction(&owner, &state_pda, first_withdraw_delay - 1),
+        &[(owner, owner_account.clone()), (state_pda, vault_state_account.clone())],
+    );
+    assert!(result.program_result.is_err());
+
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, owner_account),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let withdraw_instruction = create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdraw_amount);
+    let close_instruction = create_close_instruction(&owner, &vault_pda, &state_pda);
+
+    // Before the delay has passed neither a withdrawal nor a close goes through
+    mollusk.warp_to_slot(init_slot + first_withdraw_delay - 1);
+    for instruction in [&withdraw_instruction, &close_instruction] {
+        let result = mollusk.process_instruction(instruction, &accounts);
+        assert!(result.program_result.is_err());
+        assert_eq!(result.get_account(&vault_pda).unwrap().lamports, 3_000_000_000);
+    }
+
+    mollusk.warp_to_slot(init_slot + first_withdraw_delay);
+    mollusk.process_and_validate_instruction(
+        &withdraw_instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(3_000_000_000 - withdraw_amount).build(),
+        ],
+    );
+    mollusk.process_and_validate_instruction(
+        &close_instruction,
+        &accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(0).build()],
+    );
+}
+
+#[test]
+fn test_vault_metadata() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let (metadata_pda, _) = find_vault_metadata_pda(&state_pda);
+    let stranger = Pubkey::new_unique();
+    let metadata_rent = mollusk.sysvars.rent.minimum_balance(VAULT_METADATA_LEN);
+    let vault_rent = accounts[1].1.lamports;
+    let state_rent = accounts[2].1.lamports;
+    accounts.push((metadata_pda, Account::default()));
+    accounts.push((stranger, Account::new(1_000_000_000, 0, &system_program::id())));
+
+    // The first write opens the metadata account at the owner's expense
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_metadata_instruction(&owner, &state_pda, b"{\"color\":\"blue\"}"),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&metadata_pda).lamports(metadata_rent).owner(&PROGRAM_ID).space(VAULT_METADATA_LEN).build(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE - metadata_rent).build(),
+        ],
+    );
+    let accounts = result.resulting_accounts;
+    let data = &accounts.iter().find(|(key, _)| *key == metadata_pda).unwrap().1.data;
+    assert_eq!(data[..8], VAULT_METADATA_DISCRIMINATOR);
+    let metadata: &VaultMetadata = bytemuck::from_bytes(&data[8..]);
+    assert_eq!(metadata.state, state_pda.to_bytes().into());
+    assert_eq!(metadata.metadata(), b"{\"color\":\"blue\"}");
+    assert_eq!(accounts[2].1.data[HAS_METADATA_OFFSET], 1);
+
+    // Later writes replace it in place, padding out what the shorter blob no longer covers
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_metadata_instruction(&owner, &state_pda, b"red"),
+        &accounts,
+        &[Check::success(), Check::account(&owner).lamports(SETUP_OWNER_BALANCE - metadata_rent).build()],
+    );
+    let metadata: &VaultMetadata = bytemuck::from_bytes(&result.get_account(&metadata_pda).unwrap().data[8..]);
+    assert_eq!(metadata.metadata(), b"red");
+    assert!(metadata.data[3..].iter().all(|&byte| byte == 0));
+    let accounts = result.resulting_accounts;
+
+    // Only the owner, and only up to 256 bytes
+    mollusk.process_and_validate_instruction(
+        &create_set_metadata_instruction(&owner, &state_pda, &[1; 257]),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_ARGUMENT_ERROR))],
+    );
+    for instruction in [
+        create_set_metadata_instruction(&stranger, &state_pda, b"stolen"),
+        create_clear_metadata_instruction(&stranger, &state_pda),
+    ] {
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_err());
+    }
+
+    // Closing the vault has to close the metadata too, refunding its rent with the rest
+    let result = mollusk.process_instruction(&create_close_instruction(&owner, &vault_pda, &state_pda), &accounts);
+    assert!(result.program_result.is_err());
+    mollusk.process_and_validate_instruction(
+        &create_close_many_instruction(&owner, &[(vault_pda, state_pda)]),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_STATE_ERROR))],
+    );
+    let result = mollusk.process_and_validate_instruction(
+        &create_close_with_metadata_instruction(&owner, &vault_pda, &state_pda),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE + vault_rent + state_rent).build(),
+            Check::account(&metadata_pda).lamports(0).build(),
+            Check::account(&state_pda).lamports(0).build(),
+        ],
+    );
+    let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+    assert_eq!(refunded, vault_rent + state_rent + metadata_rent);
+
+    // Clearing closes the metadata account on its own, the vault closes normally after
+    let result = mollusk.process_and_validate_instruction(
+        &create_clear_metadata_instruction(&owner, &state_pda),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE).build(),
+            Check::account(&metadata_pda).lamports(0).build(),
+        ],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[HAS_METADATA_OFFSET], 0);
+    mollusk.process_and_validate_instruction(
+        &create_close_instruction(&owner, &vault_pda, &state_pda),
+        &result.resulting_accounts,
+        &[Check::success()],
+    );
+}
+
+#[test]
+fn test_set_label() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+    ];
+
+    // Stored null padded, and read back without the padding
+    let label = "Rainy day fund ☔";
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_label_instruction(&owner, &state_pda, label.as_bytes()),
+        &accounts,
+        &[Check::success()],
+    );
+    let data = &result.get_account(&state_pda).unwrap().data;
+    let mut expected = [0; 32];
+    expected[..label.len()].copy_from_slice(label.as_bytes());
+    assert_eq!(data[LABEL_OFFSET..SWEEP_THRESHOLD_OFFSET], expected);
+    let state: &VaultState = bytemuck::from_bytes(&data[8..]);
+    assert_eq!(state.label().unwrap(), label);
+
+    // Over-length labels are rejected rather than truncated, as are bytes wallets can't show
+    let invalid: [&[u8]; 4] = [&[b'a'; 33], b"line\nbreak", b"nul\0", &[0xff, 0xfe]];
+    for label in invalid {
+        let result = mollusk.process_instruction(&create_set_label_instruction(&owner, &state_pda, label), &accounts);
+        assert!(result.program_result.is_err(), "{label:?} was accepted");
+    }
+}
+
+#[test]
+fn test_keeper_sweep() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let keeper = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let sweep_threshold = 1_000_000_000;
+    let keeper_tip = 10_000_000;
+    let owner_balance = 1_000_000_000;
+    let keeper_balance = 100_000_000;
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_keeper_sweep_instruction(&owner, &state_pda, sweep_threshold, keeper_tip),
+        &[(owner, Account::new(owner_balance, 0, &system_program::id())), (state_pda, vault_state_account)],
+        &[Check::success()],
+    );
+    let vault_state_account = result.get_account(&state_pda).unwrap().clone();
+    assert_eq!(vault_state_account.data[SWEEP_THRESHOLD_OFFSET..KEEPER_TIP_OFFSET], sweep_threshold.to_le_bytes());
+    assert_eq!(vault_state_account.data[KEEPER_TIP_OFFSET..PENDING_RECIPIENT_OFFSET], keeper_tip.to_le_bytes());
+
+    let instruction = create_keeper_sweep_instruction(&keeper, &vault_pda, &state_pda, &owner);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts_with_vault_balance = |vault_balance: u64| {
+        vec![
+            (keeper, Account::new(keeper_balance, 0, &system_program::id())),
+            (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+            (state_pda, vault_state_account.clone()),
+            (owner, Account::new(owner_balance, 0, &system_program::id())),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ]
+    };
+
+    // At or below the threshold the sweep succeeds without moving anything
+    let vault_balance = vault_rent + sweep_threshold;
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts_with_vault_balance(vault_balance),
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_balance).build(),
+            Check::account(&owner).lamports(owner_balance).build(),
+            Check::account(&keeper).lamports(keeper_balance).build(),
+        ],
+    );
+
+    // Above it the excess goes to the owner, less the keeper's tip
+    let excess = 2_000_000_000;
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts_with_vault_balance(vault_rent + sweep_threshold + excess),
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_rent + sweep_threshold).build(),
+            Check::account(&owner).lamports(owner_balance + excess - keeper_tip).build(),
+            Check::account(&keeper).lamports(keeper_balance + keeper_tip).build(),
+        ],
+    );
+
+    // The keeper can't redirect the sweep to itself
+    let result = mollusk.process_instruction(
+        &create_keeper_sweep_instruction(&keeper, &vault_pda, &state_pda, &keeper),
+        &accounts_with_vault_balance(vault_rent + sweep_threshold + excess),
+    );
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_configure_updates_only_given_fields() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+    let min_deposit: u64 = 1_000;
+    let first_withdraw_delay: u64 = 50;
+
+    // Start from a vault with settings of its own, which must survive untouched
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[MIN_DEPOSIT_OFFSET..RENT_BUFFER_BPS_OFFSET].copy_from_slice(&min_deposit.to_le_bytes());
+    vault_state_data[FIRST_WITHDRAW_DELAY_OFFSET..LABEL_OFFSET].copy_from_slice(&first_withdraw_delay.to_le_bytes());
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data.clone(),
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let owner_account = Account::new(1_000_000_000, 0, &system_program::id());
+
+    // One field
+    let cooldown_slots: u64 = 10;
+    let mut expected = vault_state_data.clone();
+    expected[COOLDOWN_SLOTS_OFFSET..LAST_WITHDRAW_SLOT_OFFSET].copy_from_slice(&cooldown_slots.to_le_bytes());
+    let result = mollusk.process_and_validate_instruction(
+        &create_configure_instruction(&owner, &state_pda, &ConfigureIx { cooldown_slots: Some(cooldown_slots), ..Default::default() }),
+        &[(owner, owner_account.clone()), (state_pda, vault_state_account)],
+        &[Check::success(), Check::account(&state_pda).data(&expected).build()],
+    );
+    let vault_state_account = result.get_account(&state_pda).unwrap().clone();
+
+    // Several fields at once
+    let max_deposit_per_tx: u64 = 5_000_000_000;
+    let label = b"Savings";
+    let configure = ConfigureIx {
+        burn_dust: Some(true),
+        max_deposit_per_tx: Some(max_deposit_per_tx),
+        label: Some(label.to_vec()),
+        ..Default::default()
+    };
+    expected[BURN_DUST_OFFSET] = 1;
+    expected[MAX_DEPOSIT_PER_TX_OFFSET..BALANCE_CAP_OFFSET].copy_from_slice(&max_deposit_per_tx.to_le_bytes());
+    expected[LABEL_OFFSET..LABEL_OFFSET + label.len()].copy_from_slice(label);
+    let result = mollusk.process_and_validate_instruction(
+        &create_configure_instruction(&owner, &state_pda, &configure),
+        &[(owner, owner_account.clone()), (state_pda, vault_state_account)],
+        &[Check::success(), Check::account(&state_pda).data(&expected).build()],
+    );
+    let vault_state_account = result.get_account(&state_pda).unwrap().clone();
+
+    // A field failing its check rejects the whole update, valid fields included
+    let configure = ConfigureIx {
+        cooldown_slots: Some(cooldown_slots * 2),
+        first_withdraw_delay: Some(first_withdraw_delay - 1),
+        ..Default::default()
+    };
+    let result = mollusk.process_instruction(
+        &create_configure_instruction(&owner, &state_pda, &configure),
+        &[(owner, owner_account), (state_pda, vault_state_account)],
+    );
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&state_pda).unwrap().data, expected);
+}
+
+#[test]
+fn test_admin_role_separation() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let admin = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (admin, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Until an admin is set the owner holds the role, and only the owner can hand it over
+    let result = mollusk.process_instruction(&create_set_admin_instruction(&admin, &state_pda, &admin), &accounts);
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_admin_instruction(&owner, &state_pda, &admin),
+        &accounts,
+        &[Check::success()],
+    );
+    let accounts = result.resulting_accounts;
+    assert_eq!(accounts[3].1.data[ADMIN_OFFSET..ADMIN_OFFSET + 32], admin.to_bytes());
+
+    // The admin sets limits, the owner no longer can
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_max_deposit_per_tx_instruction(&admin, &state_pda, 500_000_000),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.resulting_accounts[3].1.data[MAX_DEPOSIT_PER_TX_OFFSET..BALANCE_CAP_OFFSET], 500_000_000u64.to_le_bytes());
+    for instruction in [
+        create_set_max_deposit_per_tx_instruction(&owner, &state_pda, 0),
+        create_set_deposit_limits_instruction(&owner, &state_pda, 0, 0),
+        create_configure_instruction(&owner, &state_pda, &ConfigureIx { max_deposit_per_tx: Some(0), ..Default::default() }),
+    ] {
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_err());
+    }
+
+    // Settings that could hold back withdrawals stay with the owner, so the admin can't lock the
+    // owner's funds away
+    for instruction in [
+        create_set_withdraw_cooldown_instruction(&admin, &state_pda, u64::MAX),
+        create_set_first_withdraw_delay_instruction(&admin, &state_pda, u64::MAX),
+        create_configure_instruction(&admin, &state_pda, &ConfigureIx { cooldown_slots: Some(u64::MAX), ..Default::default() }),
+        create_configure_instruction(&admin, &state_pda, &ConfigureIx { first_withdraw_delay: Some(u64::MAX), ..Default::default() }),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::err(solana_sdk::program_error::ProgramError::Custom(UNAUTHORIZED_ERROR))],
+        );
+    }
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_withdraw_cooldown_instruction(&owner, &state_pda, 10),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.resulting_accounts[3].1.data[COOLDOWN_SLOTS_OFFSET..COOLDOWN_SLOTS_OFFSET + 8], 10u64.to_le_bytes());
+
+    // Owner-only settings stay with the owner, even through `ConfigureIx`
+    let result = mollusk.process_instruction(
+        &create_configure_instruction(&admin, &state_pda, &ConfigureIx { label: Some(b"Managed".to_vec()), ..Default::default() }),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    // The keeper tip is paid out of the vault, so the admin can't set it to the whole balance and
+    // then sweep it to themselves
+    for instruction in [
+        create_set_keeper_sweep_instruction(&admin, &state_pda, 1, u64::MAX),
+        create_configure_instruction(
+            &admin,
+            &state_pda,
+            &ConfigureIx { sweep_threshold: Some(1), keeper_tip: Some(u64::MAX), ..Default::default() },
+        ),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::err(solana_sdk::program_error::ProgramError::Custom(UNAUTHORIZED_ERROR))],
+        );
+    }
+    let result = mollusk.process_instruction(&create_keeper_sweep_instruction(&admin, &vault_pda, &state_pda, &owner), &accounts);
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&vault_pda).unwrap().lamports, 3_000_000_000);
+
+    // Only the owner moves funds
+    let result = mollusk.process_instruction(&create_withdraw_instruction(&admin, &vault_pda, &state_pda, 1_000_000_000), &accounts);
+    assert!(result.program_result.is_err());
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000),
+        &accounts,
+        &[Check::success(), Check::account(&owner).lamports(2_000_000_000).build()],
+    );
+
+    // The admin can give the role back, and the owner can revoke it without the admin
+    for signer in [admin, owner] {
+        let result = mollusk.process_and_validate_instruction(
+            &create_set_admin_instruction(&signer, &state_pda, &owner),
+            &accounts,
+            &[Check::success()],
+        );
+        assert_eq!(result.resulting_accounts[3].1.data[ADMIN_OFFSET..ADMIN_OFFSET + 32], [0; 32]);
+    }
+}
+
+#[test]
+fn test_withdraw_policy() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    // Opened at slot 0, so a delay or cooldown of 100 slots is still running
+    let accounts_with = |first_withdraw_delay: u64, cooldown_slots: u64, policy: WithdrawPolicy| {
+        let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        vault_state_data[FIRST_WITHDRAW_DELAY_OFFSET..LABEL_OFFSET].copy_from_slice(&first_withdraw_delay.to_le_bytes());
+        vault_state_data[COOLDOWN_SLOTS_OFFSET..LAST_WITHDRAW_SLOT_OFFSET].copy_from_slice(&cooldown_slots.to_le_bytes());
+        vault_state_data[WITHDRAW_POLICY_OFFSET] = policy as u8;
+        vec![
+            (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+            (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+            (
+                state_pda,
+                Account {
+                    lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                    data: vault_state_data,
+                    owner: PROGRAM_ID,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            ),
+            (recipient, Account::new(1_000_000_000, 0, &system_program::id())),
+            (PROGRAM_ID, mollusk_svm::program::create_program_account_loader_v3(&PROGRAM_ID)),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ]
+    };
+    let withdraw = create_withdraw_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000);
+
+    // Each policy runs only its own guard
+    for (policy, timelocked, rate_limited) in [
+        (WithdrawPolicy::All, true, true),
+        (WithdrawPolicy::None, false, false),
+        (WithdrawPolicy::Timelock, true, false),
+        (WithdrawPolicy::Whitelist, false, false),
+        (WithdrawPolicy::RateLimit, false, true),
+    ] {
+        for (accounts, locked) in [(accounts_with(100, 0, policy), timelocked), (accounts_with(0, 100, policy), rate_limited)] {
+            let check = if locked {
+                Check::err(solana_sdk::program_error::ProgramError::Custom(WITHDRAWAL_LOCKED_ERROR))
+            } else {
+                Check::success()
+            };
+            mollusk.process_and_validate_instruction(&withdraw, &accounts, &[check]);
+        }
+    }
+
+    // The admin can't pick a policy that drops the owner's cooldown, only the owner sets it
+    let admin = Pubkey::new_unique();
+    let mut accounts = accounts_with(0, 100, WithdrawPolicy::All);
+    accounts[2].1.data[ADMIN_OFFSET..ADMIN_OFFSET + 32].copy_from_slice(admin.as_ref());
+    accounts.push((admin, Account::new(1_000_000_000, 0, &system_program::id())));
+    mollusk.process_and_validate_instruction(
+        &create_set_withdraw_policy_instruction(&admin, &state_pda, WithdrawPolicy::None as u8),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(UNAUTHORIZED_ERROR))],
+    );
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_withdraw_policy_instruction(&owner, &state_pda, WithdrawPolicy::None as u8),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[WITHDRAW_POLICY_OFFSET], WithdrawPolicy::None as u8);
+
+    // Unknown policies are rejected, and a running delay can't be dropped by switching policy
+    let accounts = accounts_with(100, 0, WithdrawPolicy::All);
+    let result = mollusk.process_instruction(&create_set_withdraw_policy_instruction(&owner, &state_pda, 5), &accounts);
+    assert!(result.program_result.is_err());
+    for policy in [WithdrawPolicy::None, WithdrawPolicy::Whitelist, WithdrawPolicy::RateLimit] {
+        mollusk.process_and_validate_instruction(
+            &create_set_withdraw_policy_instruction(&owner, &state_pda, policy as u8),
+            &accounts,
+            &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_STATE_ERROR))],
+        );
+    }
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_withdraw_policy_instruction(&owner, &state_pda, WithdrawPolicy::Timelock as u8),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[WITHDRAW_POLICY_OFFSET], WithdrawPolicy::Timelock as u8);
+
+    // Binding needs a policy that runs the whitelist, which then can't be switched off
+    let accounts = accounts_with(0, 0, WithdrawPolicy::None);
+    mollusk.process_and_validate_instruction(
+        &create_bind_recipient_instruction(&owner, &state_pda, &recipient),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_STATE_ERROR))],
+    );
+    let accounts = accounts_with(0, 0, WithdrawPolicy::Whitelist);
+    let result = mollusk.process_and_validate_instruction(
+        &create_bind_recipient_instruction(&owner, &state_pda, &recipient),
+        &accounts,
+        &[Check::success()],
+    );
+    let accounts = result.resulting_accounts;
+    mollusk.process_and_validate_instruction(
+        &create_bound_withdraw_instruction(&owner, &vault_pda, &state_pda, &owner, 1_000_000_000),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(RECIPIENT_NOT_ALLOWED_ERROR))],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_set_withdraw_policy_instruction(&owner, &state_pda, WithdrawPolicy::None as u8),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_STATE_ERROR))],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_bound_withdraw_instruction(&owner, &vault_pda, &state_pda, &recipient, 1_000_000_000),
+        &accounts,
+        &[Check::success(), Check::account(&recipient).lamports(2_000_000_000).build()],
+    );
+}
+
+#[test]
+fn test_auto_forward() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let spending_wallet = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let tipper = Pubkey::new_unique();
+    accounts.push((spending_wallet, Account::new(0, 0, &system_program::id())));
+    accounts.push((other, Account::new(0, 0, &system_program::id())));
+    accounts.push((tipper, Account::new(10_000_000_000, 0, &system_program::id())));
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let amount = 1_000_000_000;
+
+    // Disabled, deposits stay in the vault
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, amount),
+        &accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(vault_rent + amount).build()],
+    );
+
+    // Only the owner picks where deposits go
+    let result = mollusk.process_instruction(&create_set_auto_forward_instruction(&other, &state_pda, &other), &accounts);
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_auto_forward_instruction(&owner, &state_pda, &spending_wallet),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[AUTO_FORWARD_OFFSET..ROUND_TO_OFFSET], spending_wallet.to_bytes());
+    let accounts = result.resulting_accounts;
+
+    // Enabled, the deposit passes through and the vault stays at rent
+    mollusk.process_and_validate_instruction(
+        &create_forwarded_deposit_instruction(&owner, &vault_pda, &state_pda, &spending_wallet, amount),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE - amount).build(),
+            Check::account(&vault_pda).lamports(vault_rent).build(),
+            Check::account(&spending_wallet).lamports(amount).build(),
+        ],
+    );
+    let result = mollusk.process_instruction(&create_deposit_instruction(&owner, &vault_pda, &state_pda, amount), &accounts);
+    assert!(result.program_result.is_err());
+    mollusk.process_and_validate_instruction(
+        &create_forwarded_deposit_instruction(&owner, &vault_pda, &state_pda, &other, amount),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(RECIPIENT_NOT_ALLOWED_ERROR))],
+    );
+
+    // Tips and deposits from other funders pass through as well
+    let mut tip = create_tip_instruction(&tipper, &vault_pda, &state_pda, amount);
+    let result = mollusk.process_instruction(&tip, &accounts);
+    assert!(result.program_result.is_err());
+    tip.accounts.push(AccountMeta::new(spending_wallet, false));
+    mollusk.process_and_validate_instruction(
+        &tip,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_rent).build(),
+            Check::account(&spending_wallet).lamports(amount).build(),
+        ],
+    );
+    let mut deposit_to_owner = create_deposit_to_owner_instruction(&tipper, &vault_pda, &state_pda, &owner, amount);
+    let result = mollusk.process_instruction(&deposit_to_owner, &accounts);
+    assert!(result.program_result.is_err());
+    deposit_to_owner.accounts.push(AccountMeta::new(spending_wallet, false));
+    mollusk.process_and_validate_instruction(
+        &deposit_to_owner,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_rent).build(),
+            Check::account(&spending_wallet).lamports(amount).build(),
+        ],
+    );
+
+    // A bound vault can only forward to its recipient
+    mollusk.process_and_validate_instruction(
+        &create_bind_recipient_instruction(&owner, &state_pda, &other),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_STATE_ERROR))],
+    );
+
+    // Turned off again, deposits grow the vault
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_auto_forward_instruction(&owner, &state_pda, &Pubkey::default()),
+        &accounts,
+        &[Check::success()],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, amount),
+        &result.resulting_accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(vault_rent + amount).build()],
+    );
+}
+
+#[test]
+fn test_guardian_recovery() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let new_owner = Pubkey::new_unique();
+    let guardians = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let stranger = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+
+    // Settings whoever took the lost key may have pointed at themselves
+    let thief = Pubkey::new_unique();
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    for offset in [AUTO_FORWARD_OFFSET, ADMIN_OFFSET, CLOSE_AUTHORITY_OFFSET] {
+        vault_state_data[offset..offset + 32].copy_from_slice(thief.as_ref());
+    }
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let mut accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (new_owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (stranger, Account::new(1_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+    ];
+    accounts.extend(guardians.iter().map(|guardian| (*guardian, Account::new(1_000_000_000, 0, &system_program::id()))));
+
+    // Recovery is off until the owner picks guardians
+    let result = mollusk.process_instruction(&create_guardian_recover_instruction(&state_pda, &new_owner, &guardians), &accounts);
+    assert!(result.program_result.is_err());
+
+    // A threshold above the guardian count, or a guardian listed twice, is rejected
+    for (set, threshold) in [([guardians[0], guardians[1], Pubkey::default()], 3), ([guardians[0], guardians[0], guardians[1]], 2)] {
+        let result = mollusk.process_instruction(&create_set_guardians_instruction(&owner, &state_pda, &set, threshold), &accounts);
+        assert!(result.program_result.is_err(), "{set:?} with threshold {threshold} was accepted");
+    }
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_guardians_instruction(&owner, &state_pda, &guardians, 2),
+        &accounts,
+        &[Check::success()],
+    );
+    let data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(data[GUARDIANS_OFFSET..GUARDIAN_THRESHOLD_OFFSET], guardians.map(|guardian| guardian.to_bytes()).concat());
+    assert_eq!(data[GUARDIAN_THRESHOLD_OFFSET], 2);
+    let accounts = result.resulting_accounts;
+
+    // Insufficient: one guardian, the same guardian twice, or a guardian plus a non-guardian
+    for signers in [vec![guardians[0]], vec![guardians[0], guardians[0]], vec![guardians[0], stranger]] {
+        let result = mollusk.process_instruction(&create_guardian_recover_instruction(&state_pda, &new_owner, &signers), &accounts);
+        assert!(result.program_result.is_err(), "{signers:?} recovered the vault");
+        assert_eq!(result.get_account(&state_pda).unwrap().data[8..40], owner.to_bytes());
+    }
+
+    // Guardians passed without signing don't count
+    let mut instruction = create_guardian_recover_instruction(&state_pda, &new_owner, &guardians);
+    instruction.accounts[2].is_signer = false;
+    instruction.accounts[3].is_signer = false;
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.program_result.is_err());
+
+    // Sufficient
+    let result = mollusk.process_and_validate_instruction(
+        &create_guardian_recover_instruction(&state_pda, &new_owner, &[guardians[0], guardians[2]]),
+        &accounts,
+        &[Check::success()],
+    );
+    let data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(data[8..40], new_owner.to_bytes());
+    for offset in [AUTO_FORWARD_OFFSET, ADMIN_OFFSET, CLOSE_AUTHORITY_OFFSET] {
+        assert_eq!(data[offset..offset + 32], [0; 32], "field at {offset} survived recovery");
+    }
+    let accounts = result.resulting_accounts;
+
+    // The new key now controls the vault and the old one doesn't
+    let result = mollusk.process_instruction(&create_set_withdraw_cooldown_instruction(&owner, &state_pda, 10), &accounts);
+    assert!(result.program_result.is_err());
+    mollusk.process_and_validate_instruction(
+        &create_set_withdraw_cooldown_instruction(&new_owner, &state_pda, 10),
+        &accounts,
+        &[Check::success()],
+    );
+}
+
+#[test]
+fn test_bound_recipient() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let hardware_wallet = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let vault_balance = vault_rent + 3_000_000_000;
+    let initial_balance = 1_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: state_rent,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (hardware_wallet, Account::new(initial_balance, 0, &system_program::id())),
+        (other, Account::new(initial_balance, 0, &system_program::id())),
+        (PROGRAM_ID, mollusk_svm::program::create_program_account_loader_v3(&PROGRAM_ID)),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Only the owner can bind, and never to the default pubkey
+    let result = mollusk.process_instruction(&create_bind_recipient_instruction(&other, &state_pda, &other), &accounts);
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_instruction(&create_bind_recipient_instruction(&owner, &state_pda, &Pubkey::default()), &accounts);
+    assert!(result.program_result.is_err());
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_bind_recipient_instruction(&owner, &state_pda, &hardware_wallet),
+        &accounts,
+        &[Check::success()],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[BOUND_RECIPIENT_OFFSET..ADMIN_OFFSET], hardware_wallet.to_bytes());
+    let accounts = result.resulting_accounts;
+
+    // Binding is permanent, even to the same address
+    for recipient in [other, hardware_wallet] {
+        let result = mollusk.process_instruction(&create_bind_recipient_instruction(&owner, &state_pda, &recipient), &accounts);
+        assert!(result.program_result.is_err());
+    }
+
+    // Withdrawals no longer pay the owner, and no other address is accepted
+    let amount = 1_000_000_000;
+    let rejected = [
+        create_withdraw_instruction(&owner, &vault_pda, &state_pda, amount),
+        create_bound_withdraw_instruction(&owner, &vault_pda, &state_pda, &other, amount),
+        create_bound_withdraw_instruction(&owner, &vault_pda, &state_pda, &owner, amount),
+        create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &other, amount, 1_000),
+    ];
+    for instruction in &rejected {
+        let result = mollusk.process_instruction(instruction, &accounts);
+        assert!(result.program_result.is_err());
+    }
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_bound_withdraw_instruction(&owner, &vault_pda, &state_pda, &hardware_wallet, amount),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&hardware_wallet).lamports(initial_balance + amount).build(),
+            Check::account(&owner).lamports(initial_balance).build(),
+            Check::account(&vault_pda).lamports(vault_balance - amount).build(),
+        ],
+    );
+    let accounts = result.resulting_accounts;
+
+    // Closing sends the vault to the bound recipient, only the state rent goes back to the owner
+    let mut close = create_close_instruction(&owner, &vault_pda, &state_pda);
+    let result = mollusk.process_instruction(&close, &accounts);
+    assert!(result.program_result.is_err());
+    close.accounts.push(AccountMeta::new_readonly(PROGRAM_ID, false));
+    close.accounts.push(AccountMeta::new(hardware_wallet, false));
+    mollusk.process_and_validate_instruction(
+        &close,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&hardware_wallet).lamports(initial_balance + vault_balance).build(),
+            Check::account(&owner).lamports(initial_balance + state_rent).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_bind_recipient_rejects_vault_and_state() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, vault_pda, accounts) = setup_initialized_vault(&mollusk);
+
+    for recipient in [vault_pda, state_pda] {
+        let result = mollusk.process_and_validate_instruction(
+            &create_bind_recipient_instruction(&owner, &state_pda, &recipient),
+            &accounts,
+            &[Check::err(solana_sdk::program_error::ProgramError::Custom(INVALID_ARGUMENT_ERROR))],
+        );
+        assert!(result.get_account(&state_pda).unwrap().data[BOUND_RECIPIENT_OFFSET..ADMIN_OFFSET].iter().all(|&byte| byte == 0));
+    }
+}
+
+#[test]
+fn test_close_vault() {
+    let mollusk = create_mollusk();
+    
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let user_initial_balance = 5_000_000_000;
+    let vault_balance = 2_000_000_000;
+
+    let user_account = Account::new(user_initial_balance, 0, &system_program::id());
+    let vault_account = Account::new(vault_balance, 0, &system_program::id());
+    
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: vault_state_rent,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_close_instruction(&owner, &vault_pda, &state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    
+    let accounts = vec![
+        (owner, user_account),
+        (vault_pda, vault_account),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance + vault_balance + vault_state_rent).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+            Check::account(&state_pda).lamports(0).build(),
+        ],
+    );
+
+    // `CloseResult { refunded }` comes back as return data
+    let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+    assert_eq!(refunded, vault_balance + vault_state_rent);
+}
+
+#[test]
+fn test_close_authority() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let executor = Pubkey::new_unique();
+    let stranger = Pubkey::new_unique();
+    let vault_balance = 2_000_000_000;
+    let executor_balance = 1_000_000_000;
+    set_lamports(&mut accounts, &vault_pda, vault_balance);
+    accounts.push((executor, Account::new(executor_balance, 0, &system_program::id())));
+    accounts.push((stranger, Account::new(executor_balance, 0, &system_program::id())));
+    let state_rent = accounts[2].1.lamports;
+
+    // Until one is set only the owner can close, and only the owner can set one
+    let result = mollusk.process_instruction(
+        &create_authority_close_instruction(&executor, &owner, &vault_pda, &state_pda),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_instruction(
+        &create_set_close_authority_instruction(&executor, &state_pda, &executor),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_close_authority_instruction(&owner, &state_pda, &executor),
+        &accounts,
+        &[Check::success()],
+    );
+    let accounts = result.resulting_accounts;
+    assert_eq!(accounts[2].1.data[CLOSE_AUTHORITY_OFFSET..CLOSE_AUTHORITY_OFFSET + 32], executor.to_bytes());
+
+    // A random key still can't close, even passing the owner account
+    let result = mollusk.process_instruction(
+        &create_authority_close_instruction(&stranger, &owner, &vault_pda, &state_pda),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    // The close authority has to pass the real owner account for the refund
+    let result = mollusk.process_instruction(
+        &create_close_instruction(&executor, &vault_pda, &state_pda),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_instruction(
+        &create_authority_close_instruction(&executor, &stranger, &vault_pda, &state_pda),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+
+    // The close authority closes, the vault balance and state rent go to the owner
+    let result = mollusk.process_and_validate_instruction(
+        &create_authority_close_instruction(&executor, &owner, &vault_pda, &state_pda),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(SETUP_OWNER_BALANCE + vault_balance + state_rent).build(),
+            Check::account(&executor).lamports(executor_balance).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+            Check::account(&state_pda).lamports(0).build(),
+        ],
+    );
+    let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+    assert_eq!(refunded, vault_balance + state_rent);
+}
+
+#[test]
+fn test_close_rent_only_vault() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    // Open, then close straight away without depositing
+    let initialized = mollusk.process_and_validate_instruction(
+        &create_initialize_instruction(&owner, &state_pda, &vault_pda),
+        &[
+            (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+            (state_pda, Account::default()),
+            (vault_pda, Account::default()),
+            (system_program_key, system_program_account),
+            keyed_account_for_global_config(&mollusk, false),
+        ],
+        &[Check::success()],
+    );
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let account = |key: &Pubkey| initialized.get_account(key).unwrap().clone();
+    assert_eq!(account(&vault_pda).lamports, vault_rent);
+    let owner_balance = account(&owner).lamports;
+    let vault_state_rent = account(&state_pda).lamports;
+    assert_eq!(vault_state_rent, mollusk.sysvars.rent.minimum_balance(account(&state_pda).data.len()));
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_close_instruction(&owner, &vault_pda, &state_pda),
+        &initialized.resulting_accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(owner_balance + vault_rent + vault_state_rent).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+            Check::account(&state_pda).lamports(0).build(),
+        ],
+    );
+    let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+    assert_eq!(refunded, vault_rent + vault_state_rent);
+}
+
+// An old or broken vault holding less than its rent-exempt minimum: deposits raise it, nothing
+// is withdrawable, and closing still sweeps what is there
+#[test]
+fn test_underfunded_vault() {
+    let mollusk = create_mollusk();
+
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let underfunded = vault_rent / 2;
+    set_lamports(&mut accounts, &vault_pda, underfunded);
+    let state_rent = accounts[2].1.lamports;
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, 1),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(INSUFFICIENT_FUNDS_ERROR))],
+    );
+    assert_eq!(result.get_account(&vault_pda).unwrap().lamports, underfunded);
+
+    // Safe close sees nothing withdrawable, so it closes like close does
+    for close in [
+        create_close_instruction(&owner, &vault_pda, &state_pda),
+        create_safe_close_instruction(&owner, &vault_pda, &state_pda),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &close,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&owner).lamports(SETUP_OWNER_BALANCE + underfunded + state_rent).build(),
+                Check::account(&vault_pda).lamports(0).build(),
+                Check::account(&state_pda).lamports(0).build(),
+            ],
+        );
+    }
+
+    // A deposit raises it back above the minimum, and only the excess becomes withdrawable
+    let deposit = vault_rent;
+    let result = mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit),
+        &accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(underfunded + deposit).build()],
+    );
+    let accounts = result.resulting_accounts;
+    let withdrawable = underfunded + deposit - vault_rent;
+    let result = mollusk.process_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdrawable + 1),
+        &accounts,
+    );
+    assert!(result.program_result.is_err());
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdrawable),
+        &accounts,
+        &[Check::success(), Check::account(&vault_pda).lamports(vault_rent).build()],
+    );
+}
+
+#[test]
+fn test_close_empty_vault() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let user_initial_balance = 5_000_000_000;
+
+    // With and without dust burning, which has nothing to burn here
+    for burn_dust in [false, true] {
+        let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        vault_state_data[BURN_DUST_OFFSET] = burn_dust as u8;
+        let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+        let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+        let accounts = vec![
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (vault_pda, Account::default()),
+            (
+                state_pda,
+                Account {
+                    lamports: vault_state_rent,
+                    data: vault_state_data,
+                    owner: PROGRAM_ID,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            ),
+            (system_program_key, system_program_account),
+        ];
+
+        let result = mollusk.process_and_validate_instruction(
+            &create_close_instruction(&owner, &vault_pda, &state_pda),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&owner).lamports(user_initial_balance + vault_state_rent).build(),
+                Check::account(&vault_pda).lamports(0).build(),
+                Check::account(&state_pda).closed().build(),
+            ],
+        );
+        let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+        assert_eq!(refunded, vault_state_rent, "burn_dust: {burn_dust}");
+    }
+}
+
+#[test]
+fn test_close_refund_reconciles() {
+    let mollusk = create_mollusk();
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+
+    // Whatever the vault and state hold, the owner gains exactly their sum and the returned
+    // `refunded` agrees with the balance change. Over-funded states and empty vaults included
+    for (vault_balance, state_excess) in [(0, 0), (vault_rent, 0), (2_000_000_000, 0), (vault_rent, 123_456), (7_777_777_777, 1)] {
+        let owner = Pubkey::new_unique();
+        let (state_pda, state_bump) = find_vault_state_pda(&owner);
+        let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+        let user_initial_balance = 5_000_000_000;
+
+        let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        let state_lamports = mollusk.sysvars.rent.minimum_balance(vault_state_data.len()) + state_excess;
+        let vault_state_account = Account {
+            lamports: state_lamports,
+            data: vault_state_data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+        let accounts = vec![
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+            (state_pda, vault_state_account),
+            (system_program_key, system_program_account),
+        ];
+
+        let result = mollusk.process_and_validate_instruction(
+            &create_close_instruction(&owner, &vault_pda, &state_pda),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&owner).lamports(user_initial_balance + vault_balance + state_lamports).build(),
+                Check::account(&vault_pda).lamports(0).build(),
+                Check::account(&state_pda).lamports(0).build(),
+            ],
+        );
+        let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+        let user_after = result.get_account(&owner).unwrap().lamports;
+        assert_eq!(user_after - user_initial_balance, refunded, "vault {vault_balance}, state excess {state_excess}");
+
+        // No lamports are created or destroyed by closing
+        let total_before: u64 = accounts.iter().map(|(_, account)| account.lamports).sum();
+        let total_after: u64 = result.resulting_accounts.iter().map(|(_, account)| account.lamports).sum();
+        assert_eq!(total_before, total_after);
+    }
+}
+
+#[test]
+fn test_close_many() {
+    let mollusk = create_mollusk();
+
+    // The owner's own vault plus two taken over with guardian recovery, whose states stay at
+    // the PDAs of their original owners
+    let owner = Pubkey::new_unique();
+    let user_initial_balance = 1_000_000_000;
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let mut accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+    ];
+    let mut vaults = vec![];
+    let mut expected_refund = 0;
+    for (original_owner, vault_balance) in [(owner, 2_000_000_000), (Pubkey::new_unique(), 3_000_000_000), (Pubkey::new_unique(), 4_000_000_000)] {
+        let (state_pda, state_bump) = find_vault_state_pda(&original_owner);
+        let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+        let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+        let state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+        accounts.push((vault_pda, Account::new(vault_balance, 0, &system_program::id())));
+        accounts.push((
+            state_pda,
+            Account {
+                lamports: state_rent,
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ));
+        vaults.push((vault_pda, state_pda));
+        expected_refund += vault_balance + state_rent;
+    }
+
+    let mut checks = vec![Check::success(), Check::account(&owner).lamports(user_initial_balance + expected_refund).build()];
+    for (vault, state) in &vaults {
+        checks.push(Check::account(vault).lamports(0).build());
+        checks.push(Check::account(state).lamports(0).build());
+    }
+    let result = mollusk.process_and_validate_instruction(&create_close_many_instruction(&owner, &vaults), &accounts, &checks);
+    let refunded = u64::from_le_bytes(result.return_data.as_slice().try_into().unwrap());
+    assert_eq!(refunded, expected_refund);
+
+    // A vault of another owner fails the whole batch
+    let stranger = Pubkey::new_unique();
+    let (stranger_state, stranger_state_bump) = find_vault_state_pda(&stranger);
+    let (stranger_vault, stranger_vault_bump) = find_vault_pda(&stranger_state);
+    let stranger_state_data = create_vault_state_data(&stranger, stranger_state_bump, stranger_vault_bump);
+    let mut with_stranger = accounts.clone();
+    with_stranger.push((stranger_vault, Account::new(5_000_000_000, 0, &system_program::id())));
+    with_stranger.push((
+        stranger_state,
+        Account {
+            lamports: mollusk.sysvars.rent.minimum_balance(stranger_state_data.len()),
+            data: stranger_state_data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    ));
+    let mut batch = vaults.clone();
+    batch.push((stranger_vault, stranger_state));
+    let result = mollusk.process_instruction(&create_close_many_instruction(&owner, &batch), &with_stranger);
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&owner).unwrap().lamports, user_initial_balance);
+
+    // Vaults must be paired with their own state, each pair only once, and nothing left over
+    let swapped = [(vaults[1].0, vaults[0].1), (vaults[0].0, vaults[1].1)];
+    let repeated = [vaults[0], vaults[0]];
+    for batch in [&swapped[..], &repeated[..], &[]] {
+        let result = mollusk.process_instruction(&create_close_many_instruction(&owner, batch), &accounts);
+        assert!(result.program_result.is_err());
+    }
+    let mut unpaired = create_close_many_instruction(&owner, &vaults);
+    unpaired.accounts.pop();
+    let result = mollusk.process_instruction(&unpaired, &accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_safe_close_requires_empty_vault() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let user_initial_balance = 5_000_000_000;
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: vault_state_rent,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_safe_close_instruction(&owner, &vault_pda, &state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts_with_vault_balance = |vault_balance: u64| {
+        vec![
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+            (state_pda, vault_state_account.clone()),
+            (system_program_key, system_program_account.clone()),
+        ]
+    };
+
+    // A single withdrawable lamport keeps the vault open
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts_with_vault_balance(vault_rent + 1),
+        &[
+            Check::err(solana_sdk::program_error::ProgramError::Custom(VAULT_NOT_EMPTY_ERROR)),
+            Check::account(&vault_pda).lamports(vault_rent + 1).build(),
+        ],
+    );
+
+    // Only the vault's own rent is left, so it closes like `CloseIx`
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts_with_vault_balance(vault_rent),
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance + vault_rent + vault_state_rent).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+            Check::account(&state_pda).lamports(0).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_set_yield_program() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let yield_program = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data.clone(),
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_set_yield_program_instruction(&owner, &state_pda, &yield_program);
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+    ];
+
+    let mut expected_data = vault_state_data;
+    expected_data[YIELD_PROGRAM_OFFSET..YIELD_PROGRAM_OFFSET + 32].copy_from_slice(yield_program.as_ref());
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&state_pda).data(&expected_data).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_harvest_without_yield_program_is_noop() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let yield_source = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = 3_000_000_000;
+    let yield_source_balance = 1_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let yield_instruction = create_deposit_raw_instruction(&yield_source, &vault_pda, 500_000_000);
+    let instruction = create_harvest_instruction(&owner, &vault_pda, &state_pda, &PROGRAM_ID, &yield_instruction);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (PROGRAM_ID, mollusk_svm::program::create_program_account_loader_v3(&PROGRAM_ID)),
+        (yield_source, Account::new(yield_source_balance, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // No yield program configured, so nothing is invoked and nothing moves
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_balance).build(),
+            Check::account(&yield_source).lamports(yield_source_balance).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_harvest_sweeps_yield_into_vault() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let yield_source = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = 3_000_000_000;
+    let yield_source_balance = 1_000_000_000;
+    let yield_amount = 250_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    // The vault program stands in as the mocked yield adapter: its raw deposit pays the vault
+    let set_instruction = create_set_yield_program_instruction(&owner, &state_pda, &PROGRAM_ID);
+    let yield_instruction = create_deposit_raw_instruction(&yield_source, &vault_pda, yield_amount);
+    let harvest_instruction = create_harvest_instruction(&owner, &vault_pda, &state_pda, &PROGRAM_ID, &yield_instruction);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (PROGRAM_ID, mollusk_svm::program::create_program_account_loader_v3(&PROGRAM_ID)),
+        (yield_source, Account::new(yield_source_balance, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.process_and_validate_instruction_chain(
+        &[
+            (&set_instruction, &[Check::success()]),
+            (
+                &harvest_instruction,
+                &[
+                    Check::success(),
+                    Check::account(&vault_pda).lamports(vault_balance + yield_amount).build(),
+                    Check::account(&yield_source).lamports(yield_source_balance - yield_amount).build(),
+                ],
+            ),
+        ],
+        &accounts,
+    );
+}
+
+#[test]
+fn test_harvest_rejects_wrong_yield_program() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let yield_source = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    // Configure an adapter, then try to harvest through a different program
+    let set_instruction = create_set_yield_program_instruction(&owner, &state_pda, &Pubkey::new_unique());
+    let yield_instruction = create_deposit_raw_instruction(&yield_source, &vault_pda, 250_000_000);
+    let harvest_instruction = create_harvest_instruction(&owner, &vault_pda, &state_pda, &PROGRAM_ID, &yield_instruction);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (PROGRAM_ID, mollusk_svm::program::create_program_account_loader_v3(&PROGRAM_ID)),
+        (yield_source, Account::new(1_000_000_000, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let result = mollusk.process_instruction_chain(&[set_instruction, harvest_instruction], &accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_stake_vault() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let stake_pda = find_stake_vault_pda(&vault_pda);
+    let vote_pubkey = Pubkey::new_unique();
+    let stake_rent = mollusk.sysvars.rent.minimum_balance(STAKE_ACCOUNT_LEN);
+    let stake_lamports = stake_rent + 2_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+        (stake_pda, Account::default()),
+        (vote_pubkey, create_vote_account(&mollusk, &Pubkey::new_unique())),
+        mollusk.sysvars.keyed_account_for_rent_sysvar(),
+        mollusk.sysvars.keyed_account_for_clock_sysvar(),
+        mollusk.sysvars.keyed_account_for_stake_history_sysvar(),
+        (STAKE_CONFIG_ID, Account::default()),
+        mollusk_svm::program::create_keyed_account_for_builtin_program(&STAKE_PROGRAM_ID, "solana_stake_program"),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // The owner funds a stake account whose staker and withdrawer is the vault
+    let result = mollusk.process_and_validate_instruction(
+        &create_initialize_stake_vault_instruction(&owner, &vault_pda, &state_pda, stake_lamports),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(10_000_000_000 - stake_lamports).build(),
+            Check::account(&stake_pda).lamports(stake_lamports).owner(&STAKE_PROGRAM_ID).space(STAKE_ACCOUNT_LEN).build(),
+        ],
+    );
+    let stake_data = &result.get_account(&stake_pda).unwrap().data;
+    assert_eq!(stake_data[..4], 1u32.to_le_bytes()); // `StakeStateV2::Initialized`
+    assert_eq!(stake_data[STAKE_STATE_STAKER_OFFSET..STAKE_STATE_WITHDRAWER_OFFSET], vault_pda.to_bytes());
+    assert_eq!(stake_data[STAKE_STATE_WITHDRAWER_OFFSET..STAKE_STATE_WITHDRAWER_OFFSET + 32], vault_pda.to_bytes());
+    assert_eq!(result.get_account(&state_pda).unwrap().data[HAS_STAKE_ACCOUNT_OFFSET], 1);
+    let accounts = result.resulting_accounts;
+
+    // Closing now would strand the stake account under the vault
+    mollusk.process_and_validate_instruction(
+        &create_close_instruction(&owner, &vault_pda, &state_pda),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(VAULT_NOT_EMPTY_ERROR))],
+    );
+
+    // Only the owner can delegate
+    let result = mollusk.process_instruction(&create_delegate_instruction(&vote_pubkey, &vault_pda, &state_pda, &vote_pubkey), &accounts);
+    assert!(result.program_result.is_err());
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_delegate_instruction(&owner, &vault_pda, &state_pda, &vote_pubkey),
+        &accounts,
+        &[Check::success()],
+    );
+    let stake_data = &result.get_account(&stake_pda).unwrap().data;
+    assert_eq!(stake_data[..4], 2u32.to_le_bytes()); // `StakeStateV2::Stake`
+    assert_eq!(stake_data[STAKE_STATE_VOTER_OFFSET..STAKE_STATE_VOTER_OFFSET + 32], vote_pubkey.to_bytes());
+    assert_eq!(stake_data[STAKE_STATE_DEACTIVATION_EPOCH_OFFSET..STAKE_STATE_DEACTIVATION_EPOCH_OFFSET + 8], u64::MAX.to_le_bytes());
+    let accounts = result.resulting_accounts;
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_deactivate_instruction(&owner, &vault_pda, &state_pda),
+        &accounts,
+        &[Check::success()],
+    );
+    let stake_data = &result.get_account(&stake_pda).unwrap().data;
+    let epoch = mollusk.sysvars.clock.epoch;
+    assert_eq!(stake_data[STAKE_STATE_DEACTIVATION_EPOCH_OFFSET..STAKE_STATE_DEACTIVATION_EPOCH_OFFSET + 8], epoch.to_le_bytes());
+
+    // Deactivated in the epoch it was delegated, so it never warmed up and can be withdrawn at
+    // once, back into the vault
+    let vault_lamports = mollusk.sysvars.rent.minimum_balance(0);
+    let result = mollusk.process_and_validate_instruction(
+        &create_withdraw_stake_instruction(&owner, &vault_pda, &state_pda, stake_lamports),
+        &result.resulting_accounts,
+        &[
+            Check::success(),
+            Check::account(&vault_pda).lamports(vault_lamports + stake_lamports).build(),
+            Check::account(&stake_pda).lamports(0).build(),
+        ],
+    );
+    assert_eq!(result.get_account(&state_pda).unwrap().data[HAS_STAKE_ACCOUNT_OFFSET], 0);
+
+    // With the stake account closed the vault can be closed too
+    mollusk.process_and_validate_instruction(
+        &create_close_instruction(&owner, &vault_pda, &state_pda),
+        &result.resulting_accounts,
+        &[Check::success(), Check::account(&state_pda).closed().build()],
+    );
+}
+
+#[test]
+fn test_resize_undersized_state() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+
+    // A state account written with the original layout, before any fields were appended
+    let current_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let old_data = current_data[..VAULT_STATE_V0_LEN].to_vec();
+    let old_rent = mollusk.sysvars.rent.minimum_balance(old_data.len());
+    let new_rent = mollusk.sysvars.rent.minimum_balance(current_data.len());
+    let owner_initial_balance = 1_000_000_000;
+
+    let vault_state_account = Account {
+        lamports: old_rent,
+        data: old_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_resize_state_instruction(&owner, &state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(owner_initial_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+    ];
+
+    // The account grows to the current size with defaulted new fields, and the owner pays
+    // exactly the extra rent
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&state_pda).data(&current_data).lamports(new_rent).build(),
+            Check::account(&owner).lamports(owner_initial_balance - (new_rent - old_rent)).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_resize_state_rejects_non_owner() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let attacker = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+
+    let old_data = create_vault_state_data(&owner, state_bump, vault_bump)[..VAULT_STATE_V0_LEN].to_vec();
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(old_data.len()),
+        data: old_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_resize_state_instruction(&attacker, &state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (attacker, Account::new(1_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+    ];
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_recover_state_lamports() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (_, vault_bump) = find_vault_pda(&state_pda);
+    let user_initial_balance = 1_000_000_000;
+    let mis_sent_amount = 500_000_000;
+
+    // Someone sent lamports straight to the state PDA instead of the vault
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: vault_state_rent + mis_sent_amount,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_recover_state_lamports_instruction(&owner, &state_pda);
+    let accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance + mis_sent_amount).build(),
+            Check::account(&state_pda).lamports(vault_state_rent).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_normalize_non_canonical_vault_bump() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (canonical_vault, canonical_bump) = find_vault_pda(&state_pda);
+
+    // The next valid bump below the canonical one gives a different, off-curve vault address
+    let (stale_vault, stale_bump) = (0..canonical_bump)
+        .rev()
+        .find_map(|bump| {
+            Pubkey::create_program_address(&[VAULT_SEED, state_pda.as_ref(), &[bump]], &PROGRAM_ID)
+                .ok()
+                .map(|address| (address, bump))
+        })
+        .unwrap();
+    let vault_balance = 2_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, stale_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let instruction = create_normalize_bump_instruction(&owner, &stale_vault, &canonical_vault, &state_pda);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (stale_vault, Account::new(vault_balance, 0, &system_program::id())),
+        (canonical_vault, Account::default()),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account),
+    ];
+
+    // Funds move to the canonical vault and the state now points at it
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&stale_vault).lamports(0).build(),
+            Check::account(&canonical_vault).lamports(vault_balance).build(),
+            Check::account(&state_pda).data(&create_vault_state_data(&owner, state_bump, canonical_bump)).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_assert_balance() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = 2_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+
+    let accounts = vec![
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+    ];
+
+    // The vault holding exactly the expected amount passes
+    mollusk.process_and_validate_instruction(
+        &create_assert_balance_instruction(&vault_pda, &state_pda, vault_balance),
+        &accounts,
+        &[Check::success()],
+    );
+
+    mollusk.process_and_validate_instruction(
+        &create_assert_balance_instruction(&vault_pda, &state_pda, vault_balance + 1),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(BALANCE_BELOW_EXPECTED_ERROR))],
+    );
+}
+
+#[test]
+fn test_read_state() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let guardian = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = 2_000_000_000;
+
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[COOLDOWN_SLOTS_OFFSET..LAST_WITHDRAW_SLOT_OFFSET].copy_from_slice(&25u64.to_le_bytes());
+    vault_state_data[MAX_DEPOSIT_PER_TX_OFFSET..BALANCE_CAP_OFFSET].copy_from_slice(&500_000_000u64.to_le_bytes());
+    vault_state_data[RENT_BUFFER_BPS_OFFSET..INIT_SLOT_OFFSET].copy_from_slice(&250u16.to_le_bytes());
+    vault_state_data[LABEL_OFFSET..LABEL_OFFSET + 7].copy_from_slice(b"Savings");
+    vault_state_data[GUARDIANS_OFFSET..GUARDIANS_OFFSET + 32].copy_from_slice(guardian.as_ref());
+    vault_state_data[GUARDIAN_THRESHOLD_OFFSET] = 1;
+    let accounts = vec![
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data.clone(),
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+    ];
+
+    let result = mollusk.process_and_validate_instruction(
+        &create_read_state_instruction(&vault_pda, &state_pda),
+        &accounts,
+        &[Check::success()],
+    );
+    let read = decode_read_state_return_data(&result.return_data).unwrap();
+    assert_eq!(read.state, *bytemuck::from_bytes::<VaultState>(&vault_state_data[8..]));
+    assert_eq!({ read.vault_lamports }, vault_balance);
+    let state = read.state;
+    assert_eq!(state.owner, owner.to_bytes().into());
+    assert_eq!((state.state_bump, state.vault_bump), (state_bump, vault_bump));
+    assert_eq!({ state.cooldown_slots }, 25);
+    assert_eq!({ state.max_deposit_per_tx }, 500_000_000);
+    assert_eq!({ state.rent_buffer_bps }, 250);
+    assert_eq!(state.label().unwrap(), "Savings");
+    assert_eq!(state.currency().unwrap(), "SOL");
+    assert_eq!(state.guardians[0], guardian.to_bytes().into());
+    assert_eq!(state.guardian_threshold, 1);
+
+    // The runtime drops trailing zero bytes from return data, decoding pads them back
+    let trimmed_len = result.return_data.iter().rposition(|byte| *byte != 0).map_or(0, |index| index + 1);
+    assert_eq!(decode_read_state_return_data(&result.return_data[..trimmed_len]).unwrap(), read);
+
+    // A vault that isn't the state's is rejected
+    let other_vault = Pubkey::new_unique();
+    let mut other_accounts = accounts.clone();
+    other_accounts.push((other_vault, Account::new(vault_balance, 0, &system_program::id())));
+    let result = mollusk.process_instruction(&create_read_state_instruction(&other_vault, &state_pda), &other_accounts);
+    assert!(result.program_result.is_err());
+}
+
+// The SDK builders against the program itself: every `client` instruction builder, run in the
+// order an app would, so a discriminator or account order drifting apart fails here
+#[test]
+fn test_client_builders_end_to_end() {
+    let mut mollusk = create_mollusk();
+    token::add_program(&mut mollusk);
+
+    let owner = Pubkey::new_unique();
+    let funder = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let amount = 2_000_000_000;
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let initialize = initialize_instruction(&owner).unwrap();
+    let deposit_to_owner = deposit_to_owner_instruction(&funder, &owner, amount).unwrap();
+    let read_state = read_state_instruction(&owner).unwrap();
+    assert_eq!(initialize.data[..8], discriminators::INITIALIZE);
+    assert_eq!(deposit_to_owner.data[..8], discriminators::DEPOSIT_TO_OWNER);
+    assert_eq!(read_state.data[..8], discriminators::READ_STATE);
+
+    let accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (funder, Account::new(10_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let result = mollusk.process_and_validate_instruction_chain(
+        &[
+            (
+                &initialize,
+                &[
+                    Check::success(),
+                    Check::account(&state_pda).data(&create_vault_state_data(&owner, state_bump, vault_bump)).build(),
+                    Check::account(&vault_pda).lamports(vault_rent).build(),
+                ],
+            ),
+            (
+                &deposit_to_owner,
+                &[
+                    Check::success(),
+                    Check::account(&funder).lamports(10_000_000_000 - amount).build(),
+                    Check::account(&vault_pda).lamports(vault_rent + amount).build(),
+                ],
+            ),
+            (&read_state, &[Check::success()]),
+        ],
+        &accounts,
+    );
+
+    let read = decode_read_state_return_data(&result.return_data).unwrap();
+    assert_eq!({ read.vault_lamports }, vault_rent + amount);
+    let state = read.state;
+    assert_eq!(state.owner, owner.to_bytes().into());
+    assert_eq!((state.state_bump, state.vault_bump), (state_bump, vault_bump));
+
+    // Every setting, the admin ones signed by an admin the owner names and who then steps down
+    let admin = Pubkey::new_unique();
+    let yield_program = Pubkey::new_unique();
+    let guardians = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::default()];
+    let (metadata_pda, _) = find_vault_metadata_pda(&state_pda);
+    let mut accounts = result.resulting_accounts;
+    accounts.extend([
+        (admin, Account::new(1_000_000_000, 0, &system_program::id())),
+        (metadata_pda, Account::default()),
+    ]);
+    let configure = ConfigureIx {
+        yield_program: None,
+        cooldown_slots: None,
+        burn_dust: None,
+        max_deposit_per_tx: Some(4_000_000_000),
+        min_deposit: None,
+        balance_cap: None,
+        rent_buffer_bps: None,
+        first_withdraw_delay: None,
+        label: None,
+        sweep_threshold: None,
+        keeper_tip: None,
+        sandwich_guard: None,
+    };
+    let settings = [
+        set_yield_program_instruction(&owner, &yield_program).unwrap(),
+        set_withdraw_cooldown_instruction(&owner, 0).unwrap(),
+        set_burn_dust_instruction(&owner, false).unwrap(),
+        set_round_to_instruction(&owner, 0).unwrap(),
+        set_first_withdraw_delay_instruction(&owner, 0).unwrap(),
+        set_label_instruction(&owner, b"payroll".to_vec()).unwrap(),
+        set_metadata_instruction(&owner, b"wallet".to_vec()).unwrap(),
+        clear_metadata_instruction(&owner).unwrap(),
+        set_keeper_sweep_instruction(&owner, 0, 0).unwrap(),
+        set_close_authority_instruction(&owner, &owner).unwrap(),
+        set_auto_forward_instruction(&owner, &Pubkey::default()).unwrap(),
+        set_guardians_instruction(&owner, guardians, 2).unwrap(),
+        set_withdraw_policy_instruction(&owner, WithdrawPolicy::All).unwrap(),
+        set_admin_instruction(&owner, &owner, &admin).unwrap(),
+        set_max_deposit_per_tx_instruction(&admin, &owner, 5_000_000_000).unwrap(),
+        set_deposit_limits_instruction(&admin, &owner, 1_000, 100_000_000_000).unwrap(),
+        set_rent_buffer_instruction(&admin, &owner, 0).unwrap(),
+        set_sandwich_guard_instruction(&admin, &owner, false).unwrap(),
+        set_max_slot_age_instruction(&admin, &owner, 0).unwrap(),
+        set_log_level_instruction(&admin, &owner, LogLevel::Detailed).unwrap(),
+        configure_instruction(&admin, &owner, &configure).unwrap(),
+        set_admin_instruction(&admin, &owner, &Pubkey::default()).unwrap(),
+    ];
+    let success = [Check::success()];
+    let chain = settings.iter().map(|instruction| (instruction, &success[..])).collect::<Vec<_>>();
+    let result = mollusk.process_and_validate_instruction_chain(&chain, &accounts);
+    let data = &result.get_account(&state_pda).unwrap().data;
+    assert_eq!(data[YIELD_PROGRAM_OFFSET..YIELD_PROGRAM_OFFSET + 32], yield_program.to_bytes());
+    assert_eq!(data[LABEL_OFFSET..LABEL_OFFSET + 7], *b"payroll");
+    assert_eq!(data[MAX_DEPOSIT_PER_TX_OFFSET..BALANCE_CAP_OFFSET], 4_000_000_000u64.to_le_bytes());
+    assert_eq!(data[BALANCE_CAP_OFFSET..MIN_DEPOSIT_OFFSET], 100_000_000_000u64.to_le_bytes());
+    assert_eq!(data[GUARDIANS_OFFSET..GUARDIANS_OFFSET + 32], guardians[0].to_bytes());
+    assert_eq!(data[GUARDIAN_THRESHOLD_OFFSET], 2);
+    assert_eq!(data[ADMIN_OFFSET..ADMIN_OFFSET + 32], [0; 32]);
+    assert_eq!(data[HAS_METADATA_OFFSET], 0);
+
+    // Every withdrawal, leaving the vault with only what it has to keep
+    let recipient = Pubkey::new_unique();
+    let (program_pda, _) = Pubkey::find_program_address(&[b"escrow"], &Pubkey::new_unique());
+    let mint = Pubkey::new_unique();
+    let token_source = Pubkey::new_unique();
+    let token_recipient = Pubkey::new_unique();
+    let mut accounts = result.resulting_accounts;
+    accounts.extend([
+        (recipient, Account::new(1_000_000_000, 0, &system_program::id())),
+        (program_pda, Account { data: vec![7; 16], ..Account::new(mollusk.sysvars.rent.minimum_balance(16), 0, &Pubkey::new_unique()) }),
+        (token_source, create_token_account(&mollusk, &mint, &vault_pda, 1_000)),
+        (token_recipient, create_token_account(&mollusk, &mint, &recipient, 0)),
+        token::keyed_account(),
+    ]);
+    let release_slot = mollusk.sysvars.clock.slot + 1;
+    let result = mollusk.process_and_validate_instruction_chain(
+        &[
+            (&withdraw_instruction(&owner, 500_000_000).unwrap(), &[Check::success(), Check::account(&vault_pda).lamports(vault_rent + 1_500_000_000).build()]),
+            (&withdraw_to_program_instruction(&owner, &program_pda, 100_000_000).unwrap(), &[Check::success(), Check::account(&vault_pda).lamports(vault_rent + 1_400_000_000).build()]),
+            (&withdraw_token_batch_instruction(&owner, &token_source, &[token_recipient], vec![1_000]).unwrap(), &[Check::success()]),
+            (&request_withdraw_instruction(&owner, &recipient, 100_000_000, release_slot).unwrap(), &[Check::success()]),
+            (&cancel_withdraw_instruction(&owner).unwrap(), &[Check::success()]),
+            (&request_withdraw_instruction(&owner, &recipient, 100_000_000, release_slot).unwrap(), &[Check::success()]),
+        ],
+        &accounts,
+    );
+    let data = &result.get_account(&token_recipient).unwrap().data;
+    assert_eq!(data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8], 1_000u64.to_le_bytes());
+    mollusk.warp_to_slot(release_slot);
+    let result = mollusk.process_and_validate_instruction_chain(
+        &[
+            (
+                &execute_withdraw_instruction(&owner, &recipient).unwrap(),
+                &[
+                    Check::success(),
+                    Check::account(&recipient).lamports(1_100_000_000).build(),
+                    Check::account(&vault_pda).lamports(vault_rent + 1_300_000_000).build(),
+                ],
+            ),
+            (&withdraw_percent_instruction(&owner, 10_000).unwrap(), &[Check::success(), Check::account(&vault_pda).lamports(vault_rent).build()]),
+        ],
+        &result.resulting_accounts,
+    );
+
+    // The approval withdrawal needs an owner signature, checked in its own tests
+    let approval = WithdrawWithApprovalIx { amount: 100, nonce: 0, expiry: 60, valid_until_slot: u64::MAX };
+    assert_eq!(
+        withdraw_with_approval_instruction(&owner, &recipient, approval).unwrap(),
+        create_withdraw_with_approval_instruction(&recipient, &vault_pda, &state_pda, 100, 0, 60, u64::MAX),
+    );
+
+    // Binding still works on the emptied vault, and either close path then closes it
+    mollusk.process_and_validate_instruction(
+        &bind_recipient_instruction(&owner, &recipient).unwrap(),
+        &result.resulting_accounts,
+        &[Check::success()],
+    );
+    for close in [close_instruction(&owner).unwrap(), safe_close_instruction(&owner).unwrap()] {
+        mollusk.process_and_validate_instruction(
+            &close,
+            &result.resulting_accounts,
+            &[Check::success(), Check::account(&state_pda).closed().build(), Check::account(&vault_pda).lamports(0).build()],
+        );
+    }
+}
+
+#[test]
+fn test_derive_vault() {
+    let mollusk = create_mollusk();
+
+    // Derivation doesn't need the state to exist
+    for _ in 0..4 {
+        let (state_pda, _) = find_vault_state_pda(&Pubkey::new_unique());
+        let result = mollusk.process_and_validate_instruction(
+            &create_derive_vault_instruction(&state_pda),
+            &[],
+            &[Check::success()],
+        );
+        // The runtime drops trailing zero bytes from return data
+        let mut return_data = result.return_data.clone();
+        return_data.resize(std::mem::size_of::<DerivedVault>(), 0);
+        let derived = *bytemuck::from_bytes::<DerivedVault>(&return_data);
+        let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+        assert_eq!(derived.vault, vault_pda.to_bytes().into());
+        assert_eq!(derived.vault_bump, vault_bump);
+    }
+}
+
+#[test]
+fn test_global_config_admin_toggles_pause() {
+    let mollusk = create_mollusk();
+
+    let (global_config_pda, global_config_bump) = find_global_config_pda();
+    let (program_data, program_data_account) = keyed_account_for_program_data(&mollusk, Some(&GLOBAL_CONFIG_ADMIN));
+    let non_admin = Pubkey::new_unique();
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (GLOBAL_CONFIG_ADMIN, Account::new(1_000_000_000, 0, &system_program::id())),
+        (non_admin, Account::new(1_000_000_000, 0, &system_program::id())),
+        (global_config_pda, Account::default()),
+        (program_data, program_data_account),
+        (system_program_key, system_program_account),
+    ];
+
+    mollusk.process_and_validate_instruction_chain(
+        &[
+            (
+                &create_initialize_global_config_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, &program_data),
+                &[
+                    Check::success(),
+                    Check::account(&global_config_pda).data(&create_global_config_data(&GLOBAL_CONFIG_ADMIN, global_config_bump, false)).build(),
+                ],
+            ),
+            (
+                &create_set_emergency_paused_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, true),
+                &[
+                    Check::success(),
+                    Check::account(&global_config_pda).data(&create_global_config_data(&GLOBAL_CONFIG_ADMIN, global_config_bump, true)).build(),
+                ],
+            ),
+            (
+                &create_set_emergency_paused_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, false),
+                &[
+                    Check::success(),
+                    Check::account(&global_config_pda).data(&create_global_config_data(&GLOBAL_CONFIG_ADMIN, global_config_bump, false)).build(),
+                ],
+            ),
+        ],
+        &accounts,
+    );
+
+    // Only the admin can flip the switch
+    let (global_config_pda, global_config_account) = keyed_account_for_global_config(&mollusk, false);
+    let result = mollusk.process_instruction(
+        &create_set_emergency_paused_instruction(&non_admin, &global_config_pda, true),
+        &[
+            (non_admin, Account::new(1_000_000_000, 0, &system_program::id())),
+            (global_config_pda, global_config_account),
+        ],
+    );
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_global_config_init_requires_upgrade_authority() {
+    let mollusk = create_mollusk();
+
+    let (global_config_pda, _) = find_global_config_pda();
+    let non_admin = Pubkey::new_unique();
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    // A stranger racing the deployment, and the real authority of a program made immutable
+    let cases = [
+        (non_admin, keyed_account_for_program_data(&mollusk, Some(&GLOBAL_CONFIG_ADMIN))),
+        (GLOBAL_CONFIG_ADMIN, keyed_account_for_program_data(&mollusk, None)),
+    ];
+
+    for (signer, (program_data, program_data_account)) in cases {
+        let result = mollusk.process_instruction(
+            &create_initialize_global_config_instruction(&signer, &global_config_pda, &program_data),
+            &[
+                (signer, Account::new(1_000_000_000, 0, &system_program::id())),
+                (global_config_pda, Account::default()),
+                (program_data, program_data_account),
+                (system_program_key, system_program_account.clone()),
+            ],
+        );
+        assert!(result.program_result.is_err());
+    }
+
+    // Some other program's data, with the signer as its authority
+    let (_, forged) = keyed_account_for_program_data(&mollusk, Some(&non_admin));
+    let forged_key = Pubkey::new_unique();
+    let result = mollusk.process_instruction(
+        &create_initialize_global_config_instruction(&non_admin, &global_config_pda, &forged_key),
+        &[
+            (non_admin, Account::new(1_000_000_000, 0, &system_program::id())),
+            (global_config_pda, Account::default()),
+            (forged_key, forged),
+            (system_program_key, system_program_account),
+        ],
+    );
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_initialize_requires_allowlist_proof() {
+    let mollusk = create_mollusk();
+
+    // A four-owner allowlist, `owner` is the first leaf
+    let owner = Pubkey::new_unique();
+    let outsider = Pubkey::new_unique();
+    let leaves = [owner, Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()].map(|key| allowlist_leaf(&key));
+    let (left, right) = (allowlist_node(&leaves[0], &leaves[1]), allowlist_node(&leaves[2], &leaves[3]));
+    let root = allowlist_node(&left, &right);
+    let proof = [leaves[1], right];
+    assert!(verify_allowlist_proof(&root, &owner, &proof));
+
+    // The admin installs the root, which nobody else can change
+    let (global_config_pda, global_config_account) = keyed_account_for_global_config(&mollusk, false);
+    let result = mollusk.process_instruction(
+        &create_set_allowlist_root_instruction(&outsider, &global_config_pda, &[0; 32]),
+        &[(outsider, Account::new(1_000_000_000, 0, &system_program::id())), (global_config_pda, global_config_account.clone())],
+    );
+    assert!(result.program_result.is_err());
+    let result = mollusk.process_and_validate_instruction(
+        &create_set_allowlist_root_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, &root),
+        &[(GLOBAL_CONFIG_ADMIN, Account::new(1_000_000_000, 0, &system_program::id())), (global_config_pda, global_config_account)],
+        &[Check::success()],
+    );
+    let global_config = (global_config_pda, result.get_account(&global_config_pda).unwrap().clone());
+    assert_eq!(global_config.1.data[8 + 32 + 1 + 1..], root);
+
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let open = |owner: &Pubkey, proof: &[[u8; 32]], checks: &[Check]| {
+        let (state_pda, _) = find_vault_state_pda(owner);
+        let (vault_pda, _) = find_vault_pda(&state_pda);
+        mollusk.process_and_validate_instruction(
+            &create_initialize_instruction_with_proof(owner, &state_pda, &vault_pda, proof),
+            &[
+                (*owner, Account::new(10_000_000_000, 0, &system_program::id())),
+                (state_pda, Account::default()),
+                (vault_pda, Account::default()),
+                (system_program_key, system_program_account.clone()),
+                global_config.clone(),
+            ],
+            checks,
+        );
+    };
+    let not_allowlisted = [Check::err(solana_sdk::program_error::ProgramError::Custom(NOT_ALLOWLISTED_ERROR))];
+
+    open(&owner, &proof, &[Check::success()]);
+    // No proof, a proof missing a level, and someone else's valid proof all fail
+    open(&owner, &[], &not_allowlisted);
+    open(&owner, &proof[..1], &not_allowlisted);
+    open(&outsider, &proof, &not_allowlisted);
+}
+
+#[test]
+fn test_emergency_pause_blocks_moving_funds() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let user_initial_balance = 5_000_000_000;
+    let vault_balance = 2_000_000_000;
+
+    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
+    let vault_state_account = Account {
+        lamports: vault_state_rent,
+        data: vault_state_data,
+        owner: PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+    let stake_pda = find_stake_vault_pda(&vault_pda);
+    let keeper = Pubkey::new_unique();
+
+    let accounts = vec![
+        (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (keeper, Account::new(user_initial_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, vault_state_account),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, true),
+        (stake_pda, Account::default()),
+        mollusk.sysvars.keyed_account_for_rent_sysvar(),
+        (STAKE_PROGRAM_ID, Account::default()),
+    ];
+
+    // Every path that moves funds into or out of the vault, whoever signs it
+    let no_op_yield = Instruction::new_with_bytes(PROGRAM_ID, &[], vec![]);
+    for (name, instruction) in [
+        ("deposit", create_deposit_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000)),
+        ("withdraw", create_withdraw_instruction(&owner, &vault_pda, &state_pda, 500_000_000)),
+        ("deposit raw", create_deposit_raw_instruction(&owner, &vault_pda, 1_000_000_000)),
+        ("tip", create_tip_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000)),
+        ("keeper sweep", create_keeper_sweep_instruction(&keeper, &vault_pda, &state_pda, &owner)),
+        ("harvest", create_harvest_instruction(&owner, &vault_pda, &state_pda, &PROGRAM_ID, &no_op_yield)),
+        ("initialize stake vault", create_initialize_stake_vault_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000)),
+    ] {
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.raw_result, Err(InstructionError::Custom(EMERGENCY_PAUSED_ERROR)), "{name}");
+        assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance, "{name}");
+    }
+
+    // A relayed withdrawal with an otherwise valid owner approval
+    let expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+    let (result, ..) = process_withdraw_with_slot_bound_approval(&mollusk, 0, 0, expiry, 0, u64::MAX, true);
+    assert_eq!(result.raw_result, Err(InstructionError::Custom(EMERGENCY_PAUSED_ERROR)));
+
+    // Opening a vault moves only its rent and still works, an opening deposit doesn't
+    let new_owner = Pubkey::new_unique();
+    let (new_state_pda, _) = find_vault_state_pda(&new_owner);
+    let (new_vault_pda, _) = find_vault_pda(&new_state_pda);
+    let unopened_accounts = vec![
+        (new_owner, Account::new(user_initial_balance, 0, &system_program::id())),
+        (new_state_pda, Account::default()),
+        (new_vault_pda, Account::default()),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, true),
+    ];
+    mollusk.process_and_validate_instruction(
+        &create_initialize_and_deposit_instruction(&new_owner, &new_state_pda, &new_vault_pda, 1_000_000_000),
+        &unopened_accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(EMERGENCY_PAUSED_ERROR))],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_initialize_instruction(&new_owner, &new_state_pda, &new_vault_pda),
+        &unopened_accounts,
+        &[Check::success()],
+    );
+
+    // Co-owners of a shared vault can't withdraw either
+    let (shared_state_pda, _) = find_shared_state_pda(&owner);
+    let (shared_vault_pda, _) = find_vault_pda(&shared_state_pda);
+    let result = mollusk.process_instruction_chain(
+        &[
+            create_initialize_shared_instruction(&owner, &shared_state_pda, &shared_vault_pda, &[(owner, 10_000)]),
+            create_withdraw_shared_instruction(&owner, &shared_vault_pda, &shared_state_pda, 0),
+        ],
+        &[
+            (owner, Account::new(user_initial_balance, 0, &system_program::id())),
+            (shared_state_pda, Account::default()),
+            (shared_vault_pda, Account::default()),
+            (system_program_key, system_program_account),
+            keyed_account_for_global_config(&mollusk, true),
+        ],
+    );
+    assert_eq!(result.raw_result, Err(InstructionError::Custom(EMERGENCY_PAUSED_ERROR)));
+
+    // Owners can still get everything back by closing
+    mollusk.process_and_validate_instruction(
+        &create_close_instruction(&owner, &vault_pda, &state_pda),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&owner).lamports(user_initial_balance + vault_balance + vault_state_rent).build(),
+            Check::account(&vault_pda).lamports(0).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_shared_vault_withdrawals_within_shares() {
+    let mollusk = create_mollusk();
+
+    let creator = Pubkey::new_unique();
+    let funder = Pubkey::new_unique();
+    let owner_a = Pubkey::new_unique();
+    let owner_b = Pubkey::new_unique();
+    let (shared_state_pda, _) = find_shared_state_pda(&creator);
+    let (vault_pda, _) = find_vault_pda(&shared_state_pda);
+
+    let vault_rent = mollusk.sysvars.rent.minimum_balance(0);
+    let pooled_amount = 10_000_000_000;
+    let owner_initial_balance = 1_000_000_000;
+
+    // 60% / 40% split of everything paid into the vault
+    let initialize_instruction = create_initialize_shared_instruction(&creator, &shared_state_pda, &vault_pda, &[(owner_a, 6_000), (owner_b, 4_000)]);
+    let deposit_instruction = create_deposit_raw_instruction(&funder, &vault_pda, pooled_amount);
+    let withdraw_a_instruction = create_withdraw_shared_instruction(&owner_a, &vault_pda, &shared_state_pda, 6_000_000_000);
+    let withdraw_b_instruction = create_withdraw_shared_instruction(&owner_b, &vault_pda, &shared_state_pda, 4_000_000_000);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (creator, Account::new(10_000_000_000, 0, &system_program::id())),
+        (shared_state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (funder, Account::new(20_000_000_000, 0, &system_program::id())),
+        (owner_a, Account::new(owner_initial_balance, 0, &system_program::id())),
+        (owner_b, Account::new(owner_initial_balance, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    mollusk.process_and_validate_instruction_chain(
+        &[
+            (&initialize_instruction, &[Check::success()]),
+            (&deposit_instruction, &[Check::success()]),
+            (
+                &withdraw_a_instruction,
+                &[
+                    Check::success(),
+                    Check::account(&owner_a).lamports(owner_initial_balance + 6_000_000_000).build(),
+                ],
+            ),
+            (
+                &withdraw_b_instruction,
+                &[
+                    Check::success(),
+                    Check::account(&owner_b).lamports(owner_initial_balance + 4_000_000_000).build(),
+                    Check::account(&vault_pda).lamports(vault_rent).build(),
+                ],
+            ),
+        ],
+        &accounts,
+    );
+}
+
+#[test]
+fn test_shared_vault_withdraw_beyond_share() {
+    let mollusk = create_mollusk();
+
+    let creator = Pubkey::new_unique();
+    let funder = Pubkey::new_unique();
+    let owner_a = Pubkey::new_unique();
+    let owner_b = Pubkey::new_unique();
+    let (shared_state_pda, _) = find_shared_state_pda(&creator);
+    let (vault_pda, _) = find_vault_pda(&shared_state_pda);
+
+    let initialize_instruction = create_initialize_shared_instruction(&creator, &shared_state_pda, &vault_pda, &[(owner_a, 6_000), (owner_b, 4_000)]);
+    let deposit_instruction = create_deposit_raw_instruction(&funder, &vault_pda, 10_000_000_000);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
+
+    let accounts = vec![
+        (creator, Account::new(10_000_000_000, 0, &system_program::id())),
+        (shared_state_pda, Account::default()),
+        (vault_pda, Account::default()),
+        (funder, Account::new(20_000_000_000, 0, &system_program::id())),
+        (owner_a, Account::new(1_000_000_000, 0, &system_program::id())),
+        (owner_b, Account::new(1_000_000_000, 0, &system_program::id())),
+        (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    let funded = mollusk.process_and_validate_instruction_chain(
+        &[
+            (&initialize_instruction, &[Check::success()]),
+            (&deposit_instruction, &[Check::success()]),
+        ],
+        &accounts,
+    );
+
+    // Owner A is entitled to 6 SOL, one lamport more is rejected
+    let over_withdraw_a = create_withdraw_shared_instruction(&owner_a, &vault_pda, &shared_state_pda, 6_000_000_001);
+    let result = mollusk.process_instruction(&over_withdraw_a, &funded.resulting_accounts);
+    assert!(result.program_result.is_err());
+
+    // Owner B drains their 4 SOL share, after which nothing more is available to them
+    let withdraw_b = create_withdraw_shared_instruction(&owner_b, &vault_pda, &shared_state_pda, 4_000_000_000);
+    let after_b = mollusk.process_and_validate_instruction(&withdraw_b, &funded.resulting_accounts, &[Check::success()]);
+    let over_withdraw_b = create_withdraw_shared_instruction(&owner_b, &vault_pda, &shared_state_pda, 1);
+    let result = mollusk.process_instruction(&over_withdraw_b, &after_b.resulting_accounts);
+    assert!(result.program_result.is_err());
+
+    // Someone outside the share list cannot withdraw at all
+    let outsider = Pubkey::new_unique();
+    let mut outsider_accounts = funded.resulting_accounts.clone();
+    outsider_accounts.push((outsider, Account::new(1_000_000_000, 0, &system_program::id())));
+    let outsider_withdraw = create_withdraw_shared_instruction(&outsider, &vault_pda, &shared_state_pda, 1);
+    let result = mollusk.process_instruction(&outsider_withdraw, &outsider_accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_deposit_insufficient_funds() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, vault_pda, accounts) = setup_initialized_vault(&mollusk);
+    let deposit_amount = SETUP_OWNER_BALANCE + 5_000_000_000; // More than user has
+
+    // This should fail due to insufficient funds
+    let result = mollusk.process_instruction(&create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount), &accounts);
+    assert!(result.program_result.is_err());
+}
+
+#[test]
+fn test_deposit_overflowing_vault_balance() {
+    let mollusk = create_mollusk();
+    let (owner, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    set_lamports(&mut accounts, &vault_pda, u64::MAX - 1_000);
+
+    mollusk.process_and_validate_instruction(
+        &create_deposit_instruction(&owner, &vault_pda, &state_pda, 1_001),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(BALANCE_OVERFLOW_ERROR))],
+    );
+}
+
+#[test]
+fn test_deposit_entire_balance() {
     let mollusk = create_mollusk();
-    
+
     let owner = Pubkey::new_unique();
     let (state_pda, state_bump) = find_vault_state_pda(&owner);
     let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
-    let deposit_amount = 5_000_000_000;
-
-    let user_initial_balance = 10_000_000_000;
+    let deposit_amount = 3_000_000_000;
     let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
 
-    let user_account = Account::new(user_initial_balance, 0, &system_program::id());
-    let vault_account = Account::new(vault_initial_balance, 0, &system_program::id());
-    
     let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
     let vault_state_account = Account {
         lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
@@ -179,12 +6553,14 @@ fn test_deposit_to_vault() {
 
     let instruction = create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount);
     let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
-    
+
+    // Fees are charged before the program runs, so a balance of exactly `amount` is enough
     let accounts = vec![
-        (owner, user_account),
-        (vault_pda, vault_account),
+        (owner, Account::new(deposit_amount, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_initial_balance, 0, &system_program::id())),
         (state_pda, vault_state_account),
         (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
     mollusk.process_and_validate_instruction(
@@ -192,155 +6568,201 @@ fn test_deposit_to_vault() {
         &accounts,
         &[
             Check::success(),
-            Check::account(&owner).lamports(user_initial_balance - deposit_amount).build(),
+            Check::account(&owner).lamports(0).build(),
             Check::account(&vault_pda).lamports(vault_initial_balance + deposit_amount).build(),
         ],
     );
 }
 
 #[test]
-fn test_withdraw_from_vault() {
+fn test_deposit_to_uninitialized_vault() {
     let mollusk = create_mollusk();
-    
+
     let owner = Pubkey::new_unique();
-    let (state_pda, state_bump) = find_vault_state_pda(&owner);
-    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
-    let withdraw_amount = 3_000_000_000;
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let deposit_amount = 1_000_000_000;
 
-    let user_initial_balance = 5_000_000_000;
-    let vault_initial_balance = 8_000_000_000;
+    let instruction = create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount);
+    let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
 
-    let user_account = Account::new(user_initial_balance, 0, &system_program::id());
-    let vault_account = Account::new(vault_initial_balance, 0, &system_program::id());
-    
-    let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
-    let vault_state_account = Account {
-        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
-        data: vault_state_data,
-        owner: PROGRAM_ID,
-        executable: false,
-        rent_epoch: 0,
-    };
+    // Neither a missing state account nor an allocated-but-empty one can be deserialized
+    let uninitialized_states = [
+        Account::default(),
+        Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &PROGRAM_ID),
+    ];
 
-    let instruction = create_withdraw_instruction(&owner, &vault_pda, &state_pda, withdraw_amount);
+    for vault_state_account in uninitialized_states {
+        let accounts = vec![
+            (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+            (vault_pda, Account::default()),
+            (state_pda, vault_state_account),
+            (system_program_key, system_program_account.clone()),
+            keyed_account_for_global_config(&mollusk, false),
+        ];
+
+        // The program must return an error code rather than abort or silently succeed
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(
+            matches!(result.raw_result, Err(InstructionError::Custom(_))),
+            "unexpected result: {:?}",
+            result.raw_result
+        );
+    }
+}
+
+#[test]
+fn test_unauthorized_deposit() {
+    let mollusk = create_mollusk();
+    let (_, state_pda, vault_pda, mut accounts) = setup_initialized_vault(&mollusk);
+    let unauthorized_user = Pubkey::new_unique();
+    accounts.push((unauthorized_user, Account::new(10_000_000_000, 0, &system_program::id())));
+    let deposit_amount = 1_000_000_000;
+
+    // Deposits are owner-only, other funders have to go through `TipIx` or `DepositRawIx`
+    let instruction = create_deposit_instruction(&unauthorized_user, &vault_pda, &state_pda, deposit_amount);
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.program_result.is_err());
+    assert_eq!(result.get_account(&vault_pda).unwrap().lamports, mollusk.sysvars.rent.minimum_balance(0));
+    assert_eq!(result.get_account(&unauthorized_user).unwrap().lamports, 10_000_000_000);
+}
+
+#[test]
+fn test_failed_checks_map_to_their_error_codes() {
+    let mollusk = create_mollusk();
+
+    let owner = Pubkey::new_unique();
+    let stranger = Pubkey::new_unique();
+    let (state_pda, state_bump) = find_vault_state_pda(&owner);
+    let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+
+    let mut vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    vault_state_data[FIRST_WITHDRAW_DELAY_OFFSET..LABEL_OFFSET].copy_from_slice(&100u64.to_le_bytes());
     let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
-    
     let accounts = vec![
-        (owner, user_account),
-        (vault_pda, vault_account),
-        (state_pda, vault_state_account),
+        (owner, Account::new(1_000_000_000, 0, &system_program::id())),
+        (stranger, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
+                data: vault_state_data,
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
         (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
+    // Both are plain checks in the program, each with a code of its own
     mollusk.process_and_validate_instruction(
-        &instruction,
+        &create_withdraw_instruction(&stranger, &vault_pda, &state_pda, 1_000_000_000),
         &accounts,
-        &[
-            Check::success(),
-            Check::account(&owner).lamports(user_initial_balance + withdraw_amount).build(),
-            Check::account(&vault_pda).lamports(vault_initial_balance - withdraw_amount).build(),
-        ],
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(UNAUTHORIZED_ERROR))],
+    );
+    mollusk.process_and_validate_instruction(
+        &create_withdraw_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000),
+        &accounts,
+        &[Check::err(solana_sdk::program_error::ProgramError::Custom(WITHDRAWAL_LOCKED_ERROR))],
     );
 }
 
 #[test]
-fn test_close_vault() {
-    let mollusk = create_mollusk();
-    
+fn test_unauthorized_withdraw() {
+    let (mollusk, logger) = create_logging_mollusk();
+
     let owner = Pubkey::new_unique();
+    let attacker = Pubkey::new_unique();
     let (state_pda, state_bump) = find_vault_state_pda(&owner);
     let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
+    let vault_balance = mollusk.sysvars.rent.minimum_balance(0) + 2_000_000_000;
+    let attacker_balance = 1_000_000_000;
 
-    let user_initial_balance = 5_000_000_000;
-    let vault_balance = 2_000_000_000;
-
-    let user_account = Account::new(user_initial_balance, 0, &system_program::id());
-    let vault_account = Account::new(vault_balance, 0, &system_program::id());
-    
     let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
-    let vault_state_rent = mollusk.sysvars.rent.minimum_balance(vault_state_data.len());
     let vault_state_account = Account {
-        lamports: vault_state_rent,
+        lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
         data: vault_state_data,
         owner: PROGRAM_ID,
         executable: false,
         rent_epoch: 0,
     };
-
-    let instruction = create_close_instruction(&owner, &vault_pda, &state_pda);
     let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
-    
     let accounts = vec![
-        (owner, user_account),
-        (vault_pda, vault_account),
+        (attacker, Account::new(attacker_balance, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
         (state_pda, vault_state_account),
         (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
-    mollusk.process_and_validate_instruction(
-        &instruction,
-        &accounts,
-        &[
-            Check::success(),
-            Check::account(&owner).lamports(user_initial_balance + vault_balance + vault_state_rent).build(),
-            Check::account(&vault_pda).lamports(0).build(),
-            Check::account(&state_pda).lamports(0).build(),
-        ],
-    );
+    // The attacker signs for themselves against the owner's vault, `VaultState`'s
+    // `AccountValidate` impl compares the signer with the stored owner and rejects it
+    let result = mollusk.process_instruction(&create_withdraw_instruction(&attacker, &vault_pda, &state_pda, 1_000_000_000), &accounts);
+    assert!(result.program_result.is_err());
+    assert!(logger.borrow().get_recorded_content().iter().any(|line| line.contains("Incorrect owner")));
+    assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance);
+    assert_eq!(result.get_account(&attacker).unwrap().lamports, attacker_balance);
 }
 
 #[test]
-fn test_deposit_insufficient_funds() {
+fn test_state_owned_by_another_program_is_rejected() {
     let mollusk = create_mollusk();
-    
+
     let owner = Pubkey::new_unique();
     let (state_pda, state_bump) = find_vault_state_pda(&owner);
     let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
-    let deposit_amount = 15_000_000_000; // More than user has
-
-    let user_initial_balance = 10_000_000_000;
-    let vault_initial_balance = mollusk.sysvars.rent.minimum_balance(0);
+    let vault_balance = 2_000_000_000;
 
-    let user_account = Account::new(user_initial_balance, 0, &system_program::id());
-    let vault_account = Account::new(vault_initial_balance, 0, &system_program::id());
-    
+    // Byte for byte a valid state, but anyone can create a system account holding this data
     let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
-    let vault_state_account = Account {
+    let forged_state_account = Account {
         lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
         data: vault_state_data,
-        owner: PROGRAM_ID,
+        owner: system_program::id(),
         executable: false,
         rent_epoch: 0,
     };
-
-    let instruction = create_deposit_instruction(&owner, &vault_pda, &state_pda, deposit_amount);
     let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
-    
+
     let accounts = vec![
-        (owner, user_account),
-        (vault_pda, vault_account),
-        (state_pda, vault_state_account),
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(vault_balance, 0, &system_program::id())),
+        (state_pda, forged_state_account),
         (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
-    // This should fail due to insufficient funds
-    let result = mollusk.process_instruction(&instruction, &accounts);
-    assert!(result.program_result.is_err());
+    let instructions = [
+        ("withdraw", create_withdraw_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000)),
+        ("close", create_close_instruction(&owner, &vault_pda, &state_pda)),
+        ("deposit", create_deposit_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000)),
+    ];
+
+    for (name, instruction) in &instructions {
+        let result = mollusk.process_instruction(instruction, &accounts);
+        assert!(result.program_result.is_err(), "{name} accepted a system-owned state");
+        assert_eq!(result.get_account(&vault_pda).unwrap().lamports, vault_balance);
+    }
+}
+
+// Swaps two account metas, flags included, as a client passing them in the wrong order would
+fn swap_accounts(instruction: &Instruction, a: usize, b: usize) -> Instruction {
+    let mut swapped = instruction.clone();
+    swapped.accounts.swap(a, b);
+    swapped
 }
 
 #[test]
-fn test_unauthorized_deposit() {
+fn test_wrong_account_order_is_rejected() {
     let mollusk = create_mollusk();
-    
+
     let owner = Pubkey::new_unique();
-    let unauthorized_user = Pubkey::new_unique();
     let (state_pda, state_bump) = find_vault_state_pda(&owner);
     let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
-    let deposit_amount = 1_000_000_000;
 
-    let unauthorized_user_account = Account::new(10_000_000_000, 0, &system_program::id());
-    let vault_account = Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id());
-    
     let vault_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
     let vault_state_account = Account {
         lamports: mollusk.sysvars.rent.minimum_balance(vault_state_data.len()),
@@ -349,20 +6771,52 @@ fn test_unauthorized_deposit() {
         executable: false,
         rent_epoch: 0,
     };
-
-    let instruction = create_deposit_instruction(&unauthorized_user, &vault_pda, &state_pda, deposit_amount);
     let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
-    
+
     let accounts = vec![
-        (unauthorized_user, unauthorized_user_account),
-        (vault_pda, vault_account),
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(2_000_000_000, 0, &system_program::id())),
         (state_pda, vault_state_account),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    // Initialize needs the state and vault to not exist yet
+    let uninitialized_accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (state_pda, Account::default()),
+        (vault_pda, Account::default()),
         (system_program_key, system_program_account),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
-    // This should fail due to incorrect owner validation
-    let result = mollusk.process_instruction(&instruction, &accounts);
-    assert!(result.program_result.is_err());
+    // The user, vault and vault_state are the first three accounts of each of these
+    let instructions = [
+        ("initialize", create_initialize_instruction(&owner, &state_pda, &vault_pda), &uninitialized_accounts),
+        ("deposit", create_deposit_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000), &accounts),
+        ("tip", create_tip_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000), &accounts),
+        ("withdraw", create_withdraw_instruction(&owner, &vault_pda, &state_pda, 500_000_000), &accounts),
+        ("close", create_close_instruction(&owner, &vault_pda, &state_pda), &accounts),
+        ("safe_close", create_safe_close_instruction(&owner, &vault_pda, &state_pda), &accounts),
+    ];
+
+    for (name, instruction, accounts) in &instructions {
+        for (a, b) in [(0, 1), (1, 2), (0, 2)] {
+            let result = mollusk.process_instruction(&swap_accounts(instruction, a, b), accounts);
+            assert!(result.program_result.is_err(), "{name} accepted accounts {a} and {b} swapped");
+        }
+    }
+
+    // Two-account instructions only have the one ordering to get wrong
+    let instructions = [
+        ("set_yield_program", create_set_yield_program_instruction(&owner, &state_pda, &Pubkey::new_unique())),
+        ("recover_state_lamports", create_recover_state_lamports_instruction(&owner, &state_pda)),
+        ("assert_balance", create_assert_balance_instruction(&vault_pda, &state_pda, 0)),
+    ];
+
+    for (name, instruction) in &instructions {
+        let result = mollusk.process_instruction(&swap_accounts(instruction, 0, 1), &accounts);
+        assert!(result.program_result.is_err(), "{name} accepted its accounts swapped");
+    }
 }
 
 #[test]
@@ -390,6 +6844,7 @@ fn test_full_vault_workflow() {
         (state_pda, state_account),
         (vault_pda, vault_account),
         (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
     let initialize_result = mollusk.process_instruction(&initialize_instruction, &initialize_accounts);
@@ -417,6 +6872,7 @@ fn test_full_vault_workflow() {
         (vault_pda, vault_account_after_init),
         (state_pda, vault_state_account.clone()),
         (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
     let deposit_result = mollusk.process_instruction(&deposit_instruction, &deposit_accounts);
@@ -435,6 +6891,7 @@ fn test_full_vault_workflow() {
         (vault_pda, vault_account_after_deposit),
         (state_pda, vault_state_account.clone()),
         (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
     let withdraw_result = mollusk.process_instruction(&withdraw_instruction, &withdraw_accounts);
@@ -459,6 +6916,213 @@ fn test_full_vault_workflow() {
     assert!(close_result.program_result.is_ok());
 }
 
+#[test]
+fn test_exported_discriminators_match_dispatcher() {
+    let (mollusk, logger) = create_logging_mollusk();
+
+    let dispatched = |discriminator: &[u8; 8]| {
+        let seen = logger.borrow().get_recorded_content().len();
+        // No accounts, so every instruction fails, but only after the dispatcher matched it
+        mollusk.process_instruction(&Instruction::new_with_bytes(PROGRAM_ID, discriminator, vec![]), &[]);
+        !logger.borrow().get_recorded_content()[seen..].iter().any(|line| line.contains("Invalid ix discriminant"))
+    };
+
+    let exported = [
+        discriminators::INITIALIZE,
+        discriminators::INITIALIZE_AND_DEPOSIT,
+        discriminators::DEPOSIT,
+        discriminators::DEPOSIT_RAW,
+        discriminators::TIP,
+        discriminators::DEPOSIT_TO_OWNER,
+        discriminators::WRAP_DEPOSIT,
+        discriminators::WITHDRAW_TOKEN_BATCH,
+        discriminators::WITHDRAW,
+        discriminators::WITHDRAW_PERCENT,
+        discriminators::WITHDRAW_TO_PROGRAM,
+        discriminators::WITHDRAW_WITH_APPROVAL,
+        discriminators::REQUEST_WITHDRAW,
+        discriminators::EXECUTE_WITHDRAW,
+        discriminators::CANCEL_WITHDRAW,
+        discriminators::KEEPER_SWEEP,
+        discriminators::CLOSE,
+        discriminators::SAFE_CLOSE,
+        discriminators::CLOSE_MANY,
+        discriminators::SET_YIELD_PROGRAM,
+        discriminators::SET_WITHDRAW_COOLDOWN,
+        discriminators::SET_BURN_DUST,
+        discriminators::SET_ROUND_TO,
+        discriminators::SET_MAX_DEPOSIT_PER_TX,
+        discriminators::SET_DEPOSIT_LIMITS,
+        discriminators::SET_RENT_BUFFER,
+        discriminators::SET_FIRST_WITHDRAW_DELAY,
+        discriminators::SET_LABEL,
+        discriminators::SET_METADATA,
+        discriminators::CLEAR_METADATA,
+        discriminators::SET_KEEPER_SWEEP,
+        discriminators::SET_SANDWICH_GUARD,
+        discriminators::SET_MAX_SLOT_AGE,
+        discriminators::SET_LOG_LEVEL,
+        discriminators::SET_ADMIN,
+        discriminators::SET_CLOSE_AUTHORITY,
+        discriminators::SET_WITHDRAW_POLICY,
+        discriminators::SET_GUARDIANS,
+        discriminators::BIND_RECIPIENT,
+        discriminators::SET_AUTO_FORWARD,
+        discriminators::GUARDIAN_RECOVER,
+        discriminators::READ_STATE,
+        discriminators::DERIVE_VAULT,
+        discriminators::CONFIGURE,
+        discriminators::HARVEST,
+        discriminators::INITIALIZE_STAKE_VAULT,
+        discriminators::DELEGATE,
+        discriminators::DEACTIVATE,
+        discriminators::WITHDRAW_STAKE,
+        discriminators::INITIALIZE_SHARED,
+        discriminators::WITHDRAW_SHARED,
+        discriminators::RESIZE_STATE,
+        discriminators::RECOVER_STATE_LAMPORTS,
+        discriminators::NORMALIZE_BUMP,
+        discriminators::ASSERT_BALANCE,
+        discriminators::INITIALIZE_GLOBAL_CONFIG,
+        discriminators::SET_EMERGENCY_PAUSED,
+        discriminators::SET_ALLOWLIST_ROOT,
+    ];
+    for discriminator in &exported {
+        assert!(dispatched(discriminator), "{discriminator:?} was not dispatched");
+    }
+    assert!(!dispatched(&[0; 8]));
+}
+
+// `ConfigureIx` with every field set, its worst case
+fn configure_all_fields() -> ConfigureIx {
+    ConfigureIx {
+        yield_program: Some(Pubkey::new_unique()),
+        cooldown_slots: Some(100),
+        burn_dust: Some(true),
+        max_deposit_per_tx: Some(5_000_000_000),
+        min_deposit: Some(1_000),
+        balance_cap: Some(100_000_000_000),
+        rent_buffer_bps: Some(5_000),
+        first_withdraw_delay: Some(100),
+        label: Some(vec![b'a'; 32]),
+        sweep_threshold: Some(1_000_000_000),
+        keeper_tip: Some(10_000),
+        sandwich_guard: Some(true),
+    }
+}
+
+// The instruction `test_compute_unit_benchmarking` runs under `name`
+fn benched_ix(name: &str) -> VaultIxSet {
+    match name {
+        "initialize_vault" => VaultIxSet::Initialize(InitializeIx { proof: vec![], currency: SOL_CURRENCY }),
+        "deposit_1_sol" => VaultIxSet::Deposit(DepositIx { amount: 1_000_000_000 }),
+        "deposit_raw_1_sol" => VaultIxSet::DepositRaw(DepositRawIx { amount: 1_000_000_000 }),
+        "deposit_to_owner_1_sol" => VaultIxSet::DepositToOwner(DepositToOwnerIx { owner: Pubkey::new_unique(), amount: 1_000_000_000 }),
+        "withdraw_0.5_sol" => VaultIxSet::Withdraw(WithdrawIx { amount: 500_000_000 }),
+        "withdraw_to_program_0.5_sol" => VaultIxSet::WithdrawToProgram(WithdrawToProgramIx { amount: 500_000_000 }),
+        "close_vault_with_2_sol" => VaultIxSet::Close(CloseIx),
+        "safe_close" => VaultIxSet::SafeClose(SafeCloseIx),
+        "tip_1_sol" => VaultIxSet::Tip(TipIx { amount: 1_000_000_000 }),
+        "withdraw_percent_50" => VaultIxSet::WithdrawPercent(WithdrawPercentIx { bps: 5_000 }),
+        "keeper_sweep" => VaultIxSet::KeeperSweep(KeeperSweepIx),
+        "request_withdraw" => VaultIxSet::RequestWithdraw(RequestWithdrawIx { recipient: Pubkey::new_unique(), amount: 500_000_000, release_slot: 100 }),
+        "execute_withdraw" => VaultIxSet::ExecuteWithdraw(ExecuteWithdrawIx),
+        "cancel_withdraw" => VaultIxSet::CancelWithdraw(CancelWithdrawIx),
+        "wrap_deposit_1_sol" => VaultIxSet::WrapDeposit(WrapDepositIx { amount: 1_000_000_000 }),
+        "withdraw_token_batch_8" => VaultIxSet::WithdrawTokenBatch(WithdrawTokenBatchIx { amounts: vec![100; 8] }),
+        "set_withdraw_cooldown" => VaultIxSet::SetWithdrawCooldown(SetWithdrawCooldownIx { cooldown_slots: 100 }),
+        "set_label_32_bytes" => VaultIxSet::SetLabel(SetLabelIx { label: vec![b'a'; 32] }),
+        "configure_all_fields" => VaultIxSet::Configure(configure_all_fields()),
+        "assert_balance" => VaultIxSet::AssertBalance(AssertBalanceIx { expected: 1_000_000_000 }),
+        "withdraw_guarded_0.5_sol" => VaultIxSet::Withdraw(WithdrawIx { amount: 500_000_000 }),
+        "withdraw_with_approval_0.5_sol" => VaultIxSet::WithdrawWithApproval(WithdrawWithApprovalIx {
+            amount: 500_000_000,
+            nonce: 0,
+            expiry: 0,
+            valid_until_slot: u64::MAX,
+        }),
+        "initialize_and_deposit_1_sol" => {
+            VaultIxSet::InitializeAndDeposit(InitializeAndDepositIx { amount: 1_000_000_000, proof: vec![], currency: SOL_CURRENCY })
+        }
+        "set_yield_program" => VaultIxSet::SetYieldProgram(SetYieldProgramIx { yield_program: Pubkey::new_unique() }),
+        "set_burn_dust" => VaultIxSet::SetBurnDust(SetBurnDustIx { burn_dust: true }),
+        "set_round_to" => VaultIxSet::SetRoundTo(SetRoundToIx { round_to: 1_000_000_000 }),
+        "set_max_deposit_per_tx" => VaultIxSet::SetMaxDepositPerTx(SetMaxDepositPerTxIx { max_deposit_per_tx: 5_000_000_000 }),
+        "set_deposit_limits" => VaultIxSet::SetDepositLimits(SetDepositLimitsIx { min_deposit: 1_000, balance_cap: 100_000_000_000 }),
+        "set_rent_buffer" => VaultIxSet::SetRentBuffer(SetRentBufferIx { rent_buffer_bps: 5_000 }),
+        "set_first_withdraw_delay" => VaultIxSet::SetFirstWithdrawDelay(SetFirstWithdrawDelayIx { first_withdraw_delay: 100 }),
+        "set_keeper_sweep" => VaultIxSet::SetKeeperSweep(SetKeeperSweepIx { sweep_threshold: 1_000_000_000, keeper_tip: 10_000 }),
+        "set_sandwich_guard" => VaultIxSet::SetSandwichGuard(SetSandwichGuardIx { sandwich_guard: true }),
+        "set_max_slot_age" => VaultIxSet::SetMaxSlotAge(SetMaxSlotAgeIx { max_slot_age: 150 }),
+        "set_log_level" => VaultIxSet::SetLogLevel(SetLogLevelIx { log_level: LogLevel::Summary as u8 }),
+        "set_admin" => VaultIxSet::SetAdmin(SetAdminIx { admin: Pubkey::new_unique() }),
+        "set_close_authority" => VaultIxSet::SetCloseAuthority(SetCloseAuthorityIx { close_authority: Pubkey::new_unique() }),
+        "set_withdraw_policy" => VaultIxSet::SetWithdrawPolicy(SetWithdrawPolicyIx { policy: WithdrawPolicy::RateLimit as u8 }),
+        "set_guardians" => VaultIxSet::SetGuardians(SetGuardiansIx { guardians: [Pubkey::new_unique(); 3], threshold: 2 }),
+        "bind_recipient" => VaultIxSet::BindRecipient(BindRecipientIx { recipient: Pubkey::new_unique() }),
+        "set_auto_forward" => VaultIxSet::SetAutoForward(SetAutoForwardIx { auto_forward: Pubkey::new_unique() }),
+        "set_metadata_256_bytes" => VaultIxSet::SetMetadata(SetMetadataIx { data: vec![b'a'; 256] }),
+        "clear_metadata" => VaultIxSet::ClearMetadata(ClearMetadataIx),
+        "guardian_recover_2_of_3" => VaultIxSet::GuardianRecover(GuardianRecoverIx { new_owner: Pubkey::new_unique() }),
+        "close_many_4" => VaultIxSet::CloseMany(CloseManyIx),
+        "resize_state" => VaultIxSet::ResizeState(ResizeStateIx),
+        "recover_state_lamports" => VaultIxSet::RecoverStateLamports(RecoverStateLamportsIx),
+        "normalize_bump" => VaultIxSet::NormalizeBump(NormalizeBumpIx),
+        "read_state" => VaultIxSet::ReadState(ReadStateIx),
+        "derive_vault" => VaultIxSet::DeriveVault(DeriveVaultIx { state: Pubkey::new_unique() }),
+        "initialize_global_config" => VaultIxSet::InitializeGlobalConfig(InitializeGlobalConfigIx),
+        "set_emergency_paused" => VaultIxSet::SetEmergencyPaused(SetEmergencyPausedIx { paused: true }),
+        "set_allowlist_root" => VaultIxSet::SetAllowlistRoot(SetAllowlistRootIx { allowlist_root: [1; 32] }),
+        "initialize_shared_2_owners" => VaultIxSet::InitializeShared(InitializeSharedIx { owners: vec![] }),
+        "withdraw_shared" => VaultIxSet::WithdrawShared(WithdrawSharedIx { amount: 1_000_000_000 }),
+        "initialize_stake_vault" => VaultIxSet::InitializeStakeVault(InitializeStakeVaultIx { lamports: 0 }),
+        "delegate" => VaultIxSet::Delegate(DelegateIx),
+        "deactivate" => VaultIxSet::Deactivate(DeactivateIx),
+        "withdraw_stake" => VaultIxSet::WithdrawStake(WithdrawStakeIx { lamports: 0 }),
+        _ => panic!("no benched instruction named {name}"),
+    }
+}
+
+// The client's compute unit limit for the run named `name`. `CloseManyIx` only lists its vaults
+// in the accounts, so the limit for those runs comes from the vault count.
+fn benched_cu(name: &str) -> u32 {
+    match name {
+        "close_many_4" => close_many_cu(4),
+        _ => recommended_cu(&benched_ix(name)),
+    }
+}
+
+#[test]
+fn test_recorded_benchmarks_fit_recommended_cu() {
+    // The bencher prepends each run that changed anything, so the first table is the latest
+    let results = include_str!("../benches/results/compute_units.md");
+    let latest = results.split("#### ").nth(1).expect("a recorded bench run");
+    let recorded = latest.lines().filter_map(|line| {
+        let mut cells = line.split('|').skip(1).map(str::trim);
+        let name = cells.next()?;
+        let consumed = cells.next()?.parse::<u32>().ok()?;
+        Some((name, consumed))
+    });
+    for (name, consumed) in recorded {
+        let limit = benched_cu(name);
+        assert!(consumed <= limit, "{name} benched at {consumed} CUs, recommended {limit}");
+    }
+
+    let owner = Pubkey::new_unique();
+    let (state_pda, _) = find_vault_state_pda(&owner);
+    let (vault_pda, _) = find_vault_pda(&state_pda);
+    let deposit_instruction = create_deposit_instruction(&owner, &vault_pda, &state_pda, 1_000_000_000);
+
+    let ix = VaultIxSet::Deposit(DepositIx { amount: 1_000_000_000 });
+    let [limit, deposit] = with_compute_unit_limit(&ix, deposit_instruction.clone());
+    assert_eq!(limit.program_id, COMPUTE_BUDGET_PROGRAM_ID);
+    assert!(limit.accounts.is_empty());
+    let mut expected_data = vec![2];
+    expected_data.extend_from_slice(&recommended_cu(&ix).to_le_bytes());
+    assert_eq!(limit.data, expected_data);
+    assert_eq!(deposit, deposit_instruction);
+}
+
 #[test]
 fn test_compute_unit_benchmarking() {
     use mollusk_svm_bencher::MolluskComputeUnitBencher;
@@ -469,7 +7133,8 @@ fn test_compute_unit_benchmarking() {
     let (state_pda, state_bump) = find_vault_state_pda(&owner);
     let (vault_pda, vault_bump) = find_vault_pda(&state_pda);
 
-    let mollusk = create_mollusk();
+    let mut mollusk = create_mollusk();
+    token::add_program(&mut mollusk);
 
     let (system_program_key, system_program_account) = mollusk_svm::program::keyed_account_for_system_program();
 
@@ -484,6 +7149,7 @@ fn test_compute_unit_benchmarking() {
         (state_pda, state_account),
         (vault_pda, vault_account),
         (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
     // Deposit benchmark
@@ -505,8 +7171,23 @@ fn test_compute_unit_benchmarking() {
         (vault_pda, vault_account_with_rent),
         (state_pda, vault_state_account.clone()),
         (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Raw deposit benchmark
+    let deposit_raw_instruction = create_deposit_raw_instruction(&owner, &vault_pda, 1_000_000_000);
+    let deposit_raw_accounts = vec![
+        (owner, Account::new(8_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
+    // The raw path skips state validation, so it must be cheaper than the checked deposit
+    let deposit_cus = mollusk.process_instruction(&deposit_instruction, &deposit_accounts).compute_units_consumed;
+    let deposit_raw_cus = mollusk.process_instruction(&deposit_raw_instruction, &deposit_raw_accounts).compute_units_consumed;
+    assert!(deposit_raw_cus < deposit_cus, "raw deposit used {deposit_raw_cus} CUs, checked deposit used {deposit_cus}");
+
     // Withdraw benchmark
     let user_account_withdraw = Account::new(5_000_000_000, 0, &system_program::id());
     let vault_account_withdraw = Account::new(3_000_000_000, 0, &system_program::id());
@@ -517,6 +7198,7 @@ fn test_compute_unit_benchmarking() {
         (vault_pda, vault_account_withdraw),
         (state_pda, vault_state_account.clone()),
         (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
     ];
 
     // Close benchmark
@@ -527,16 +7209,453 @@ fn test_compute_unit_benchmarking() {
     let close_accounts = vec![
         (owner, user_account_close),
         (vault_pda, vault_account_close),
+        (state_pda, vault_state_account.clone()),
+        (system_program_key, system_program_account.clone()),
+    ];
+
+    // Tip benchmark
+    let tipper = Pubkey::new_unique();
+    let tip_instruction = create_tip_instruction(&tipper, &vault_pda, &state_pda, 1_000_000_000);
+    let tip_accounts = vec![
+        (tipper, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Withdraw percent benchmark
+    let withdraw_percent_instruction = create_withdraw_percent_instruction(&owner, &vault_pda, &state_pda, 5_000);
+    let withdraw_percent_accounts = withdraw_accounts.clone();
+
+    // Keeper sweep benchmark, with a tip so both transfers run
+    let keeper = Pubkey::new_unique();
+    let mut sweep_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    sweep_state_data[SWEEP_THRESHOLD_OFFSET..KEEPER_TIP_OFFSET].copy_from_slice(&1_000_000_000u64.to_le_bytes());
+    sweep_state_data[KEEPER_TIP_OFFSET..PENDING_RECIPIENT_OFFSET].copy_from_slice(&10_000u64.to_le_bytes());
+    let keeper_sweep_instruction = create_keeper_sweep_instruction(&keeper, &vault_pda, &state_pda, &owner);
+    let keeper_sweep_accounts = vec![
+        (keeper, Account::new(1_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, Account { data: sweep_state_data, ..vault_state_account.clone() }),
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Escrowed withdraw benchmarks. The execute run starts from a request already released.
+    let recipient = Pubkey::new_unique();
+    let request_withdraw_instruction = create_request_withdraw_instruction(&owner, &vault_pda, &state_pda, &recipient, 500_000_000, 100);
+    let request_withdraw_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let mut pending_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    pending_state_data[PENDING_RECIPIENT_OFFSET..PENDING_AMOUNT_OFFSET].copy_from_slice(recipient.as_ref());
+    pending_state_data[PENDING_AMOUNT_OFFSET..PENDING_RELEASE_SLOT_OFFSET].copy_from_slice(&500_000_000u64.to_le_bytes());
+    let pending_state_account = Account { data: pending_state_data, ..vault_state_account.clone() };
+    let execute_withdraw_instruction = create_execute_withdraw_instruction(&vault_pda, &state_pda, &recipient);
+    let execute_withdraw_accounts = vec![
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, pending_state_account.clone()),
+        (recipient, Account::new(1_000_000_000, 0, &system_program::id())),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let cancel_withdraw_instruction = create_cancel_withdraw_instruction(&owner, &state_pda);
+    let cancel_withdraw_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (state_pda, pending_state_account),
+    ];
+
+    // Wrap deposit benchmark
+    let wsol_account = Pubkey::new_unique();
+    let wrap_deposit_instruction = create_wrap_deposit_instruction(&owner, &vault_pda, &state_pda, &wsol_account, 1_000_000_000);
+    let wrap_deposit_accounts = vec![
+        (owner, Account::new(8_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (wsol_account, create_wsol_account(&mollusk, &vault_pda)),
+        token::keyed_account(),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Settings benchmarks
+    let owner_signer = (owner, Account::new(5_000_000_000, 0, &system_program::id()));
+    let settings_accounts = vec![owner_signer, (state_pda, vault_state_account.clone())];
+    let set_withdraw_cooldown_instruction = create_set_withdraw_cooldown_instruction(&owner, &state_pda, 100);
+    let set_label_instruction = create_set_label_instruction(&owner, &state_pda, &[b'a'; 32]);
+    let configure_instruction = create_configure_instruction(&owner, &state_pda, &configure_all_fields());
+
+    // Deposit to owner benchmark, paid by someone other than the owner
+    let funder = Pubkey::new_unique();
+    let deposit_to_owner_instruction = create_deposit_to_owner_instruction(&funder, &vault_pda, &state_pda, &owner, 1_000_000_000);
+    let deposit_to_owner_accounts = vec![
+        (funder, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Withdraw to program benchmark, into another program's PDA
+    let (program_pda, _) = Pubkey::find_program_address(&[b"escrow"], &Pubkey::new_unique());
+    let program_pda_account = Account {
+        lamports: mollusk.sysvars.rent.minimum_balance(16),
+        data: vec![7; 16],
+        owner: Pubkey::new_unique(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    let withdraw_to_program_instruction = create_withdraw_to_program_instruction(&owner, &vault_pda, &state_pda, &program_pda, 500_000_000);
+    let withdraw_to_program_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (program_pda, program_pda_account),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Safe close benchmark, of a vault holding only its rent
+    let safe_close_instruction = create_safe_close_instruction(&owner, &vault_pda, &state_pda);
+    let safe_close_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (system_program_key, system_program_account.clone()),
+    ];
+
+    // Token batch benchmark, paying 8 recipients
+    let mint = Pubkey::new_unique();
+    let token_source = Pubkey::new_unique();
+    let token_recipients: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+    let withdraw_token_batch_instruction =
+        create_withdraw_token_batch_instruction(&owner, &vault_pda, &state_pda, &token_source, &token_recipients, &[100; 8]);
+    let mut withdraw_token_batch_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (token_source, create_token_account(&mollusk, &mint, &vault_pda, 1_000)),
+        token::keyed_account(),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    withdraw_token_batch_accounts.extend(
+        token_recipients.iter().map(|recipient| (*recipient, create_token_account(&mollusk, &mint, &Pubkey::new_unique(), 0))),
+    );
+
+    // Withdraw behind a compute budget instruction with the sandwich guard scanning it
+    let mut guarded_state_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    guarded_state_data[SANDWICH_GUARD_OFFSET] = 1;
+    let guarded_withdraw_instruction = create_guarded_withdraw_instruction(&owner, &vault_pda, &state_pda, 500_000_000);
+    let [compute_limit_instruction, _] =
+        with_compute_unit_limit(&benched_ix("withdraw_guarded_0.5_sol"), guarded_withdraw_instruction.clone());
+    let guarded_withdraw_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, Account { data: guarded_state_data, ..vault_state_account.clone() }),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+        (
+            solana_instructions_sysvar::ID,
+            create_instructions_sysvar_account(&[&compute_limit_instruction, &guarded_withdraw_instruction], 1),
+        ),
+    ];
+
+    // Relayed withdrawal of an owner-signed approval
+    let approver = Keypair::new();
+    let (approver_state, approver_state_bump) = find_vault_state_pda(&approver.pubkey());
+    let (approver_vault, approver_vault_bump) = find_vault_pda(&approver_state);
+    let approval_recipient = Pubkey::new_unique();
+    let approval_expiry = mollusk.sysvars.clock.unix_timestamp + 60;
+    let approval_signature_instruction =
+        create_approval_signature_instruction(&approver, &approver_state, &approval_recipient, 500_000_000, 0, approval_expiry, u64::MAX);
+    let withdraw_with_approval_instruction = create_withdraw_with_approval_instruction(
+        &approval_recipient,
+        &approver_vault,
+        &approver_state,
+        500_000_000,
+        0,
+        approval_expiry,
+        u64::MAX,
+    );
+    let withdraw_with_approval_accounts = vec![
+        (approval_recipient, Account::new(0, 0, &system_program::id())),
+        (approver_vault, Account::new(3_000_000_000, 0, &system_program::id())),
+        (
+            approver_state,
+            Account { data: create_vault_state_data(&approver.pubkey(), approver_state_bump, approver_vault_bump), ..vault_state_account.clone() },
+        ),
+        (
+            solana_instructions_sysvar::ID,
+            create_instructions_sysvar_account(&[&approval_signature_instruction, &withdraw_with_approval_instruction], 1),
+        ),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Initialize and deposit benchmark, into a fresh vault like the initialize run
+    let initialize_and_deposit_instruction = create_initialize_and_deposit_instruction(&owner, &state_pda, &vault_pda, 1_000_000_000);
+    let initialize_and_deposit_accounts = initialize_accounts.clone();
+
+    // The remaining settings, each signed by the owner, who is also the admin until one is set
+    let guardians = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let set_yield_program_instruction = create_set_yield_program_instruction(&owner, &state_pda, &Pubkey::new_unique());
+    let set_burn_dust_instruction = create_set_burn_dust_instruction(&owner, &state_pda, true);
+    let set_round_to_instruction = create_set_round_to_instruction(&owner, &state_pda, 1_000_000_000);
+    let set_max_deposit_per_tx_instruction = create_set_max_deposit_per_tx_instruction(&owner, &state_pda, 5_000_000_000);
+    let set_deposit_limits_instruction = create_set_deposit_limits_instruction(&owner, &state_pda, 1_000, 100_000_000_000);
+    let set_rent_buffer_instruction = create_set_rent_buffer_instruction(&owner, &state_pda, 5_000);
+    let set_first_withdraw_delay_instruction = create_set_first_withdraw_delay_instruction(&owner, &state_pda, 100);
+    let set_keeper_sweep_instruction = create_set_keeper_sweep_instruction(&owner, &state_pda, 1_000_000_000, 10_000);
+    let set_sandwich_guard_instruction = create_set_sandwich_guard_instruction(&owner, &state_pda, true);
+    let set_max_slot_age_instruction = create_set_max_slot_age_instruction(&owner, &state_pda, 150);
+    let set_log_level_instruction = create_set_log_level_instruction(&owner, &state_pda, LogLevel::Summary as u8);
+    let set_admin_instruction = create_set_admin_instruction(&owner, &state_pda, &Pubkey::new_unique());
+    let set_close_authority_instruction = create_set_close_authority_instruction(&owner, &state_pda, &Pubkey::new_unique());
+    let set_withdraw_policy_instruction = create_set_withdraw_policy_instruction(&owner, &state_pda, WithdrawPolicy::RateLimit as u8);
+    let set_guardians_instruction = create_set_guardians_instruction(&owner, &state_pda, &guardians, 2);
+    let bind_recipient_instruction = create_bind_recipient_instruction(&owner, &state_pda, &Pubkey::new_unique());
+    let set_auto_forward_instruction = create_set_auto_forward_instruction(&owner, &state_pda, &Pubkey::new_unique());
+
+    // Metadata benchmarks, opening the account and then closing it again
+    let (metadata_pda, _) = find_vault_metadata_pda(&state_pda);
+    let set_metadata_instruction = create_set_metadata_instruction(&owner, &state_pda, &[b'a'; 256]);
+    let set_metadata_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (metadata_pda, Account::default()),
+        (system_program_key, system_program_account.clone()),
+    ];
+    let clear_metadata_instruction = create_clear_metadata_instruction(&owner, &state_pda);
+    let clear_metadata_accounts = mollusk.process_instruction(&set_metadata_instruction, &set_metadata_accounts).resulting_accounts;
+
+    // Guardian recovery benchmark, by two of three guardians
+    let mut guarded_by_data = create_vault_state_data(&owner, state_bump, vault_bump);
+    guarded_by_data[GUARDIANS_OFFSET..GUARDIAN_THRESHOLD_OFFSET].copy_from_slice(&guardians.map(|guardian| guardian.to_bytes()).concat());
+    guarded_by_data[GUARDIAN_THRESHOLD_OFFSET] = 2;
+    let guardian_recover_instruction = create_guardian_recover_instruction(&state_pda, &Pubkey::new_unique(), &guardians[..2]);
+    let mut guardian_recover_accounts = vec![(state_pda, Account { data: guarded_by_data, ..vault_state_account.clone() })];
+    guardian_recover_accounts.extend(guardians[..2].iter().map(|guardian| (*guardian, Account::new(1_000_000_000, 0, &system_program::id()))));
+
+    // Close many benchmark, the owner's vault plus three taken over from other owners
+    let mut close_many_vaults = vec![];
+    let mut close_many_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (system_program_key, system_program_account.clone()),
+    ];
+    for original_owner in [owner, Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()] {
+        let (other_state, other_state_bump) = find_vault_state_pda(&original_owner);
+        let (other_vault, other_vault_bump) = find_vault_pda(&other_state);
+        close_many_accounts.push((other_vault, Account::new(2_000_000_000, 0, &system_program::id())));
+        close_many_accounts.push((
+            other_state,
+            Account { data: create_vault_state_data(&owner, other_state_bump, other_vault_bump), ..vault_state_account.clone() },
+        ));
+        close_many_vaults.push((other_vault, other_state));
+    }
+    let close_many_instruction = create_close_many_instruction(&owner, &close_many_vaults);
+
+    // State maintenance benchmarks: growing an original layout state, returning lamports sent to
+    // the state, and moving a vault off a non-canonical bump
+    let resize_state_instruction = create_resize_state_instruction(&owner, &state_pda);
+    let resize_state_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (
+            state_pda,
+            Account {
+                lamports: mollusk.sysvars.rent.minimum_balance(VAULT_STATE_V0_LEN),
+                data: vault_state_account.data[..VAULT_STATE_V0_LEN].to_vec(),
+                ..vault_state_account.clone()
+            },
+        ),
+        (system_program_key, system_program_account.clone()),
+    ];
+    let recover_state_lamports_instruction = create_recover_state_lamports_instruction(&owner, &state_pda);
+    let recover_state_lamports_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (state_pda, Account { lamports: vault_state_account.lamports + 500_000_000, ..vault_state_account.clone() }),
+    ];
+    let (stale_vault, stale_bump) = (0..vault_bump)
+        .rev()
+        .find_map(|bump| {
+            Pubkey::create_program_address(&[VAULT_SEED, state_pda.as_ref(), &[bump]], &PROGRAM_ID)
+                .ok()
+                .map(|address| (address, bump))
+        })
+        .unwrap();
+    let normalize_bump_instruction = create_normalize_bump_instruction(&owner, &stale_vault, &vault_pda, &state_pda);
+    let normalize_bump_accounts = vec![
+        (owner, Account::new(5_000_000_000, 0, &system_program::id())),
+        (stale_vault, Account::new(2_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::default()),
+        (state_pda, Account { data: create_vault_state_data(&owner, state_bump, stale_bump), ..vault_state_account.clone() }),
+        (system_program_key, system_program_account.clone()),
+    ];
+
+    // Read-only benchmarks
+    let read_state_instruction = create_read_state_instruction(&vault_pda, &state_pda);
+    let read_state_accounts = vec![
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+    ];
+    let derive_vault_instruction = create_derive_vault_instruction(&state_pda);
+    let derive_vault_accounts = vec![];
+
+    // Global config benchmarks, signed by its admin
+    let (global_config_pda, _) = find_global_config_pda();
+    let (program_data, program_data_account) = keyed_account_for_program_data(&mollusk, Some(&GLOBAL_CONFIG_ADMIN));
+    let initialize_global_config_instruction =
+        create_initialize_global_config_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, &program_data);
+    let initialize_global_config_accounts = vec![
+        (GLOBAL_CONFIG_ADMIN, Account::new(1_000_000_000, 0, &system_program::id())),
+        (global_config_pda, Account::default()),
+        (program_data, program_data_account),
+        (system_program_key, system_program_account.clone()),
+    ];
+    let set_emergency_paused_instruction = create_set_emergency_paused_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, true);
+    let set_allowlist_root_instruction = create_set_allowlist_root_instruction(&GLOBAL_CONFIG_ADMIN, &global_config_pda, &[1; 32]);
+    let global_config_admin_accounts = vec![
+        (GLOBAL_CONFIG_ADMIN, Account::new(1_000_000_000, 0, &system_program::id())),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+
+    // Shared vault benchmarks, opened for two owners and then withdrawn from by one
+    let shared_owner = Pubkey::new_unique();
+    let (shared_state_pda, _) = find_shared_state_pda(&owner);
+    let (shared_vault_pda, _) = find_vault_pda(&shared_state_pda);
+    let initialize_shared_instruction =
+        create_initialize_shared_instruction(&owner, &shared_state_pda, &shared_vault_pda, &[(shared_owner, 6_000), (Pubkey::new_unique(), 4_000)]);
+    let initialize_shared_accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (shared_state_pda, Account::default()),
+        (shared_vault_pda, Account::default()),
+        (system_program_key, system_program_account.clone()),
+    ];
+    let mut withdraw_shared_accounts =
+        mollusk.process_instruction(&initialize_shared_instruction, &initialize_shared_accounts).resulting_accounts;
+    withdraw_shared_accounts.retain(|(key, _)| *key != owner);
+    withdraw_shared_accounts.push((shared_owner, Account::new(1_000_000_000, 0, &system_program::id())));
+    withdraw_shared_accounts.push(keyed_account_for_global_config(&mollusk, false));
+    for (key, account) in &mut withdraw_shared_accounts {
+        if *key == shared_vault_pda {
+            account.lamports += 5_000_000_000;
+        }
+    }
+    let withdraw_shared_instruction = create_withdraw_shared_instruction(&shared_owner, &shared_vault_pda, &shared_state_pda, 1_000_000_000);
+
+    // Stake benchmarks, each starting from the accounts the one before left behind
+    let stake_pda = find_stake_vault_pda(&vault_pda);
+    let vote_pubkey = Pubkey::new_unique();
+    let stake_lamports = mollusk.sysvars.rent.minimum_balance(STAKE_ACCOUNT_LEN) + 2_000_000_000;
+    let initialize_stake_vault_instruction = create_initialize_stake_vault_instruction(&owner, &vault_pda, &state_pda, stake_lamports);
+    let initialize_stake_vault_accounts = vec![
+        (owner, Account::new(10_000_000_000, 0, &system_program::id())),
+        (vault_pda, Account::new(mollusk.sysvars.rent.minimum_balance(0), 0, &system_program::id())),
+        (state_pda, vault_state_account.clone()),
+        (stake_pda, Account::default()),
+        (vote_pubkey, create_vote_account(&mollusk, &Pubkey::new_unique())),
+        mollusk.sysvars.keyed_account_for_rent_sysvar(),
+        mollusk.sysvars.keyed_account_for_clock_sysvar(),
+        mollusk.sysvars.keyed_account_for_stake_history_sysvar(),
+        (STAKE_CONFIG_ID, Account::default()),
+        mollusk_svm::program::create_keyed_account_for_builtin_program(&STAKE_PROGRAM_ID, "solana_stake_program"),
+        (system_program_key, system_program_account.clone()),
+        keyed_account_for_global_config(&mollusk, false),
+    ];
+    let delegate_instruction = create_delegate_instruction(&owner, &vault_pda, &state_pda, &vote_pubkey);
+    let delegate_accounts =
+        mollusk.process_instruction(&initialize_stake_vault_instruction, &initialize_stake_vault_accounts).resulting_accounts;
+    let deactivate_instruction = create_deactivate_instruction(&owner, &vault_pda, &state_pda);
+    let deactivate_accounts = mollusk.process_instruction(&delegate_instruction, &delegate_accounts).resulting_accounts;
+    // Deactivated in the epoch it was delegated, so it can all be withdrawn at once
+    let withdraw_stake_instruction = create_withdraw_stake_instruction(&owner, &vault_pda, &state_pda, stake_lamports);
+    let withdraw_stake_accounts = mollusk.process_instruction(&deactivate_instruction, &deactivate_accounts).resulting_accounts;
+
+    // Assert balance benchmark
+    let assert_balance_instruction = create_assert_balance_instruction(&vault_pda, &state_pda, 1_000_000_000);
+    let assert_balance_accounts = vec![
+        (vault_pda, Account::new(3_000_000_000, 0, &system_program::id())),
         (state_pda, vault_state_account),
-        (system_program_key, system_program_account),
     ];
 
-    MolluskComputeUnitBencher::new(mollusk)
-        .bench(("initialize_vault", &initialize_instruction, &initialize_accounts))
-        .bench(("deposit_1_sol", &deposit_instruction, &deposit_accounts))
-        .bench(("withdraw_0.5_sol", &withdraw_instruction, &withdraw_accounts))
-        .bench(("close_vault_with_2_sol", &close_instruction, &close_accounts))
+    let runs = [
+        ("initialize_vault", &initialize_instruction, &initialize_accounts),
+        ("deposit_1_sol", &deposit_instruction, &deposit_accounts),
+        ("deposit_raw_1_sol", &deposit_raw_instruction, &deposit_raw_accounts),
+        ("deposit_to_owner_1_sol", &deposit_to_owner_instruction, &deposit_to_owner_accounts),
+        ("withdraw_0.5_sol", &withdraw_instruction, &withdraw_accounts),
+        ("withdraw_to_program_0.5_sol", &withdraw_to_program_instruction, &withdraw_to_program_accounts),
+        ("close_vault_with_2_sol", &close_instruction, &close_accounts),
+        ("safe_close", &safe_close_instruction, &safe_close_accounts),
+        ("tip_1_sol", &tip_instruction, &tip_accounts),
+        ("withdraw_percent_50", &withdraw_percent_instruction, &withdraw_percent_accounts),
+        ("keeper_sweep", &keeper_sweep_instruction, &keeper_sweep_accounts),
+        ("request_withdraw", &request_withdraw_instruction, &request_withdraw_accounts),
+        ("execute_withdraw", &execute_withdraw_instruction, &execute_withdraw_accounts),
+        ("cancel_withdraw", &cancel_withdraw_instruction, &cancel_withdraw_accounts),
+        ("wrap_deposit_1_sol", &wrap_deposit_instruction, &wrap_deposit_accounts),
+        ("withdraw_token_batch_8", &withdraw_token_batch_instruction, &withdraw_token_batch_accounts),
+        ("set_withdraw_cooldown", &set_withdraw_cooldown_instruction, &settings_accounts),
+        ("set_label_32_bytes", &set_label_instruction, &settings_accounts),
+        ("configure_all_fields", &configure_instruction, &settings_accounts),
+        ("assert_balance", &assert_balance_instruction, &assert_balance_accounts),
+        ("withdraw_guarded_0.5_sol", &guarded_withdraw_instruction, &guarded_withdraw_accounts),
+        ("withdraw_with_approval_0.5_sol", &withdraw_with_approval_instruction, &withdraw_with_approval_accounts),
+        ("initialize_and_deposit_1_sol", &initialize_and_deposit_instruction, &initialize_and_deposit_accounts),
+        ("set_yield_program", &set_yield_program_instruction, &settings_accounts),
+        ("set_burn_dust", &set_burn_dust_instruction, &settings_accounts),
+        ("set_round_to", &set_round_to_instruction, &settings_accounts),
+        ("set_max_deposit_per_tx", &set_max_deposit_per_tx_instruction, &settings_accounts),
+        ("set_deposit_limits", &set_deposit_limits_instruction, &settings_accounts),
+        ("set_rent_buffer", &set_rent_buffer_instruction, &settings_accounts),
+        ("set_first_withdraw_delay", &set_first_withdraw_delay_instruction, &settings_accounts),
+        ("set_keeper_sweep", &set_keeper_sweep_instruction, &settings_accounts),
+        ("set_sandwich_guard", &set_sandwich_guard_instruction, &settings_accounts),
+        ("set_max_slot_age", &set_max_slot_age_instruction, &settings_accounts),
+        ("set_log_level", &set_log_level_instruction, &settings_accounts),
+        ("set_admin", &set_admin_instruction, &settings_accounts),
+        ("set_close_authority", &set_close_authority_instruction, &settings_accounts),
+        ("set_withdraw_policy", &set_withdraw_policy_instruction, &settings_accounts),
+        ("set_guardians", &set_guardians_instruction, &settings_accounts),
+        ("bind_recipient", &bind_recipient_instruction, &settings_accounts),
+        ("set_auto_forward", &set_auto_forward_instruction, &settings_accounts),
+        ("set_metadata_256_bytes", &set_metadata_instruction, &set_metadata_accounts),
+        ("clear_metadata", &clear_metadata_instruction, &clear_metadata_accounts),
+        ("guardian_recover_2_of_3", &guardian_recover_instruction, &guardian_recover_accounts),
+        ("close_many_4", &close_many_instruction, &close_many_accounts),
+        ("resize_state", &resize_state_instruction, &resize_state_accounts),
+        ("recover_state_lamports", &recover_state_lamports_instruction, &recover_state_lamports_accounts),
+        ("normalize_bump", &normalize_bump_instruction, &normalize_bump_accounts),
+        ("read_state", &read_state_instruction, &read_state_accounts),
+        ("derive_vault", &derive_vault_instruction, &derive_vault_accounts),
+        ("initialize_global_config", &initialize_global_config_instruction, &initialize_global_config_accounts),
+        ("set_emergency_paused", &set_emergency_paused_instruction, &global_config_admin_accounts),
+        ("set_allowlist_root", &set_allowlist_root_instruction, &global_config_admin_accounts),
+        ("initialize_shared_2_owners", &initialize_shared_instruction, &initialize_shared_accounts),
+        ("withdraw_shared", &withdraw_shared_instruction, &withdraw_shared_accounts),
+        ("initialize_stake_vault", &initialize_stake_vault_instruction, &initialize_stake_vault_accounts),
+        ("delegate", &delegate_instruction, &delegate_accounts),
+        ("deactivate", &deactivate_instruction, &deactivate_accounts),
+        ("withdraw_stake", &withdraw_stake_instruction, &withdraw_stake_accounts),
+    ];
+
+    // Client compute unit limits must cover the benched usage
+    for (name, instruction, accounts) in runs {
+        let consumed = mollusk.process_instruction(instruction, accounts).compute_units_consumed;
+        let limit = benched_cu(name);
+        assert!(consumed <= u64::from(limit), "{name} used {consumed} CUs, recommended {limit}");
+    }
+
+    runs.iter()
+        .fold(MolluskComputeUnitBencher::new(mollusk), |bencher, &(name, instruction, accounts)| {
+            bencher.bench((name, instruction, accounts))
+        })
         .must_pass(true)
-        .out_dir("benches/results")
+        // Run again with `--features verbose-logs` (program built with it too) to compare the cost of events
+        .out_dir(if cfg!(feature = "verbose-logs") { "benches/results/verbose-logs" } else { "benches/results" })
         .execute();
 }
\ No newline at end of file
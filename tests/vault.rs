@@ -4,7 +4,7 @@ use starframe_vault::*;
 mod tests {
     // bring program type into scope only when IDL generation test is enabled
     #[cfg(feature = "idl")]
-    use starframe_vault::VaultProgram;
+    use starframe_vault::{discriminators, VaultProgram};
 
     #[test]
     fn test_vault_initialization() {
@@ -31,6 +31,25 @@ mod tests {
         println!("Vault close test");
     }
 
+    #[test]
+    fn test_program_id() {
+        use star_frame::program::StarFrameProgram;
+        assert_eq!(
+            starframe_vault::VaultProgram::ID,
+            starframe_vault::PROGRAM_ID
+        );
+        assert_eq!(starframe_vault::ID, starframe_vault::PROGRAM_ID);
+
+        // Deployments take the address from Starpin.toml
+        let starpin = include_str!("../Starpin.toml");
+        let deployed = format!("starframe_vault = \"{}\"", starframe_vault::PROGRAM_ID);
+        assert_eq!(
+            starpin.matches(&deployed).count(),
+            3,
+            "Starpin.toml program ids"
+        );
+    }
+
     #[test]
     fn test_authority_validation() {
         // Test authority validation
@@ -45,6 +64,197 @@ mod tests {
         let codama_idl: ProgramNode = idl.try_into()?;
         let idl_json = codama_idl.to_json()?;
         std::fs::write("idl.json", &idl_json)?;
+
+        // Events aren't referenced by any instruction, check they still made it in
+        let idl: serde_json::Value = serde_json::from_str(&idl_json)?;
+        let defined_types = idl["definedTypes"].as_array().expect("definedTypes");
+        for (event, fields) in [
+            ("vaultOpenedEvent", &["owner", "state", "vault"][..]),
+            ("vaultClosedEvent", &["owner", "refunded"]),
+            ("tipEvent", &["from", "amount"]),
+        ] {
+            let defined_type = defined_types
+                .iter()
+                .find(|defined_type| defined_type["name"] == event)
+                .unwrap_or_else(|| panic!("{event} missing from the IDL"));
+            let field_names: Vec<_> = defined_type["type"]["fields"]
+                .as_array()
+                .expect("fields")
+                .iter()
+                .map(|field| field["name"].as_str().unwrap())
+                .collect();
+            assert_eq!(field_names, fields, "{event} fields");
+        }
+
+        // Front-ends label vaults from the IDL, so the currency tag must be part of the state
+        let vault_state = idl["accounts"]
+            .as_array()
+            .expect("accounts")
+            .iter()
+            .find(|account| account["name"] == "vaultState")
+            .expect("vaultState missing from the IDL");
+        assert!(vault_state["data"]["fields"]
+            .as_array()
+            .expect("fields")
+            .iter()
+            .any(|field| field["name"] == "currency"));
+        Ok(())
+    }
+
+    // The Mollusk tests build instructions from `discriminators`, check those agree with the IDL
+    // clients are generated from
+    #[cfg(feature = "idl")]
+    #[test]
+    fn idl_discriminators_match_constants() -> anyhow::Result<()> {
+        use star_frame::prelude::*;
+        let idl = VaultProgram::program_to_idl()?;
+        let codama_idl: ProgramNode = idl.try_into()?;
+        let idl: serde_json::Value = serde_json::from_str(&codama_idl.to_json()?)?;
+
+        let discriminator = |node: &serde_json::Value, fields: &str| -> String {
+            let field = node[fields]
+                .as_array()
+                .expect("fields")
+                .iter()
+                .find(|field| field["name"] == "discriminator")
+                .unwrap_or_else(|| panic!("{} has no discriminator", node["name"]));
+            field["defaultValue"]["data"]
+                .as_str()
+                .expect("discriminator bytes")
+                .to_owned()
+        };
+        let hex = |bytes: [u8; 8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        };
+
+        let instructions = idl["instructions"].as_array().expect("instructions");
+        let expected = [
+            ("assertBalanceIx", discriminators::ASSERT_BALANCE),
+            ("bindRecipientIx", discriminators::BIND_RECIPIENT),
+            ("cancelWithdrawIx", discriminators::CANCEL_WITHDRAW),
+            ("clearMetadataIx", discriminators::CLEAR_METADATA),
+            ("closeIx", discriminators::CLOSE),
+            ("closeManyIx", discriminators::CLOSE_MANY),
+            ("configureIx", discriminators::CONFIGURE),
+            ("deactivateIx", discriminators::DEACTIVATE),
+            ("delegateIx", discriminators::DELEGATE),
+            ("depositIx", discriminators::DEPOSIT),
+            ("depositRawIx", discriminators::DEPOSIT_RAW),
+            ("depositToOwnerIx", discriminators::DEPOSIT_TO_OWNER),
+            ("deriveVaultIx", discriminators::DERIVE_VAULT),
+            ("executeWithdrawIx", discriminators::EXECUTE_WITHDRAW),
+            ("guardianRecoverIx", discriminators::GUARDIAN_RECOVER),
+            ("harvestIx", discriminators::HARVEST),
+            (
+                "initializeAndDepositIx",
+                discriminators::INITIALIZE_AND_DEPOSIT,
+            ),
+            (
+                "initializeGlobalConfigIx",
+                discriminators::INITIALIZE_GLOBAL_CONFIG,
+            ),
+            ("initializeIx", discriminators::INITIALIZE),
+            ("initializeSharedIx", discriminators::INITIALIZE_SHARED),
+            (
+                "initializeStakeVaultIx",
+                discriminators::INITIALIZE_STAKE_VAULT,
+            ),
+            ("keeperSweepIx", discriminators::KEEPER_SWEEP),
+            ("normalizeBumpIx", discriminators::NORMALIZE_BUMP),
+            ("readStateIx", discriminators::READ_STATE),
+            (
+                "recoverStateLamportsIx",
+                discriminators::RECOVER_STATE_LAMPORTS,
+            ),
+            ("requestWithdrawIx", discriminators::REQUEST_WITHDRAW),
+            ("resizeStateIx", discriminators::RESIZE_STATE),
+            ("safeCloseIx", discriminators::SAFE_CLOSE),
+            ("setAdminIx", discriminators::SET_ADMIN),
+            ("setAllowlistRootIx", discriminators::SET_ALLOWLIST_ROOT),
+            ("setAutoForwardIx", discriminators::SET_AUTO_FORWARD),
+            ("setBurnDustIx", discriminators::SET_BURN_DUST),
+            ("setCloseAuthorityIx", discriminators::SET_CLOSE_AUTHORITY),
+            ("setDepositLimitsIx", discriminators::SET_DEPOSIT_LIMITS),
+            ("setEmergencyPausedIx", discriminators::SET_EMERGENCY_PAUSED),
+            (
+                "setFirstWithdrawDelayIx",
+                discriminators::SET_FIRST_WITHDRAW_DELAY,
+            ),
+            ("setGuardiansIx", discriminators::SET_GUARDIANS),
+            ("setKeeperSweepIx", discriminators::SET_KEEPER_SWEEP),
+            ("setLabelIx", discriminators::SET_LABEL),
+            ("setLogLevelIx", discriminators::SET_LOG_LEVEL),
+            (
+                "setMaxDepositPerTxIx",
+                discriminators::SET_MAX_DEPOSIT_PER_TX,
+            ),
+            ("setMaxSlotAgeIx", discriminators::SET_MAX_SLOT_AGE),
+            ("setMetadataIx", discriminators::SET_METADATA),
+            ("setRentBufferIx", discriminators::SET_RENT_BUFFER),
+            ("setRoundToIx", discriminators::SET_ROUND_TO),
+            ("setSandwichGuardIx", discriminators::SET_SANDWICH_GUARD),
+            (
+                "setWithdrawCooldownIx",
+                discriminators::SET_WITHDRAW_COOLDOWN,
+            ),
+            ("setWithdrawPolicyIx", discriminators::SET_WITHDRAW_POLICY),
+            ("setYieldProgramIx", discriminators::SET_YIELD_PROGRAM),
+            ("tipIx", discriminators::TIP),
+            ("withdrawIx", discriminators::WITHDRAW),
+            ("withdrawPercentIx", discriminators::WITHDRAW_PERCENT),
+            ("withdrawSharedIx", discriminators::WITHDRAW_SHARED),
+            ("withdrawStakeIx", discriminators::WITHDRAW_STAKE),
+            ("withdrawToProgramIx", discriminators::WITHDRAW_TO_PROGRAM),
+            ("withdrawTokenBatchIx", discriminators::WITHDRAW_TOKEN_BATCH),
+            (
+                "withdrawWithApprovalIx",
+                discriminators::WITHDRAW_WITH_APPROVAL,
+            ),
+            ("wrapDepositIx", discriminators::WRAP_DEPOSIT),
+        ];
+        assert_eq!(
+            instructions.len(),
+            expected.len(),
+            "every instruction needs a constant"
+        );
+        for (name, constant) in expected {
+            let instruction = instructions
+                .iter()
+                .find(|instruction| instruction["name"] == name)
+                .unwrap_or_else(|| panic!("{name} missing from the IDL"));
+            assert_eq!(
+                discriminator(instruction, "arguments"),
+                hex(constant),
+                "{name}"
+            );
+        }
+
+        let accounts = idl["accounts"].as_array().expect("accounts");
+        let expected = [
+            ("vaultState", discriminators::VAULT_STATE),
+            ("globalConfig", discriminators::GLOBAL_CONFIG),
+            ("sharedVaultState", discriminators::SHARED_VAULT_STATE),
+            ("vaultMetadata", discriminators::VAULT_METADATA),
+        ];
+        assert_eq!(
+            accounts.len(),
+            expected.len(),
+            "every account needs a constant"
+        );
+        for (name, constant) in expected {
+            let account = accounts
+                .iter()
+                .find(|account| account["name"] == name)
+                .unwrap_or_else(|| panic!("{name} missing from the IDL"));
+            assert_eq!(
+                discriminator(&account["data"], "fields"),
+                hex(constant),
+                "{name}"
+            );
+        }
         Ok(())
     }
 }